@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use lottie::prelude::Model;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Model::from_reader(data);
+});
@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use lottie::{DenyResolver, FontDB, Lottie};
+
+// Stages every layer of the animation, without reading fonts or assets from
+// the system
+fuzz_target!(|data: &[u8]| {
+    let _ = Lottie::from_reader_with_fontdb(data, FontDB::default(), &DenyResolver);
+});
@@ -0,0 +1,10 @@
+#!/bin/sh
+# Seed the corpus of every fuzz target with the lottie files of fixtures/
+set -e
+cd "$(dirname "$0")"
+for target in model timeline; do
+    mkdir -p "corpus/$target"
+    find ../../../fixtures -name '*.json' | while read -r file; do
+        cp "$file" "corpus/$target/$(echo "$file" | sed 's|^\.\./\.\./\.\./fixtures/||; s|/|_|g')"
+    done
+done
@@ -0,0 +1,254 @@
+use std::fmt;
+
+use crate::font::FontDB;
+use crate::layer::text::missing_chars;
+use crate::model::*;
+use crate::visit::{walk, Visit};
+
+/// How far the rendering of a feature diverges from the file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// The feature is rendered approximately
+    Warning,
+    /// The feature is dropped
+    Error,
+}
+
+/// A feature of the lottie format this crate does not support, or only
+/// partially
+#[derive(Debug, Clone, PartialEq)]
+pub enum Feature {
+    /// Layer effects, e.g. blurs and drop shadows
+    Effect(Option<String>),
+    /// Expressions, the keyframes of the property are used instead
+    Expression,
+    /// Layer types other than precompositions, solids, images, nulls, shapes,
+    /// texts and audio, e.g. cameras
+    LayerType(u64),
+    /// 3D layers, rendered flat
+    Layer3d,
+    /// Shape modifiers other than trims, e.g. repeaters or merge paths
+    ShapeModifier(&'static str),
+    /// Trims of multiple shapes applied simultaneously
+    TrimSimultaneously,
+    /// Masks other than add, subtract and none
+    MaskMode(MaskMode),
+    InvertedMask,
+    MaskExpansion,
+    /// Masks of layers other than shape layers, e.g. of precompositions or
+    /// images. Masks text layers follow as a path are supported
+    LayerMask(&'static str),
+    /// Skew of transforms, ignored outside of text animators
+    Skew,
+    StrokeDashes,
+    /// Chars of a text that neither its font nor the fallbacks contain, drawn
+    /// as `.notdef` boxes
+    MissingGlyphs(String),
+}
+
+impl Feature {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Feature::Expression | Feature::Layer3d | Feature::MaskExpansion | Feature::Skew => {
+                Severity::Warning
+            }
+            _ => Severity::Error,
+        }
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Feature::Effect(Some(name)) => write!(f, "effect {} is not supported", name),
+            Feature::Effect(None) => f.write_str("effects are not supported"),
+            Feature::Expression => f.write_str("expressions are not evaluated"),
+            Feature::LayerType(ty) => write!(f, "layer type {} is not supported", ty),
+            Feature::Layer3d => f.write_str("3D layers are rendered flat"),
+            Feature::ShapeModifier(name) => write!(f, "{} is not supported", name),
+            Feature::TrimSimultaneously => {
+                f.write_str("trimming multiple shapes simultaneously is not supported")
+            }
+            Feature::MaskMode(mode) => write!(f, "mask mode {:?} is not supported", mode),
+            Feature::InvertedMask => f.write_str("inverted masks are not supported"),
+            Feature::MaskExpansion => f.write_str("mask expansion is ignored"),
+            Feature::LayerMask(kind) => write!(f, "masks of {} layers are not supported", kind),
+            Feature::Skew => f.write_str("skew is ignored"),
+            Feature::StrokeDashes => f.write_str("dashed strokes are not supported"),
+            Feature::MissingGlyphs(chars) => {
+                write!(f, "no font contains the glyphs of {:?}", chars)
+            }
+        }
+    }
+}
+
+/// An unsupported feature used by an animation, at the JSON path of the value
+/// using it, e.g. `layers[3].shapes[1].it[2]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub path: String,
+    pub feature: Feature,
+    pub severity: Severity,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{} at `{}`: {}", severity, self.path, self.feature)
+    }
+}
+
+/// List the features of `model` that are unsupported or only partially
+/// supported, so that files could be fixed before being handed off
+pub fn analyze(model: &Model) -> Vec<Diagnostic> {
+    let mut analyzer = Analyzer::default();
+    walk(model, &mut analyzer);
+    analyzer.diagnostics
+}
+
+/// List the text layers of `model` with chars no font of `fontdb` contains,
+/// once per layer, at the path of its document
+pub(crate) fn missing_glyphs(model: &Model, fontdb: &FontDB) -> Vec<Diagnostic> {
+    let mut checker = GlyphChecker {
+        model,
+        fontdb,
+        analyzer: Analyzer::default(),
+    };
+    walk(model, &mut checker);
+    checker.analyzer.diagnostics
+}
+
+#[derive(Default)]
+struct Analyzer {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Analyzer {
+    fn push(&mut self, path: String, feature: Feature) {
+        self.diagnostics.push(Diagnostic {
+            path,
+            severity: feature.severity(),
+            feature,
+        });
+    }
+
+    /// Masks are only rendered on shape layers, and text layers use the mask
+    /// they follow as a path
+    fn layer_masks(&mut self, path: &str, layer: &Layer) {
+        if !layer.has_mask {
+            return;
+        }
+        let (kind, follow_path) = match &layer.content {
+            LayerContent::PreCompositionRef(_) => ("precomposition", None),
+            LayerContent::SolidColor { .. } => ("solid", None),
+            LayerContent::MediaRef(_) | LayerContent::Media(_) => ("image", None),
+            LayerContent::Text(text) => ("text", text.follow_path.mask),
+            _ => return,
+        };
+        for (index, mask) in layer.masks_properties.iter().enumerate() {
+            if mask.mode != MaskMode::None && follow_path != Some(index) {
+                let path = format!("{}.masksProperties[{}]", path, index);
+                self.push(path, Feature::LayerMask(kind));
+            }
+        }
+    }
+}
+
+impl Visit for Analyzer {
+    fn layer(&mut self, path: &str, layer: &Layer) {
+        if layer.is_3d {
+            self.push(format!("{}.ddd", path), Feature::Layer3d);
+        }
+        for (index, effect) in layer.effects.iter().enumerate() {
+            let feature = Feature::Effect(effect.name.clone());
+            self.push(format!("{}.ef[{}]", path, index), feature);
+        }
+        match &layer.content {
+            LayerContent::Text(text) if text.document.expression.is_some() => {
+                self.push(format!("{}.t.d.x", path), Feature::Expression)
+            }
+            LayerContent::Unsupported(ty) => {
+                self.push(format!("{}.ty", path), Feature::LayerType(*ty))
+            }
+            _ => {}
+        }
+        self.layer_masks(path, layer);
+    }
+
+    fn mask(&mut self, path: &str, mask: &Mask) {
+        match mask.mode {
+            MaskMode::Add | MaskMode::Subtract | MaskMode::None => {}
+            mode => self.push(format!("{}.mode", path), Feature::MaskMode(mode)),
+        }
+        if mask.inverted {
+            self.push(format!("{}.inv", path), Feature::InvertedMask);
+        }
+        if let Some(expand) = &mask.expand {
+            if expand.is_animated() || expand.initial_value() != 0.0 {
+                self.push(format!("{}.e", path), Feature::MaskExpansion);
+            }
+        }
+    }
+
+    fn transform(&mut self, path: &str, transform: &Transform) {
+        if let Some(skew) = &transform.skew {
+            if skew.is_animated() || skew.initial_value() != 0.0 {
+                self.push(format!("{}.sk", path), Feature::Skew);
+            }
+        }
+    }
+
+    fn shape(&mut self, path: &str, shape: &Shape) {
+        let modifier = match shape {
+            Shape::Stroke(Stroke { dashes, .. })
+            | Shape::GradientStroke(GradientStroke { dashes, .. })
+                if !dashes.is_empty() =>
+            {
+                self.push(format!("{}.d", path), Feature::StrokeDashes);
+                None
+            }
+            Shape::Trim(trim) if trim.multiple_shape == TrimMultipleShape::Simultaneously => {
+                self.push(format!("{}.m", path), Feature::TrimSimultaneously);
+                None
+            }
+            Shape::Repeater { .. } => Some("repeater"),
+            Shape::RoundedCorners { .. } => Some("rounded corners"),
+            Shape::PuckerBloat { .. } => Some("pucker and bloat"),
+            Shape::Twist { .. } => Some("twist"),
+            Shape::Merge { .. } => Some("merge paths"),
+            Shape::OffsetPath { .. } => Some("offset path"),
+            Shape::ZigZag { .. } => Some("zig zag"),
+            _ => None,
+        };
+        if let Some(name) = modifier {
+            self.push(path.to_string(), Feature::ShapeModifier(name));
+        }
+    }
+
+    fn animated<T>(&mut self, path: &str, key: &str, animated: &Animated<T>) {
+        if animated.expression.is_some() {
+            self.push(format!("{}.{}.x", path, key), Feature::Expression);
+        }
+    }
+}
+
+struct GlyphChecker<'a> {
+    model: &'a Model,
+    fontdb: &'a FontDB,
+    analyzer: Analyzer,
+}
+
+impl Visit for GlyphChecker<'_> {
+    fn layer(&mut self, path: &str, layer: &Layer) {
+        if let LayerContent::Text(text) = &layer.content {
+            let chars = missing_chars(text, self.model, self.fontdb);
+            if !chars.is_empty() {
+                let feature = Feature::MissingGlyphs(chars.into_iter().collect());
+                self.analyzer.push(format!("{}.t.d", path), feature);
+            }
+        }
+    }
+}
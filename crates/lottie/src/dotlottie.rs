@@ -0,0 +1,389 @@
+use std::collections::HashMap;
+use std::io::{Read, Seek, Write};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::model::{Asset, FontPathOrigin, Model};
+use crate::resolver::{read_bounded, AssetKind, AssetResolver};
+use crate::{Error, FontDB, LoadLimits, Lottie};
+
+/// Animation listed in the manifest of a dotLottie archive
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ManifestAnimation {
+    pub id: String,
+    /// Playback settings and other fields, kept as written
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Theme listed in the manifest of a dotLottie archive
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ManifestTheme {
+    pub id: String,
+    /// Animations the theme applies to, all of them when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub animations: Vec<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// `manifest.json` of a dotLottie archive
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Manifest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generator: Option<String>,
+    pub animations: Vec<ManifestAnimation>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub themes: Vec<ManifestTheme>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Manifest {
+            version: Some("2".to_string()),
+            generator: Some(concat!("lottie-rs ", env!("CARGO_PKG_VERSION")).to_string()),
+            animations: vec![],
+            themes: vec![],
+            extra: Map::new(),
+        }
+    }
+}
+
+/// A dotLottie (`.lottie`) archive: a zip of a manifest, animations, their
+/// images and fonts, and themes. Both the `animations/`, `images/` layout
+/// of version 1 and the `a/`, `i/`, `t/` layout of version 2 are read
+pub struct DotLottie {
+    manifest: Manifest,
+    files: HashMap<String, Vec<u8>>,
+    /// Limits the animations are parsed with
+    limits: LoadLimits,
+}
+
+impl DotLottie {
+    pub fn from_reader<R: Read + Seek>(r: R) -> Result<Self, Error> {
+        DotLottie::from_reader_with_limits(r, &LoadLimits::default())
+    }
+
+    /// Same as [DotLottie::from_reader], refusing JSON files larger than
+    /// `max_json_bytes` and other files larger than `max_asset_bytes` of
+    /// `limits` once decompressed. Animations are then parsed within
+    /// `limits`
+    pub fn from_reader_with_limits<R: Read + Seek>(
+        r: R,
+        limits: &LoadLimits,
+    ) -> Result<Self, Error> {
+        let mut archive = zip::ZipArchive::new(r)?;
+        let mut files = HashMap::new();
+        for index in 0..archive.len() {
+            let file = archive.by_index(index)?;
+            if file.is_dir() {
+                continue;
+            }
+            let name = file.name().trim_start_matches('/').to_string();
+            let max_bytes = if name.ends_with(".json") {
+                limits.max_json_bytes
+            } else {
+                limits.max_asset_bytes
+            };
+            files.insert(name, read_bounded(file, max_bytes)?);
+        }
+        let manifest = files
+            .get("manifest.json")
+            .ok_or_else(|| Error::MalformedDotLottie("manifest.json is missing".to_string()))?;
+        let manifest = serde_json::from_slice(manifest)
+            .map_err(|e| Error::MalformedDotLottie(format!("manifest.json: {}", e)))?;
+        Ok(DotLottie {
+            manifest,
+            files,
+            limits: limits.clone(),
+        })
+    }
+
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    /// Animations of the archive, in the order of the manifest
+    pub fn animations(&self) -> &[ManifestAnimation] {
+        &self.manifest.animations
+    }
+
+    pub fn themes(&self) -> &[ManifestTheme] {
+        &self.manifest.themes
+    }
+
+    /// Parse the animation `id`, with the slots of its properties replaced by
+    /// the rules of `theme` if any, or by their default values
+    pub fn model(&self, id: &str, theme: Option<&str>) -> Result<Model, Error> {
+        if !self.animations().iter().any(|animation| animation.id == id) {
+            return Err(Error::AnimationNotFound(id.to_string()));
+        }
+        let data = self
+            .file(&[format!("a/{}.json", id), format!("animations/{}.json", id)])
+            .ok_or_else(|| Error::AnimationNotFound(id.to_string()))?;
+        let mut value: Value = serde_json::from_slice(data).map_err(crate::model::Error::from)?;
+        if let Some(theme) = theme {
+            apply_theme(&mut value, &self.theme(theme)?, id);
+        }
+        let slotted = resolve_slots(&mut value);
+        // positions of failures only hold for the animation file as is
+        let bytes = (theme.is_none() && !slotted).then_some(data);
+        self.limits.model_from_json(value, bytes)
+    }
+
+    /// Load the animation `id` with `theme` applied, its images and fonts
+    /// are only resolved from the archive
+    pub fn load(&self, id: &str, theme: Option<&str>, fontdb: FontDB) -> Result<Lottie, Error> {
+        let model = self.model(id, theme)?;
+        Lottie::with_fontdb(model, fontdb, self)
+    }
+
+    fn theme(&self, id: &str) -> Result<Value, Error> {
+        if !self.themes().iter().any(|theme| theme.id == id) {
+            return Err(Error::ThemeNotFound(id.to_string()));
+        }
+        // version 1 themes are Lottie Style Sheets, which are not supported
+        let data = self
+            .file(&[format!("t/{}.json", id), format!("themes/{}.json", id)])
+            .ok_or_else(|| Error::ThemeNotFound(id.to_string()))?;
+        serde_json::from_slice(data)
+            .map_err(|e| Error::MalformedDotLottie(format!("theme {}: {}", id, e)))
+    }
+
+    fn file(&self, candidates: &[String]) -> Option<&[u8]> {
+        candidates
+            .iter()
+            .find_map(|name| self.files.get(name))
+            .map(Vec::as_slice)
+    }
+}
+
+impl AssetResolver for DotLottie {
+    fn resolve(&self, path: &str, kind: AssetKind) -> Result<Vec<u8>, Error> {
+        let path = path.trim_start_matches('/');
+        let name = path.rsplit('/').next().unwrap_or(path);
+        let folders: &[&str] = match kind {
+            AssetKind::Media => &["i", "images", "u", "audio"],
+            AssetKind::Font => &["f", "fonts"],
+        };
+        let mut candidates = vec![path.to_string()];
+        candidates.extend(folders.iter().map(|folder| format!("{}/{}", folder, name)));
+        self.file(&candidates)
+            .map(<[u8]>::to_vec)
+            .ok_or_else(|| Error::AssetNotFound(path.to_string()))
+    }
+}
+
+/// Packs animations, with their images and fonts, and themes into a
+/// dotLottie archive of version 2
+#[derive(Default)]
+pub struct DotLottieWriter {
+    manifest: Manifest,
+    files: Vec<(String, Vec<u8>)>,
+}
+
+impl DotLottieWriter {
+    pub fn new() -> Self {
+        DotLottieWriter::default()
+    }
+
+    /// Add `model` as the animation `id`. Its images and the fonts it loads
+    /// from urls are read through `resolver` and bundled, embedded images are
+    /// kept in the animation
+    pub fn add_animation(
+        &mut self,
+        id: &str,
+        model: &Model,
+        resolver: &dyn AssetResolver,
+    ) -> Result<&mut ManifestAnimation, Error> {
+        let mut model = model.clone();
+        for asset in &mut model.assets {
+            let Asset::Media(media) = asset else {
+                continue;
+            };
+            let path = media.path();
+            if path.starts_with("data:") {
+                continue;
+            }
+            let data = resolver.resolve(&path, AssetKind::Media)?;
+            media.filename = self.add_file("i", &media.filename, data);
+            media.pwd = "/i/".to_string();
+            media.embedded = false;
+        }
+        for font in &mut model.fonts.list {
+            if font.origin != FontPathOrigin::FontUrl {
+                continue;
+            }
+            let Some(path) = &font.path else {
+                continue;
+            };
+            let data = resolver.resolve(path, AssetKind::Font)?;
+            let name = path.rsplit('/').next().unwrap_or(path).to_string();
+            font.path = Some(format!("/f/{}", self.add_file("f", &name, data)));
+        }
+        let data = serde_json::to_vec(&model).map_err(|e| Error::Io(e.into()))?;
+        self.files.push((format!("a/{}.json", id), data));
+        self.manifest.animations.push(ManifestAnimation {
+            id: id.to_string(),
+            extra: Map::new(),
+        });
+        Ok(self.manifest.animations.last_mut().unwrap())
+    }
+
+    /// Add the theme `id`, whose `rules` set the slots of the animations,
+    /// e.g. `{"rules": [{"id": "background", "type": "Color", "value": [1, 0,
+    /// 0, 1]}]}`
+    pub fn add_theme(&mut self, id: &str, theme: &Value) -> Result<&mut ManifestTheme, Error> {
+        let data = serde_json::to_vec(theme).map_err(|e| Error::Io(e.into()))?;
+        self.files.push((format!("t/{}.json", id), data));
+        self.manifest.themes.push(ManifestTheme {
+            id: id.to_string(),
+            animations: vec![],
+            extra: Map::new(),
+        });
+        Ok(self.manifest.themes.last_mut().unwrap())
+    }
+
+    pub fn manifest_mut(&mut self) -> &mut Manifest {
+        &mut self.manifest
+    }
+
+    pub fn write<W: Write + Seek>(&self, w: W) -> Result<(), Error> {
+        let mut zip = zip::ZipWriter::new(w);
+        let options =
+            zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        let manifest = serde_json::to_vec(&self.manifest).map_err(|e| Error::Io(e.into()))?;
+        zip.start_file("manifest.json", options)?;
+        zip.write_all(&manifest)?;
+        for (name, data) in &self.files {
+            zip.start_file(name.as_str(), options)?;
+            zip.write_all(data)?;
+        }
+        zip.finish()?;
+        Ok(())
+    }
+
+    /// Store `data` as `name` in `folder`, renamed if another file already
+    /// uses the name. Returns the name of the stored file
+    fn add_file(&mut self, folder: &str, name: &str, data: Vec<u8>) -> String {
+        let mut stored = name.to_string();
+        let mut count = 0;
+        loop {
+            let path = format!("{}/{}", folder, stored);
+            match self.files.iter().find(|(existing, _)| *existing == path) {
+                Some((_, existing)) if *existing == data => return stored,
+                Some(_) => {
+                    count += 1;
+                    stored = format!("{}_{}", count, name);
+                }
+                None => {
+                    self.files.push((path, data));
+                    return stored;
+                }
+            }
+        }
+    }
+}
+
+/// Write the rules of `theme` that apply to `animation` into the `slots` of
+/// the animation
+fn apply_theme(value: &mut Value, theme: &Value, animation: &str) {
+    let Some(rules) = theme.get("rules").and_then(Value::as_array) else {
+        return;
+    };
+    let Value::Object(root) = value else {
+        return;
+    };
+    let slots = root
+        .entry("slots")
+        .or_insert_with(|| Value::Object(Map::new()));
+    let Value::Object(slots) = slots else {
+        return;
+    };
+    for rule in rules {
+        let Some(id) = rule.get("id").and_then(Value::as_str) else {
+            continue;
+        };
+        if let Some(Value::Array(animations)) = rule.get("animations") {
+            if !animations.iter().any(|a| a.as_str() == Some(animation)) {
+                continue;
+            }
+        }
+        let property = if let Some(Value::Array(keyframes)) = rule.get("keyframes") {
+            json!({"a": 1, "k": keyframes.iter().map(keyframe).collect::<Vec<_>>()})
+        } else if let Some(value) = rule.get("value") {
+            match rule.get("type").and_then(Value::as_str) {
+                Some("Text") => json!({"k": [{"t": 0, "s": value}]}),
+                Some("Image") => {
+                    log::warn!("image theme rule {} is not supported", id);
+                    continue;
+                }
+                _ => json!({"a": 0, "k": value}),
+            }
+        } else {
+            continue;
+        };
+        slots.insert(id.to_string(), json!({ "p": property }));
+    }
+}
+
+/// Lottie keyframe of a theme rule keyframe
+fn keyframe(keyframe: &Value) -> Value {
+    let mut result = Map::new();
+    if let Some(frame) = keyframe.get("frame") {
+        result.insert("t".to_string(), frame.clone());
+    }
+    if let Some(value) = keyframe.get("value") {
+        let value = match value {
+            Value::Number(_) => json!([value]),
+            _ => value.clone(),
+        };
+        result.insert("s".to_string(), value);
+    }
+    if let Some(tangent) = keyframe.get("inTangent") {
+        result.insert("i".to_string(), tangent.clone());
+    }
+    if let Some(tangent) = keyframe.get("outTangent") {
+        result.insert("o".to_string(), tangent.clone());
+    }
+    if keyframe.get("hold").and_then(Value::as_bool) == Some(true) {
+        result.insert("h".to_string(), json!(1));
+    }
+    Value::Object(result)
+}
+
+/// Replace the properties bound to a slot by their `sid` with the property
+/// `p` of the slot, returns whether any property was replaced
+fn resolve_slots(value: &mut Value) -> bool {
+    let Some(Value::Object(slots)) = value.get("slots").cloned() else {
+        return false;
+    };
+    let mut resolved = false;
+    let mut stack = vec![value];
+    while let Some(value) = stack.pop() {
+        match value {
+            Value::Object(object) => {
+                let property = object
+                    .get("sid")
+                    .and_then(Value::as_str)
+                    .and_then(|sid| slots.get(sid))
+                    .and_then(|slot| slot.get("p"));
+                if let Some(Value::Object(property)) = property {
+                    for (key, value) in property {
+                        object.insert(key.clone(), value.clone());
+                    }
+                    resolved = true;
+                }
+                stack.extend(object.values_mut());
+            }
+            Value::Array(values) => stack.extend(values.iter_mut()),
+            _ => {}
+        }
+    }
+    resolved
+}
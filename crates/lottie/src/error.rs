@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::limits::Limit;
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("Shape must have a sibling Transform")]
@@ -12,18 +14,35 @@ pub enum Error {
     FontFamilyNotFound(String),
     #[error("Font family {0} cannot be loaded")]
     FontNotLoaded(String),
-    #[error("Font family {0} doesn't contain the glyph for {1}")]
-    FontGlyphNotFound(String, char),
+    #[cfg(feature = "network")]
     #[error(transparent)]
     Network(#[from] ureq::Error),
-    #[error("Url {0} response contains no Content-Length header")]
-    NetworkMissingContentLength(String),
-    #[error("Url {0} response contains invalid Content-Length header")]
-    NetworkMalformedContentLength(String),
+    #[cfg(feature = "dotlottie")]
+    #[error(transparent)]
+    Zip(#[from] zip::result::ZipError),
+    #[cfg(feature = "dotlottie")]
+    #[error("Malformed dotLottie archive: {0}")]
+    MalformedDotLottie(String),
+    #[cfg(feature = "dotlottie")]
+    #[error("Animation {0} not found in the dotLottie archive")]
+    AnimationNotFound(String),
+    #[cfg(feature = "dotlottie")]
+    #[error("Theme {0} not found in the dotLottie archive")]
+    ThemeNotFound(String),
     #[error(transparent)]
     Io(#[from] std::io::Error),
     #[error(transparent)]
     Url(#[from] url::ParseError),
     #[error(transparent)]
     Base64Decode(#[from] base64::DecodeError),
+    #[error("Animation exceeds the {limit} limit of {max}")]
+    LimitExceeded { limit: Limit, max: usize },
+    #[error("Layer {0} is its own ancestor")]
+    ParentCycle(u32),
+    #[error("Parent layer {0} is ambiguous, several layers have this index")]
+    AmbiguousParent(u32),
+    #[error("Asset {0} not found")]
+    AssetNotFound(String),
+    #[error("Asset {0} denied by the asset resolver")]
+    AssetDenied(String),
 }
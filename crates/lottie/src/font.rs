@@ -1,58 +1,182 @@
 use std::collections::HashMap;
-use std::io::Read;
 use std::ops::Deref;
+use std::sync::{Arc, Mutex};
 
 use crate::model::{Font as LottieFont, FontPathOrigin, Model};
 use fontkit::{Font, FontKey, FontKit};
 
+use crate::resolver::{AssetKind, AssetResolver};
 use crate::Error;
+use matching::face_index;
+pub use matching::FontRequest;
 
-const BUFFER_LIMIT: usize = 10 * 1_024 * 1_024;
+mod matching;
+
+/// Family used when no font matches a text layer font
+const DEFAULT_FONT: &str = "Fira Mono";
+
+/// Families tried in order for glyphs missing from the font of a text layer
+const DEFAULT_FALLBACKS: &[&str] = &[
+    "Noto Sans",
+    "Noto Sans CJK SC",
+    "Noto Color Emoji",
+    "Apple Color Emoji",
+    "Segoe UI Emoji",
+    "Segoe UI Symbol",
+    "PingFang SC",
+    "Microsoft YaHei",
+    "Arial Unicode MS",
+    "Fira Mono",
+];
+
+/// Raw data of a font file, with the index of the face used in font
+/// collections
+type FontFile = (Arc<Vec<u8>>, u32);
+
+/// Raw data of a font, with the variation coordinates to shape it with
+pub(crate) struct FontFace {
+    pub data: Arc<Vec<u8>>,
+    /// Index of the face in `data`, for font collections
+    pub index: u32,
+    pub variations: Vec<rustybuzz::Variation>,
+}
 
 pub struct FontDB {
     fontkit: FontKit,
     font_map: HashMap<String, Vec<FontKey>>,
+    /// Raw font files by lottie font name, needed for shaping
+    buffers: Mutex<HashMap<String, FontFile>>,
+    /// Font families looked up for glyphs missing from a text layer font
+    fallbacks: Vec<String>,
+    /// Raw font files of fallback families, `None` if the family is not found
+    fallback_buffers: Mutex<HashMap<String, Option<FontFile>>>,
+    /// Fonts registered from memory, by the family they were registered as
+    families: HashMap<String, Vec<FontKey>>,
+    /// Raw data of fonts registered from memory or loaded from urls
+    memory: HashMap<FontKey, Arc<Vec<u8>>>,
+    default_font: Option<String>,
+    /// Whether fonts installed on the system are scanned
+    system_fonts: bool,
+    system_fonts_loaded: bool,
+}
+
+impl Default for FontDB {
+    fn default() -> Self {
+        FontDB::new(FontKit::new())
+    }
 }
 
 impl FontDB {
+    /// A font database only holding the fonts of `fontkit`. Fonts of the
+    /// system are not scanned unless enabled by [FontDB::set_system_fonts]
     pub fn new(fontkit: FontKit) -> Self {
         FontDB {
             fontkit,
             font_map: HashMap::new(),
+            buffers: Mutex::new(HashMap::new()),
+            fallbacks: DEFAULT_FALLBACKS.iter().map(|s| s.to_string()).collect(),
+            fallback_buffers: Mutex::new(HashMap::new()),
+            families: HashMap::new(),
+            memory: HashMap::new(),
+            default_font: Some(DEFAULT_FONT.to_string()),
+            system_fonts: false,
+            system_fonts_loaded: false,
+        }
+    }
+
+    /// Register the fonts of a font file under `family`. Text layers of that
+    /// family use them before any font found on the system, matched on
+    /// weight, italic and stretch
+    pub fn register_font(&mut self, family: &str, data: Vec<u8>) -> Result<(), Error> {
+        let keys = self.fontkit.add_font_from_buffer(data.clone())?;
+        let data = Arc::new(data);
+        for key in &keys {
+            self.memory.insert(key.clone(), data.clone());
+        }
+        self.families
+            .entry(family.to_string())
+            .or_default()
+            .extend(keys);
+        // fonts found before may not be the best matches anymore
+        self.clear_buffers();
+        self.clear_fallback_buffers();
+        Ok(())
+    }
+
+    /// Family used when no font matches a text layer font, `Fira Mono` by
+    /// default
+    pub fn default_font(&self) -> Option<&str> {
+        self.default_font.as_deref()
+    }
+
+    /// Set the family used when no font matches a text layer font. Without
+    /// one, text layers whose font is missing are skipped
+    pub fn set_default_font(&mut self, family: Option<String>) {
+        self.default_font = family;
+        self.clear_buffers();
+    }
+
+    fn clear_buffers(&mut self) {
+        if let Ok(buffers) = self.buffers.get_mut() {
+            buffers.clear();
+        }
+    }
+
+    fn clear_fallback_buffers(&mut self) {
+        if let Ok(buffers) = self.fallback_buffers.get_mut() {
+            buffers.clear();
         }
     }
 
-    pub fn load_fonts_from_model(&mut self, model: &Model) -> Result<(), Error> {
-        // load default font
-        #[cfg(not(target_arch = "wasm32"))]
+    /// Whether fonts installed on the system are scanned when loading an
+    /// animation
+    pub fn system_fonts(&self) -> bool {
+        self.system_fonts
+    }
+
+    /// Turn scanning of the fonts installed on the system on or off. Fonts
+    /// already scanned stay available
+    pub fn set_system_fonts(&mut self, enabled: bool) {
+        self.system_fonts = enabled;
+    }
+
+    fn load_system_fonts(&mut self) -> Result<(), Error> {
+        if self.system_fonts_loaded {
+            return Ok(());
+        }
+        #[cfg(not(all(target_os = "unknown", target_arch = "wasm32")))]
         {
-            let current_exe = std::env::current_exe()?;
-            let mut path = current_exe.clone();
-            path.push("assets/FiraMono-Regular.ttf");
-
-            while !path.exists() && path.parent().is_some() {
-                path.pop();
-                path.pop();
-                path.pop();
-                path.push("assets/FiraMono-Regular.ttf");
-            }
-            if path.exists() {
-                self.fontkit.search_fonts_from_path(
-                    &path.into_os_string().into_string().unwrap_or_default(),
-                )?;
+            if let Some(path) = dirs::font_dir() {
+                self.fontkit.search_fonts_from_path(path)?;
             }
+            #[cfg(target_os = "macos")]
+            self.fontkit
+                .search_fonts_from_path(std::path::PathBuf::from("/System/Library/Fonts"))?;
+        }
+        self.system_fonts_loaded = true;
+        Ok(())
+    }
+
+    /// Scan system fonts if enabled, and load the fonts `model` references
+    /// by url through `resolver`
+    pub fn load_fonts_from_model(
+        &mut self,
+        model: &Model,
+        resolver: &dyn AssetResolver,
+    ) -> Result<(), Error> {
+        if self.system_fonts {
+            self.load_system_fonts()?;
         }
         // load remote fonts
         for font in &model.fonts.list {
             if let Some(path) = font.path.as_ref() {
                 if font.origin == FontPathOrigin::FontUrl {
-                    let response = ureq::get(path).call()?;
-                    let mut bytes = vec![];
-                    response
-                        .into_reader()
-                        .take((BUFFER_LIMIT + 1) as u64)
-                        .read_to_end(&mut bytes)?;
-                    let keys = self.fontkit.add_font_from_buffer(bytes)?;
+                    let bytes = resolver.resolve(path, AssetKind::Font)?;
+                    let keys = self.fontkit.add_font_from_buffer(bytes.clone())?;
+                    let data = Arc::new(bytes);
+                    for key in &keys {
+                        self.memory.insert(key.clone(), data.clone());
+                    }
                     self.font_map.insert(font.name.clone(), keys);
                 }
             }
@@ -60,26 +184,150 @@ impl FontDB {
         Ok(())
     }
 
+    /// Font used for `font`. Local fonts are looked up among the registered
+    /// fonts of the family, then matched on family, weight, italic and
+    /// stretch as described in [FontRequest], then looked up by name, then
+    /// replaced by the default font. Fonts loaded from an url are matched
+    /// among the fonts of that file
     pub fn font(&self, font: &LottieFont) -> Option<impl Deref<Target = Font> + '_> {
+        let request = FontRequest::from_font(font);
         match font.origin {
             // This is not an html player. So we treat script/css urls as local obtained fonts
             // TODO: could this be a thing in WASM target?
             FontPathOrigin::Local | FontPathOrigin::ScriptUrl | FontPathOrigin::CssUrl => self
-                .fontkit
-                .query(&FontKey::new_with_family(font.name.clone()))
+                .registered(&request)
+                .and_then(|key| self.fontkit.query(key))
                 .or_else(|| {
-                    self.fontkit
-                        .query(&FontKey::new_with_family(font.family.clone()))
+                    let keys = self
+                        .fontkit
+                        .font_keys()
+                        .filter(|key| key.family == request.family)
+                        .collect::<Vec<_>>();
+                    self.fontkit.query(request.best(&keys)?)
                 })
                 .or_else(|| {
-                    // default font
                     self.fontkit
-                        .query(&FontKey::new_with_family("Fira Mono".to_string()))
+                        .query(&FontKey::new_with_family(font.name.clone()))
+                })
+                .or_else(|| {
+                    let family = self.default_font.clone()?;
+                    let request = FontRequest {
+                        family: family.clone(),
+                        ..request.clone()
+                    };
+                    self.registered(&request)
+                        .and_then(|key| self.fontkit.query(key))
+                        .or_else(|| self.fontkit.query(&FontKey::new_with_family(family)))
                 }),
-            // TODO: What if font from url is *.ttc and font.name points to one font in the
-            // collection? Could this be possible?
-            FontPathOrigin::FontUrl => self.fontkit.query(self.font_map.get(&font.name)?.first()?),
+            FontPathOrigin::FontUrl => self
+                .fontkit
+                .query(request.best(self.font_map.get(&font.name)?)?),
+        }
+    }
+
+    /// Best match for `request` among the fonts registered under its family
+    fn registered(&self, request: &FontRequest) -> Option<&FontKey> {
+        request.best(self.families.get(&request.family)?)
+    }
+
+    /// Raw data of the font file used for `font`. Fonts found on disk are read
+    /// once and cached
+    pub fn font_data(&self, font: &LottieFont) -> Option<Arc<Vec<u8>>> {
+        self.font_file(font).map(|(data, _)| data)
+    }
+
+    /// Raw data of the font file used for `font`
+    fn font_file(&self, font: &LottieFont) -> Option<FontFile> {
+        let mut buffers = self.buffers.lock().ok()?;
+        if let Some(file) = buffers.get(&font.name) {
+            return Some(file.clone());
+        }
+        let found = self.font(font)?;
+        let key = found.key();
+        let data = match self.memory.get(&key) {
+            Some(data) => data.clone(),
+            None => Arc::new(std::fs::read(found.path()?).ok()?),
+        };
+        let file = (data.clone(), face_index(&data, &key));
+        buffers.insert(font.name.clone(), file.clone());
+        Some(file)
+    }
+
+    /// Font families looked up, in order, for glyphs missing from the font
+    /// of a text layer
+    pub fn fallbacks(&self) -> &[String] {
+        &self.fallbacks
+    }
+
+    /// Replace the fallback chain. Glyphs missing from a text layer font are
+    /// taken from the first family of `families` containing them
+    pub fn set_fallbacks(&mut self, families: Vec<String>) {
+        self.fallbacks = families;
+        self.clear_fallback_buffers();
+    }
+
+    /// `font` followed by the fallback fonts needed for the chars of `text`
+    /// it lacks. Chars no font contains are left to the `.notdef` glyph of
+    /// `font`. Variable fonts are set to the weight and style of `font`
+    pub(crate) fn font_chain(&self, font: &LottieFont, text: &str) -> Option<Vec<FontFace>> {
+        let (primary, index) = self.font_file(font)?;
+        let (variations, mut missing) = {
+            let face = rustybuzz::Face::from_slice(&primary, index)?;
+            let missing = text
+                .chars()
+                .filter(|c| !c.is_whitespace() && !c.is_control())
+                .filter(|c| face.glyph_index(*c).is_none())
+                .collect::<Vec<_>>();
+            (FontRequest::from_font(font).variations(&face), missing)
+        };
+        let mut chain = vec![FontFace {
+            data: primary,
+            index,
+            variations,
+        }];
+        for family in &self.fallbacks {
+            if missing.is_empty() {
+                break;
+            }
+            let (data, index) = match self.fallback_file(family) {
+                Some(file) => file,
+                None => continue,
+            };
+            let face = match rustybuzz::Face::from_slice(&data, index) {
+                Some(face) => face,
+                None => continue,
+            };
+            let count = missing.len();
+            missing.retain(|c| face.glyph_index(*c).is_none());
+            if missing.len() < count {
+                chain.push(FontFace {
+                    data: data.clone(),
+                    index,
+                    variations: vec![],
+                });
+            }
         }
+        Some(chain)
+    }
+
+    fn fallback_file(&self, family: &str) -> Option<FontFile> {
+        let mut buffers = self.fallback_buffers.lock().ok()?;
+        buffers
+            .entry(family.to_string())
+            .or_insert_with(|| {
+                if let Some(key) = self.families.get(family).and_then(|keys| keys.first()) {
+                    let data = self.memory.get(key)?.clone();
+                    let index = face_index(&data, key);
+                    return Some((data, index));
+                }
+                let font = self
+                    .fontkit
+                    .query(&FontKey::new_with_family(family.to_string()))?;
+                let data = std::fs::read(font.path()?).ok()?;
+                let index = face_index(&data, &font.key());
+                Some((Arc::new(data), index))
+            })
+            .clone()
     }
 
     pub fn fontkit(&self) -> &FontKit {
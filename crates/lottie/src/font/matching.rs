@@ -0,0 +1,294 @@
+use rustybuzz::ttf_parser::{self, name_id, Tag};
+use rustybuzz::Variation;
+
+use crate::model::Font as LottieFont;
+use fontkit::FontKey;
+
+/// Width classes of `OS/2`, from ultra condensed to ultra expanded, as
+/// percentages of the normal width
+const WIDTH_PERCENTAGES: [f32; 9] = [50.0, 62.5, 75.0, 87.5, 100.0, 112.5, 125.0, 150.0, 200.0];
+
+/// Weight, style and stretch requested by a lottie font.
+///
+/// Fonts of a family are matched like CSS does it (CSS Fonts Level 4, font
+/// style matching):
+///
+/// 1. stretch: the requested width class if available. Otherwise, narrower
+///    widths are tried from the closest one when the request is normal or
+///    narrower, then wider ones. Requests wider than normal try wider widths
+///    first.
+/// 2. italic: fonts with the requested italic flag are preferred.
+/// 3. weight: a request between 400 and 500 tries heavier weights up to 500,
+///    then lighter weights from the closest one, then weights above 500. A
+///    request below 400 tries lighter weights first, a request above 500 tries
+///    heavier weights first.
+///
+/// Each step only narrows the candidates left by the previous one.
+///
+/// Variable fonts use the named instance whose subfamily name is the
+/// requested style, e.g. "Bold Italic". Without such an instance the `wght`,
+/// `wdth` and `ital` axes are set from the request, clamped to their ranges.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontRequest {
+    pub family: String,
+    /// Style name, e.g. "SemiBold Italic"
+    pub style: String,
+    /// Weight from 100 to 900, 400 being regular
+    pub weight: u32,
+    pub italic: bool,
+    /// `OS/2` width class from 1 to 9, 5 being normal
+    pub stretch: u32,
+}
+
+impl FontRequest {
+    /// Reads the request from `fStyle`, and `fWeight` when it is numeric
+    pub fn from_font(font: &LottieFont) -> Self {
+        let style = normalize(&font.style);
+        let weight = font
+            .weight
+            .as_ref()
+            .and_then(|weight| weight.trim().parse::<u32>().ok())
+            .unwrap_or_else(|| style_weight(&style));
+        FontRequest {
+            family: font.family.clone(),
+            style: font.style.clone(),
+            weight: weight.clamp(1, 1000),
+            italic: style.contains("italic") || style.contains("oblique"),
+            stretch: style_stretch(&style),
+        }
+    }
+
+    /// Preference of a font, lower is better
+    fn rank(&self, weight: u32, italic: bool, stretch: u32) -> impl Ord {
+        let stretch = if self.stretch <= 5 {
+            preference(self.stretch, stretch, stretch <= self.stretch)
+        } else {
+            preference(self.stretch, stretch, stretch >= self.stretch)
+        };
+        let weight = if (400..=500).contains(&self.weight) {
+            if weight >= self.weight && weight <= 500 {
+                (0, weight - self.weight)
+            } else if weight < self.weight {
+                (1, self.weight - weight)
+            } else {
+                (2, weight - self.weight)
+            }
+        } else if self.weight < 400 {
+            preference(self.weight, weight, weight <= self.weight)
+        } else {
+            preference(self.weight, weight, weight >= self.weight)
+        };
+        (stretch, italic != self.italic, weight)
+    }
+
+    fn key_rank(&self, key: &FontKey) -> impl Ord {
+        self.rank(
+            key.weight.unwrap_or(400).into(),
+            key.italic.unwrap_or(false),
+            key.stretch.unwrap_or(5).into(),
+        )
+    }
+
+    /// Best match among `keys`
+    pub(crate) fn best<'k>(&self, keys: &'k [FontKey]) -> Option<&'k FontKey> {
+        keys.iter().min_by_key(|key| self.key_rank(key))
+    }
+
+    /// Variation coordinates to apply to `face`, empty for static fonts
+    pub(crate) fn variations(&self, face: &ttf_parser::Face) -> Vec<Variation> {
+        if !face.is_variable() {
+            return vec![];
+        }
+        let style = normalize(&self.style);
+        let instance = named_instances(face)
+            .into_iter()
+            .find(|(name, _)| normalize(name) == style);
+        if let Some((_, variations)) = instance {
+            return variations;
+        }
+        let requested = [
+            (Tag::from_bytes(b"wght"), self.weight as f32),
+            (
+                Tag::from_bytes(b"wdth"),
+                WIDTH_PERCENTAGES[self.stretch.clamp(1, 9) as usize - 1],
+            ),
+            (
+                Tag::from_bytes(b"ital"),
+                if self.italic { 1.0 } else { 0.0 },
+            ),
+        ];
+        face.variation_axes()
+            .into_iter()
+            .filter_map(|axis| {
+                let (tag, value) = requested.iter().find(|(tag, _)| *tag == axis.tag)?;
+                Some(Variation {
+                    tag: *tag,
+                    value: value.clamp(axis.min_value, axis.max_value),
+                })
+            })
+            .collect()
+    }
+}
+
+/// `(0, distance)` for values in the preferred direction, `(1, distance)`
+/// for the others
+fn preference(requested: u32, value: u32, preferred: bool) -> (u8, u32) {
+    (if preferred { 0 } else { 1 }, requested.abs_diff(value))
+}
+
+/// Lowercase alphanumeric chars of a style name, so that "Semi Bold" and
+/// "SemiBold" are the same
+fn normalize(style: &str) -> String {
+    style
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn style_weight(style: &str) -> u32 {
+    // compound names first, "extrabold" also contains "bold"
+    const WEIGHTS: [(&str, u32); 16] = [
+        ("hairline", 100),
+        ("thin", 100),
+        ("extralight", 200),
+        ("ultralight", 200),
+        ("semilight", 350),
+        ("light", 300),
+        ("medium", 500),
+        ("semibold", 600),
+        ("demibold", 600),
+        ("extrabold", 800),
+        ("ultrabold", 800),
+        ("bold", 700),
+        ("extrablack", 950),
+        ("ultrablack", 950),
+        ("black", 900),
+        ("heavy", 900),
+    ];
+    WEIGHTS
+        .iter()
+        .find(|(name, _)| style.contains(name))
+        .map(|(_, weight)| *weight)
+        .unwrap_or(400)
+}
+
+fn style_stretch(style: &str) -> u32 {
+    const STRETCHES: [(&str, u32); 8] = [
+        ("ultracondensed", 1),
+        ("extracondensed", 2),
+        ("semicondensed", 4),
+        ("condensed", 3),
+        ("ultraexpanded", 9),
+        ("extraexpanded", 8),
+        ("semiexpanded", 6),
+        ("expanded", 7),
+    ];
+    STRETCHES
+        .iter()
+        .find(|(name, _)| style.contains(name))
+        .map(|(_, stretch)| *stretch)
+        .unwrap_or(5)
+}
+
+/// Subfamily names and coordinates of the named instances in `fvar`
+fn named_instances(face: &ttf_parser::Face) -> Vec<(String, Vec<Variation>)> {
+    let read_u16 = |data: &[u8], offset: usize| -> Option<u16> {
+        Some(u16::from_be_bytes(
+            data.get(offset..offset + 2)?.try_into().ok()?,
+        ))
+    };
+    let read_fixed = |data: &[u8], offset: usize| -> Option<f32> {
+        let value = i32::from_be_bytes(data.get(offset..offset + 4)?.try_into().ok()?);
+        Some(value as f32 / 65536.0)
+    };
+    let parse = || -> Option<Vec<(String, Vec<Variation>)>> {
+        let data = face.raw_face().table(Tag::from_bytes(b"fvar"))?;
+        let axes_offset = read_u16(data, 4)? as usize;
+        let axis_count = read_u16(data, 8)? as usize;
+        let axis_size = read_u16(data, 10)? as usize;
+        let instance_count = read_u16(data, 12)? as usize;
+        let instance_size = read_u16(data, 14)? as usize;
+        let axes = face.variation_axes();
+        let mut result = vec![];
+        for index in 0..instance_count {
+            let offset = axes_offset + axis_count * axis_size + index * instance_size;
+            let name_id = read_u16(data, offset)?;
+            let name = face
+                .names()
+                .into_iter()
+                .filter(|name| name.name_id == name_id)
+                .find_map(|name| decode_name(&name));
+            let name = match name {
+                Some(name) => name,
+                None => continue,
+            };
+            let variations = (0..axis_count)
+                .map(|axis| {
+                    Some(Variation {
+                        tag: axes.get(axis as u16)?.tag,
+                        value: read_fixed(data, offset + 4 + axis * 4)?,
+                    })
+                })
+                .collect::<Option<Vec<_>>>()?;
+            result.push((name, variations));
+        }
+        Some(result)
+    };
+    parse().unwrap_or_default()
+}
+
+/// Decode a `name` record, only Unicode records being UTF-16BE
+fn decode_name(name: &ttf_parser::name::Name) -> Option<String> {
+    if !name.is_unicode() {
+        return None;
+    }
+    let units = name
+        .name
+        .chunks_exact(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]));
+    char::decode_utf16(units).collect::<Result<_, _>>().ok()
+}
+
+/// Index of the face of `key` in the font file `data`, 0 unless it is a
+/// collection. fontkit does not expose the index of the faces it loads, so
+/// they are told apart by the names and style their key is made of
+pub(crate) fn face_index(data: &[u8], key: &FontKey) -> u32 {
+    let Some(count) = ttf_parser::fonts_in_collection(data) else {
+        return 0;
+    };
+    let matches = |index: u32| -> Option<(bool, bool)> {
+        let face = ttf_parser::Face::parse(data, index).ok()?;
+        let family = face
+            .names()
+            .into_iter()
+            .filter(|name| {
+                matches!(
+                    name.name_id,
+                    name_id::FAMILY
+                        | name_id::FULL_NAME
+                        | name_id::POST_SCRIPT_NAME
+                        | name_id::TYPOGRAPHIC_FAMILY
+                )
+            })
+            .filter_map(|name| {
+                decode_name(&name)
+                    .or_else(|| Some(std::str::from_utf8(name.name).ok()?.to_string()))
+            })
+            .any(|name| {
+                name.trim_start_matches('.') == key.family || name.replace(' ', "-") == key.family
+            });
+        let style = key.weight == Some(face.weight().to_number())
+            && key.italic == Some(face.is_italic())
+            && key.stretch == Some(face.width().to_number());
+        Some((family, style))
+    };
+    let faces = (0..count)
+        .filter_map(|index| Some((index, matches(index)?)))
+        .collect::<Vec<_>>();
+    faces
+        .iter()
+        .find(|(_, (family, style))| *family && *style)
+        .or_else(|| faces.iter().find(|(_, (family, _))| *family))
+        .map_or(0, |(index, _)| *index)
+}
@@ -1,6 +1,10 @@
 pub mod frame;
 pub mod hierarchy;
 pub mod media;
+pub mod path;
 pub mod shape;
+pub mod shaping;
 pub mod staged;
 pub mod text;
+pub mod text_animator;
+pub mod text_path;
@@ -49,6 +49,9 @@ impl OpacityHierarchy {
 pub struct StagedLayerMask {
     pub mode: MatteMode,
     pub id: Id,
+    /// The mask clips a precomposition to its bounds. Renderers short of mask
+    /// slots should drop these before the masks of the animation itself
+    pub clip: bool,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -0,0 +1,57 @@
+use std::convert::Infallible;
+use std::fmt;
+use std::str::FromStr;
+
+use super::staged::StagedLayer;
+
+/// Address of a layer, descending into precompositions one segment at a time.
+/// A segment matches a layer if it equals the layer's name (`nm`), or if it is
+/// a number equal to the layer's index (`ind`).
+///
+/// Parsed from a dot separated string, e.g. `"Card.Badge"` addresses the layer
+/// named `Badge` inside the precomposition layer named `Card`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LayerPath {
+    segments: Vec<String>,
+}
+
+impl LayerPath {
+    pub fn new(segments: Vec<String>) -> Self {
+        LayerPath { segments }
+    }
+
+    pub fn segments(&self) -> &[String] {
+        &self.segments
+    }
+
+    pub(crate) fn matches(segment: &str, layer: &StagedLayer) -> bool {
+        if layer.name.as_deref() == Some(segment) {
+            return true;
+        }
+        match (segment.parse::<u32>(), layer.index) {
+            (Ok(index), Some(layer_index)) => index == layer_index,
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for LayerPath {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(LayerPath::new(s.split('.').map(String::from).collect()))
+    }
+}
+
+impl From<&str> for LayerPath {
+    fn from(s: &str) -> Self {
+        // parsing is infallible
+        s.parse().unwrap()
+    }
+}
+
+impl fmt::Display for LayerPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.segments.join("."))
+    }
+}
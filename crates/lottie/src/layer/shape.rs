@@ -214,7 +214,8 @@ impl Shape {
             Shape::Rectangle { .. }
             | Shape::Ellipse { .. }
             | Shape::PolyStar { .. }
-            | Shape::Path { .. } => true,
+            | Shape::Path { .. }
+            | Shape::Image { .. } => true,
             _ => false,
         }
     }
@@ -285,9 +286,12 @@ impl PathFactory for Vec<Bezier> {
             }
             if b.closed {
                 let index = b.verticies.len() - 1;
+                // tangents may be missing in malformed files
+                let c1 = b.out_tangent.get(index).copied().unwrap_or_default();
+                let c2 = b.in_tangent.first().copied().unwrap_or_default();
                 builder.cubic_bezier_to(
-                    (b.verticies[index] + b.out_tangent[index]).to_point(),
-                    (b.verticies[0] + b.in_tangent[0]).to_point(),
+                    (b.verticies[index] + c1).to_point(),
+                    (b.verticies[0] + c2).to_point(),
                     b.verticies[0].to_point(),
                 );
             }
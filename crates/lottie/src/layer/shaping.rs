@@ -0,0 +1,573 @@
+use std::collections::HashMap;
+
+use rustybuzz::ttf_parser::colr::{ClipBox, ColorStop, CompositeMode, Paint, Painter};
+use rustybuzz::ttf_parser::{
+    GlyphId, OutlineBuilder, RasterImageFormat, RgbaColor, Transform as FontTransform,
+};
+use rustybuzz::{Direction, Face, UnicodeBuffer};
+use unicode_bidi::BidiInfo;
+
+use crate::font::FontFace;
+use crate::model::*;
+
+/// A glyph produced by shaping a line
+pub(crate) struct ShapedGlyph {
+    /// Index of the font in the fallback chain the glyph comes from
+    pub font: usize,
+    pub glyph_id: u16,
+    /// Char drawn with the glyphs embedded in the animation instead of a font
+    pub embedded: Option<char>,
+    /// Index of the first char of the cluster this glyph belongs to, in
+    /// logical order, so text range selectors still address original chars
+    pub char_index: usize,
+    /// Pen position of the glyph relative to the line start, in visual order
+    pub x: f32,
+    /// Vertical offset of the glyph, positive goes up
+    pub y: f32,
+    pub advance: f32,
+    pub size: f32,
+}
+
+/// A glyph of a color font
+pub(crate) enum ColorGlyph {
+    /// `COLR` layers painted bottom to top, each an outline with its fill
+    Layers(Vec<(Vec<Bezier>, Shape)>),
+    /// A `CBDT`/`sbix` bitmap or an `SVG` glyph
+    Image(GlyphImage),
+}
+
+/// OpenType shaping of lines through rustybuzz, with bidi reordering. Each
+/// char is shaped with the first font of the chain containing it
+pub(crate) struct Shaper<'a> {
+    faces: Vec<Face<'a>>,
+    /// Glyphs of the `chars` array of the animation. When set, they are laid
+    /// out instead of font glyphs
+    embedded: HashMap<char, &'a Character>,
+}
+
+impl<'a> Shaper<'a> {
+    /// `fonts` holds the text font followed by its fallbacks
+    pub(crate) fn new(fonts: &'a [FontFace]) -> Option<Self> {
+        let mut faces = fonts.iter().map(|font| {
+            let mut face = Face::from_slice(&font.data, font.index)?;
+            face.set_variations(&font.variations);
+            Some(face)
+        });
+        // the text font is required, broken fallbacks are skipped
+        let mut result = vec![faces.next()??];
+        result.extend(faces.flatten());
+        Some(Shaper {
+            faces: result,
+            embedded: HashMap::new(),
+        })
+    }
+
+    /// Lays out the glyphs embedded in the animation, which must hold every
+    /// char. Only advance widths are known, so no OpenType feature applies
+    pub(crate) fn embedded(chars: HashMap<char, &'a Character>) -> Self {
+        Shaper {
+            faces: vec![],
+            embedded: chars,
+        }
+    }
+
+    /// Whether the embedded glyphs or a font of the chain contain `c`
+    pub(crate) fn contains(&self, c: char) -> bool {
+        self.embedded.contains_key(&c)
+            || self.faces.iter().any(|face| face.glyph_index(c).is_some())
+    }
+
+    /// Default distance between baselines for a font size of `size`
+    pub(crate) fn line_height(&self, size: f32) -> f32 {
+        match self.faces.first() {
+            Some(face) => {
+                let height = face.ascender() - face.descender() + face.line_gap();
+                height as f32 * size / face.units_per_em() as f32
+            }
+            None => size * 1.2,
+        }
+    }
+
+    /// Index of the font each char is shaped with. Chars no font contains
+    /// use the text font, and so its `.notdef` glyph. A char stays in the
+    /// font of the previous char when possible, so that runs are not split
+    fn font_indices(&self, chars: &[char]) -> Vec<usize> {
+        if self.faces.is_empty() {
+            return vec![0; chars.len()];
+        }
+        let mut result: Vec<usize> = Vec::with_capacity(chars.len());
+        for c in chars {
+            let previous = result.last().cloned();
+            let index = match previous {
+                Some(previous) if self.faces[previous].glyph_index(*c).is_some() => previous,
+                _ => self
+                    .faces
+                    .iter()
+                    .position(|face| face.glyph_index(*c).is_some())
+                    .unwrap_or(0),
+            };
+            result.push(index);
+        }
+        result
+    }
+
+    /// Shape a line of `chars`. `styles` holds the font size and letter
+    /// spacing of each char, chars of different sizes are shaped separately.
+    /// Glyphs are returned in visual order
+    pub(crate) fn shape_line(&self, chars: &[char], styles: &[(f32, f32)]) -> Vec<ShapedGlyph> {
+        let text = chars.iter().collect::<String>();
+        if text.is_empty() {
+            return vec![];
+        }
+        // byte offset of each char in `text`
+        let offsets = text.char_indices().map(|(i, _)| i).collect::<Vec<_>>();
+        let char_at = |byte: usize| offsets.partition_point(|offset| *offset <= byte) - 1;
+        let fonts = self.font_indices(chars);
+
+        let bidi = BidiInfo::new(&text, None);
+        let mut result = vec![];
+        let mut pen = 0.0;
+        for paragraph in &bidi.paragraphs {
+            let (levels, runs) = bidi.visual_runs(paragraph, paragraph.range.clone());
+            for run in runs {
+                let rtl = levels[run.start].is_rtl();
+                // split the run where the style or the font changes
+                let mut pieces = vec![];
+                let mut start = run.start;
+                for (index, offset) in offsets.iter().enumerate() {
+                    if *offset <= run.start || *offset >= run.end {
+                        continue;
+                    }
+                    if styles[index] != styles[index - 1] || fonts[index] != fonts[index - 1] {
+                        pieces.push(start..*offset);
+                        start = *offset;
+                    }
+                }
+                pieces.push(start..run.end);
+                if rtl {
+                    pieces.reverse();
+                }
+                for piece in pieces {
+                    let (size, letter_spacing) = styles[char_at(piece.start)];
+                    if !self.embedded.is_empty() {
+                        let mut indices =
+                            (char_at(piece.start)..=char_at(piece.end - 1)).collect::<Vec<_>>();
+                        if rtl {
+                            indices.reverse();
+                        }
+                        for index in indices {
+                            let c = chars[index];
+                            let width = self.embedded.get(&c).map(|ch| ch.width).unwrap_or(0.0);
+                            let advance = width * size / 100.0 + letter_spacing;
+                            result.push(ShapedGlyph {
+                                font: 0,
+                                glyph_id: 0,
+                                embedded: Some(c),
+                                char_index: index,
+                                x: pen,
+                                y: 0.0,
+                                advance,
+                                size,
+                            });
+                            pen += advance;
+                        }
+                        continue;
+                    }
+                    let font = fonts[char_at(piece.start)];
+                    let face = &self.faces[font];
+                    let scale = size / face.units_per_em() as f32;
+                    let mut buffer = UnicodeBuffer::new();
+                    buffer.push_str(&text[piece.clone()]);
+                    buffer.set_direction(if rtl {
+                        Direction::RightToLeft
+                    } else {
+                        Direction::LeftToRight
+                    });
+                    let output = rustybuzz::shape(face, &[], buffer);
+                    for (info, position) in
+                        output.glyph_infos().iter().zip(output.glyph_positions())
+                    {
+                        let advance = position.x_advance as f32 * scale + letter_spacing;
+                        result.push(ShapedGlyph {
+                            font,
+                            glyph_id: info.glyph_id as u16,
+                            embedded: None,
+                            char_index: char_at(piece.start + info.cluster as usize),
+                            x: pen + position.x_offset as f32 * scale,
+                            y: position.y_offset as f32 * scale,
+                            advance,
+                            size,
+                        });
+                        pen += advance;
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// Outline of a glyph at `size`, with y pointing down
+    pub(crate) fn outline(&self, glyph: &ShapedGlyph) -> Vec<Bezier> {
+        if let Some(c) = glyph.embedded {
+            let mut beziers = vec![];
+            if let Some(ch) = self.embedded.get(&c) {
+                embedded_outline(&ch.data.shapes, glyph.size / 100.0, &mut beziers);
+            }
+            return beziers;
+        }
+        let face = &self.faces[glyph.font];
+        let factor = glyph.size / face.units_per_em() as f32;
+        let mut builder = BezierBuilder::new(factor, FontTransform::default());
+        face.outline_glyph(GlyphId(glyph.glyph_id), &mut builder);
+        builder.finish()
+    }
+
+    /// Colored rendering of a glyph, `None` for plain glyphs. `COLR` glyphs
+    /// are preferred over bitmaps, and `SVG` documents are only used for
+    /// glyphs without an outline, as they have to be rasterized by renderers
+    pub(crate) fn color_glyph(&self, glyph: &ShapedGlyph, foreground: Rgba) -> Option<ColorGlyph> {
+        if glyph.embedded.is_some() {
+            return None;
+        }
+        let face = &self.faces[glyph.font];
+        let id = GlyphId(glyph.glyph_id);
+        let factor = glyph.size / face.units_per_em() as f32;
+        if face.is_color_glyph(id) {
+            let mut painter = ColorPainter {
+                face,
+                factor,
+                transforms: vec![],
+                outline: vec![],
+                clips: vec![],
+                layers: vec![],
+            };
+            let foreground = RgbaColor::new(foreground.r, foreground.g, foreground.b, foreground.a);
+            face.paint_color_glyph(id, 0, foreground, &mut painter)?;
+            return Some(ColorGlyph::Layers(painter.layers));
+        }
+        // the largest strike, as glyphs may be scaled up by transforms
+        if let Some(image) = face.glyph_raster_image(id, u16::MAX) {
+            if image.format == RasterImageFormat::PNG && image.pixels_per_em > 0 {
+                let scale = glyph.size / image.pixels_per_em as f32;
+                return Some(ColorGlyph::Image(GlyphImage {
+                    content: image.data.to_vec(),
+                    origin: Vector2D::new(image.x as f32, -(image.y as f32 + image.height as f32))
+                        * scale,
+                    size: Vector2D::new(image.width as f32, image.height as f32) * scale,
+                }));
+            }
+        }
+        if face.glyph_bounding_box(id).is_none() {
+            let document = face.glyph_svg_image(id)?;
+            return Some(ColorGlyph::Image(GlyphImage {
+                content: document.data.to_vec(),
+                origin: Vector2D::new(0.0, -glyph.size),
+                size: Vector2D::new(glyph.size, glyph.size),
+            }));
+        }
+        None
+    }
+}
+
+/// Collects the layers of a `COLR` glyph. Composite modes are not supported
+/// and layers are painted over each other, nested clips keep the innermost
+/// one only
+struct ColorPainter<'f, 'a> {
+    face: &'f Face<'a>,
+    factor: f32,
+    /// Combined transforms, the last one is current
+    transforms: Vec<FontTransform>,
+    /// Last outlined glyph
+    outline: Vec<Bezier>,
+    clips: Vec<Vec<Bezier>>,
+    layers: Vec<(Vec<Bezier>, Shape)>,
+}
+
+impl ColorPainter<'_, '_> {
+    fn transform(&self) -> FontTransform {
+        self.transforms.last().cloned().unwrap_or_default()
+    }
+
+    fn point(&self, x: f32, y: f32) -> Vector2D {
+        map_point(&self.transform(), self.factor, x, y)
+    }
+
+    fn push(&mut self, transform: FontTransform) {
+        let transform = FontTransform::combine(self.transform(), transform);
+        self.transforms.push(transform);
+    }
+
+    fn gradient(
+        &self,
+        ty: GradientType,
+        start: Vector2D,
+        end: Vector2D,
+        stops: impl Iterator<Item = ColorStop>,
+    ) -> Shape {
+        let colors = stops
+            .map(|stop| GradientColor {
+                offset: stop.stop_offset,
+                color: Rgba::new_u8(
+                    stop.color.red,
+                    stop.color.green,
+                    stop.color.blue,
+                    stop.color.alpha,
+                ),
+            })
+            .collect::<Vec<_>>();
+        // gradients need two stops at least
+        if colors.len() < 2 {
+            let color = match colors.first() {
+                Some(stop) => stop.color,
+                None => Rgba::new_u8(0, 0, 0, 0),
+            };
+            return solid(RgbaColor::new(color.r, color.g, color.b, color.a));
+        }
+        Shape::GradientFill(GradientFill {
+            opacity: Animated::from_value(100.0),
+            fill_rule: FillRule::NonZero,
+            gradient: Gradient {
+                start: Animated::from_value(start),
+                end: Animated::from_value(end),
+                gradient_ty: ty,
+                colors: ColorList::new(colors),
+            },
+        })
+    }
+}
+
+fn solid(color: RgbaColor) -> Shape {
+    Shape::Fill(Fill {
+        opacity: Animated::from_value(color.alpha as f32 / 255.0 * 100.0),
+        color: Animated::from_value(Rgb::new_u8(color.red, color.green, color.blue)),
+        fill_rule: FillRule::NonZero,
+    })
+}
+
+impl<'a> Painter<'a> for ColorPainter<'_, 'a> {
+    fn outline_glyph(&mut self, glyph_id: GlyphId) {
+        let mut builder = BezierBuilder::new(self.factor, self.transform());
+        self.face.outline_glyph(glyph_id, &mut builder);
+        self.outline = builder.finish();
+    }
+
+    fn paint(&mut self, paint: Paint<'a>) {
+        let outline = self.clips.last().unwrap_or(&self.outline).clone();
+        // variation coordinates are not applied to gradient stops
+        let style = match paint {
+            Paint::Solid(color) => solid(color),
+            Paint::LinearGradient(g) => self.gradient(
+                GradientType::Linear,
+                self.point(g.x0, g.y0),
+                self.point(g.x1, g.y1),
+                g.stops(0, &[]),
+            ),
+            Paint::RadialGradient(g) => self.gradient(
+                GradientType::Radial,
+                self.point(g.x1, g.y1),
+                self.point(g.x1 + g.r1, g.y1),
+                g.stops(0, &[]),
+            ),
+            // sweep gradients are approximated by their first color
+            Paint::SweepGradient(g) => match g.stops(0, &[]).next() {
+                Some(stop) => solid(stop.color),
+                None => return,
+            },
+        };
+        self.layers.push((outline, style));
+    }
+
+    fn push_clip(&mut self) {
+        self.clips.push(self.outline.clone());
+    }
+
+    fn push_clip_box(&mut self, clipbox: ClipBox) {
+        let verticies = vec![
+            self.point(clipbox.x_min, clipbox.y_min),
+            self.point(clipbox.x_max, clipbox.y_min),
+            self.point(clipbox.x_max, clipbox.y_max),
+            self.point(clipbox.x_min, clipbox.y_max),
+        ];
+        self.clips.push(vec![Bezier {
+            closed: true,
+            in_tangent: vec![Vector2D::zero(); 4],
+            out_tangent: vec![Vector2D::zero(); 4],
+            verticies,
+        }]);
+    }
+
+    fn pop_clip(&mut self) {
+        self.clips.pop();
+    }
+
+    fn push_layer(&mut self, _mode: CompositeMode) {}
+
+    fn pop_layer(&mut self) {}
+
+    fn push_translate(&mut self, tx: f32, ty: f32) {
+        self.push(FontTransform::new_translate(tx, ty));
+    }
+
+    fn push_scale(&mut self, sx: f32, sy: f32) {
+        self.push(FontTransform::new(sx, 0.0, 0.0, sy, 0.0, 0.0));
+    }
+
+    fn push_rotate(&mut self, angle: f32) {
+        // angles are in multiples of 180 degrees, counter clockwise
+        let (sin, cos) = (angle * std::f32::consts::PI).sin_cos();
+        self.push(FontTransform::new(cos, sin, -sin, cos, 0.0, 0.0));
+    }
+
+    fn push_skew(&mut self, skew_x: f32, skew_y: f32) {
+        let x = (-skew_x * std::f32::consts::PI).tan();
+        let y = (skew_y * std::f32::consts::PI).tan();
+        self.push(FontTransform::new(1.0, y, x, 1.0, 0.0, 0.0));
+    }
+
+    fn push_transform(&mut self, transform: FontTransform) {
+        self.push(transform);
+    }
+
+    fn pop_transform(&mut self) {
+        self.transforms.pop();
+    }
+}
+
+/// Maps a point in font units to layer units, flipping y to point down
+fn map_point(transform: &FontTransform, factor: f32, x: f32, y: f32) -> Vector2D {
+    let t = transform;
+    Vector2D::new(t.a * x + t.c * y + t.e, -(t.b * x + t.d * y + t.f)) * factor
+}
+
+/// A hollow box standing for a glyph no font contains, for fonts whose
+/// `.notdef` glyph has no outline
+pub(crate) fn notdef_box(width: f32, height: f32) -> Vec<Bezier> {
+    let rect = |min: Vector2D, max: Vector2D, clockwise: bool| {
+        let mut verticies = vec![
+            min,
+            Vector2D::new(max.x, min.y),
+            max,
+            Vector2D::new(min.x, max.y),
+        ];
+        if !clockwise {
+            verticies.reverse();
+        }
+        Bezier {
+            closed: true,
+            in_tangent: vec![Vector2D::zero(); 4],
+            out_tangent: vec![Vector2D::zero(); 4],
+            verticies,
+        }
+    };
+    let inset = (width * 0.1).min(height * 0.1);
+    let (min, max) = (
+        Vector2D::new(inset, -height),
+        Vector2D::new(width - inset, 0.0),
+    );
+    let border = (width * 0.08).max(1.0);
+    // the inner rect runs the other way, leaving a hole with nonzero filling
+    vec![
+        rect(min, max, true),
+        rect(
+            min + Vector2D::new(border, border),
+            max - Vector2D::new(border, border),
+            false,
+        ),
+    ]
+}
+
+/// Paths of the shapes of an embedded glyph, scaled by `factor`. Groups of
+/// exported glyphs have no transform, so theirs are ignored
+fn embedded_outline(shapes: &[ShapeLayer], factor: f32, result: &mut Vec<Bezier>) {
+    for layer in shapes.iter().filter(|layer| !layer.hidden) {
+        match &layer.shape {
+            Shape::Path { d, .. } => {
+                let beziers = d.keyframes.first().map(|keyframe| &keyframe.start_value);
+                result.extend(beziers.into_iter().flatten().map(|bezier| Bezier {
+                    closed: bezier.closed,
+                    verticies: bezier.verticies.iter().map(|v| *v * factor).collect(),
+                    in_tangent: bezier.in_tangent.iter().map(|v| *v * factor).collect(),
+                    out_tangent: bezier.out_tangent.iter().map(|v| *v * factor).collect(),
+                }));
+            }
+            Shape::Group { shapes } => embedded_outline(shapes, factor, result),
+            _ => {}
+        }
+    }
+}
+
+struct BezierBuilder {
+    factor: f32,
+    transform: FontTransform,
+    beziers: Vec<Bezier>,
+    bezier: Bezier,
+    last_pt: Vector2D,
+}
+
+impl BezierBuilder {
+    fn new(factor: f32, transform: FontTransform) -> Self {
+        BezierBuilder {
+            factor,
+            transform,
+            beziers: vec![],
+            bezier: Bezier::default(),
+            last_pt: Vector2D::zero(),
+        }
+    }
+
+    fn point(&self, x: f32, y: f32) -> Vector2D {
+        map_point(&self.transform, self.factor, x, y)
+    }
+
+    fn finish(mut self) -> Vec<Bezier> {
+        if !self.bezier.verticies.is_empty() {
+            self.bezier.out_tangent.push(Vector2D::zero());
+            self.beziers.push(self.bezier);
+        }
+        self.beziers
+    }
+}
+
+impl OutlineBuilder for BezierBuilder {
+    fn move_to(&mut self, x: f32, y: f32) {
+        if !self.bezier.verticies.is_empty() {
+            let mut old = std::mem::take(&mut self.bezier);
+            old.out_tangent.push(Vector2D::zero());
+            self.beziers.push(old);
+        }
+        self.bezier.in_tangent.push(Vector2D::zero());
+        self.last_pt = self.point(x, y);
+        self.bezier.verticies.push(self.last_pt);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        let pt = self.point(x, y);
+        self.bezier.out_tangent.push(Vector2D::zero());
+        self.bezier.in_tangent.push(Vector2D::zero());
+        self.bezier.verticies.push(pt);
+        self.last_pt = pt;
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let pt1 = self.point(x1, y1);
+        let pt = self.point(x, y);
+        self.bezier.out_tangent.push(pt1 - self.last_pt);
+        self.bezier.in_tangent.push(pt1 - pt);
+        self.bezier.verticies.push(pt);
+        self.last_pt = pt;
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let pt1 = self.point(x1, y1);
+        let pt2 = self.point(x2, y2);
+        let pt = self.point(x, y);
+        self.bezier.out_tangent.push(pt1 - self.last_pt);
+        self.bezier.in_tangent.push(pt2 - pt);
+        self.bezier.verticies.push(pt);
+        self.last_pt = pt;
+    }
+
+    fn close(&mut self) {
+        self.bezier.closed = true;
+    }
+}
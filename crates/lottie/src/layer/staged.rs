@@ -7,6 +7,7 @@ use crate::Error;
 use super::frame::{FrameTransform, FrameTransformHierarchy};
 use super::hierarchy::TransformHierarchy;
 use super::media::Media;
+use crate::resolver::AssetResolver;
 
 #[derive(Debug, Clone)]
 pub enum RenderableContent {
@@ -19,6 +20,12 @@ impl RenderableContent {
     pub(crate) fn into_stage_layer(self, layer: &Layer) -> StagedLayer {
         let mut transform = layer.transform.clone().unwrap_or_default();
         transform.auto_orient = layer.auto_orient;
+        let precomp_size = match &layer.content {
+            LayerContent::PreCompositionRef(r) => {
+                Some(Vector2D::new(r.width as f32, r.height as f32))
+            }
+            _ => None,
+        };
         StagedLayer {
             id: Id::default(),
             name: layer.name.clone(),
@@ -37,8 +44,37 @@ impl RenderableContent {
             matte_mode: layer.matte_mode,
             mask_hierarchy: MaskHierarchy::default(),
             blend_mode: layer.blend_mode.unwrap_or(BlendMode::Normal),
+            precomp_size,
+            index: layer.index,
+            precomp: None,
+            hidden: false,
+            text: None,
         }
     }
+
+    /// A rectangle covering `(0, 0)` to `size` in the layer's own coordinate
+    /// space, painted with `fill`
+    fn rectangle(size: Vector2D, fill: Fill) -> Self {
+        RenderableContent::Shape(ShapeGroup {
+            shapes: vec![
+                ShapeLayer {
+                    name: None,
+                    hidden: false,
+                    shape: Shape::Rectangle(Rectangle {
+                        direction: ShapeDirection::Clockwise,
+                        position: Animated::from_value(size / 2.0),
+                        size: Animated::from_value(size),
+                        radius: Animated::from_value(0.0),
+                    }),
+                },
+                ShapeLayer {
+                    name: None,
+                    hidden: false,
+                    shape: Shape::Fill(fill),
+                },
+            ],
+        })
+    }
 }
 
 #[derive(Clone, Hash, PartialEq, Eq, PartialOrd, Ord, Debug)]
@@ -52,10 +88,22 @@ pub(crate) enum ContentInfo {
     ContentWithMasks {
         content: RenderableContent,
         masks: Vec<(RenderableContent, MatteMode)>,
+        /// The masks clip a precomposition to its bounds
+        clip: bool,
     },
     TextKeyframes(Vec<TextKeyframe>),
 }
 
+/// Source of a staged text layer, kept so that its text could be replaced and
+/// laid out again at runtime
+#[derive(Debug, Clone)]
+pub(crate) struct TextSource {
+    pub data: TextAnimationData,
+    pub masks: Vec<Mask>,
+    /// Index of the document keyframe shown by the staged layer
+    pub keyframe: usize,
+}
+
 pub(crate) struct TextKeyframe {
     pub content: RenderableContent,
     pub start_frame: f32,
@@ -85,6 +133,21 @@ pub struct StagedLayer {
     pub matte_mode: Option<MatteMode>,
     pub mask_hierarchy: MaskHierarchy,
     pub blend_mode: BlendMode,
+    /// Size of the precomposition referenced by this layer. Children of this
+    /// layer are clipped to `(0, 0, width, height)` in this layer's
+    /// coordinate space
+    pub precomp_size: Option<Vector2D>,
+    /// Maps to `Layer::index`
+    pub index: Option<u32>,
+    /// The precomposition layer this layer is staged from, `None` for layers
+    /// in the root composition
+    pub precomp: Option<Id>,
+    /// Hidden at runtime by
+    /// [Lottie::set_layer_visibility](crate::Lottie::set_layer_visibility)
+    pub hidden: bool,
+    /// Text of text layers, laid out again by
+    /// [Lottie::set_text](crate::Lottie::set_text)
+    pub(crate) text: Option<TextSource>,
 }
 
 impl ContentInfo {
@@ -92,7 +155,7 @@ impl ContentInfo {
         layer: Layer,
         model: &Model,
         fontdb: &FontDB,
-        root_path: &str,
+        resolver: &dyn AssetResolver,
     ) -> Result<ContentInfo, Error> {
         let content = match layer.content.clone() {
             LayerContent::Shape(shape_group) => {
@@ -100,6 +163,15 @@ impl ContentInfo {
                 let content = RenderableContent::Shape(shape_group);
                 if layer.has_mask {
                     for mask in &layer.masks_properties {
+                        let matte_mode = match mask.mode {
+                            MaskMode::Add => MatteMode::Alpha,
+                            MaskMode::Subtract => MatteMode::InvertedAlpha,
+                            MaskMode::None => MatteMode::Normal,
+                            mode => {
+                                log::warn!("mask mode {:?} is not supported", mode);
+                                continue;
+                            }
+                        };
                         let mut opacity = mask.opacity.clone();
                         for keyframe in &mut opacity.keyframes {
                             keyframe.start_value /= 100.0;
@@ -122,6 +194,8 @@ impl ContentInfo {
                                         opacity,
                                         color: Animated {
                                             animated: false,
+                                            expression: None,
+                                            slot: None,
                                             keyframes: vec![KeyFrame::from_value(Rgb::new_u8(
                                                 0, 0, 0,
                                             ))],
@@ -136,68 +210,61 @@ impl ContentInfo {
                                 },
                             ],
                         });
-                        let matte_mode = match mask.mode {
-                            MaskMode::Add => MatteMode::Alpha,
-                            MaskMode::Subtract => MatteMode::InvertedAlpha,
-                            MaskMode::None => MatteMode::Normal,
-                            _ => unimplemented!(),
-                        };
                         result.push((content, matte_mode));
                     }
                     ContentInfo::ContentWithMasks {
                         content,
                         masks: result,
+                        clip: false,
                     }
                 } else {
                     ContentInfo::Simple(content)
                 }
             }
-            LayerContent::PreCompositionRef(_)
-            | LayerContent::Empty
-            | LayerContent::MediaRef(_) => ContentInfo::Simple(RenderableContent::Group.into()),
-            LayerContent::Text(text) => match RenderableContent::from_text(&text, model, fontdb) {
-                Ok(t) => ContentInfo::TextKeyframes(
-                    t.keyframes
-                        .into_iter()
-                        .map(|keyframe| TextKeyframe {
-                            content: keyframe.start_value,
-                            start_frame: keyframe.start_frame,
-                            end_frame: keyframe.end_frame,
-                        })
-                        .collect(),
-                ),
-                Err(e) => {
-                    log::warn!("{:?}", e);
-                    ContentInfo::Simple(RenderableContent::Group)
+            LayerContent::PreCompositionRef(r) => {
+                // Precomp content is clipped to the precomp bounds. As the clip
+                // mask shares transform and parent with this layer, it follows the
+                // layer through transforms and nesting
+                let size = Vector2D::new(r.width as f32, r.height as f32);
+                let clip = RenderableContent::rectangle(size, Rgba::new_u8(0, 0, 0, 255).into());
+                ContentInfo::ContentWithMasks {
+                    content: RenderableContent::Group,
+                    masks: vec![(clip, MatteMode::Alpha)],
+                    clip: true,
                 }
-            },
+            }
+            LayerContent::Empty | LayerContent::MediaRef(_) | LayerContent::Unsupported(_) => {
+                ContentInfo::Simple(RenderableContent::Group.into())
+            }
+            LayerContent::Text(text) => {
+                match RenderableContent::from_text(&text, &layer.masks_properties, model, fontdb) {
+                    Ok(t) => ContentInfo::TextKeyframes(
+                        t.keyframes
+                            .into_iter()
+                            .map(|keyframe| TextKeyframe {
+                                content: keyframe.start_value,
+                                start_frame: keyframe.start_frame,
+                                end_frame: keyframe.end_frame,
+                            })
+                            .collect(),
+                    ),
+                    Err(e) => {
+                        log::warn!("{:?}", e);
+                        ContentInfo::Simple(RenderableContent::Group)
+                    }
+                }
+            }
             LayerContent::SolidColor {
                 color,
                 height,
                 width,
-            } => ContentInfo::Simple(RenderableContent::Shape(ShapeGroup {
-                shapes: vec![
-                    ShapeLayer {
-                        name: None,
-                        hidden: false,
-                        shape: Shape::Rectangle(Rectangle {
-                            direction: ShapeDirection::Clockwise,
-                            position: Animated::from_value(Vector2D::new(width, height) / 2.0),
-                            size: Animated::from_value(Vector2D::new(width, height)),
-                            radius: Animated::from_value(0.0),
-                        }),
-                    },
-                    ShapeLayer {
-                        name: None,
-                        hidden: false,
-                        shape: Shape::Fill(color.into()),
-                    },
-                ],
-            })),
-            LayerContent::Media(media) => ContentInfo::Simple(RenderableContent::Media(
-                Media::new(media, Some(root_path))?,
+            } => ContentInfo::Simple(RenderableContent::rectangle(
+                Vector2D::new(width, height),
+                color.into(),
             )),
-            _ => todo!(),
+            LayerContent::Media(media) => {
+                ContentInfo::Simple(RenderableContent::Media(Media::new(media, resolver)?))
+            }
         };
         Ok(content)
     }
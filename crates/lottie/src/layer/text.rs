@@ -1,61 +1,243 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
 use crate::model::*;
-use fontkit::{Area, Line, Metrics, PathSegment, Span, TextMetrics};
 
-use crate::font::FontDB;
+use crate::font::{FontDB, FontFace};
+use crate::layer::shaping::{notdef_box, ColorGlyph, ShapedGlyph, Shaper};
+use crate::layer::staged::TextSource;
+use crate::layer::text_path::{PathGlyph, TextPath};
 use crate::prelude::RenderableContent;
 use crate::Error;
 
-struct GlyphData {
-    c: char,
-    beziers: Vec<Bezier>,
-    offset_x: f32,
+/// Ascent of a font in percentage of font size, used when the animation does
+/// not specify one
+const DEFAULT_ASCENT: f32 = 75.0;
+/// Size of lowercase chars rendered as small caps, relative to the font size
+const SMALL_CAPS_SCALE: f32 = 0.7;
+/// Precision of the font size of text shrunk to fit, relative to its
+/// original size
+const SHRINK_PRECISION: f32 = 0.01;
+
+/// How text replaced at runtime is fitted in its layer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextFit {
+    /// Keep the font size of the document
+    None,
+    /// Shrink the font size, down to `min_size` but no less than 1, until
+    /// paragraph text shows all its lines without breaking words, and until
+    /// point text is no wider than `max_width` if any. Line heights shrink
+    /// along
+    Shrink {
+        min_size: f32,
+        max_width: Option<f32>,
+    },
+}
+
+/// Placement of a laid out line
+struct LineLayout {
+    /// Left edge and baseline of the line
+    x: f32,
+    y: f32,
+    /// Width without trailing spaces
+    width: f32,
+    /// Left edge and advance width of each char, in logical order. Chars
+    /// merged into the glyph of a previous char have no width
+    glyphs: Vec<(f32, f32)>,
+    /// Glyphs in visual order, positioned relative to the text origin
+    shaped: Vec<ShapedGlyph>,
 }
 
 impl RenderableContent {
     pub fn from_text(
         text: &TextAnimationData,
+        masks: &[Mask],
         model: &Model,
         fontdb: &FontDB,
     ) -> Result<Animated<RenderableContent>, Error> {
+        let text_path = TextPath::new(&text.follow_path, masks);
         let keyframes = text
             .document
             .keyframes
             .iter()
             .map(|keyframe| {
-                let parser = TextDocumentParser::new(keyframe, &text.ranges, &model, fontdb)?;
-                let shape = parser.shape_layer()?;
-                let content = RenderableContent::Shape(ShapeGroup {
-                    shapes: vec![shape],
-                });
+                let content =
+                    Self::from_text_document(keyframe, text, text_path.as_ref(), model, fontdb)?;
                 Ok(keyframe.alter_value(content.clone(), content))
             })
             .collect::<Result<Vec<_>, Error>>()?;
         Ok(Animated {
             animated: true,
+            expression: None,
+            slot: None,
             keyframes,
         })
     }
+
+    /// Lays out the document keyframe of a staged text layer with `value`
+    /// as its text, fitted as described by `fit`
+    pub(crate) fn from_text_source(
+        source: &TextSource,
+        value: &str,
+        fit: TextFit,
+        model: &Model,
+        fontdb: &FontDB,
+    ) -> Result<RenderableContent, Error> {
+        let text = &source.data;
+        let text_path = TextPath::new(&text.follow_path, &source.masks);
+        let mut keyframe = match text.document.keyframes.get(source.keyframe) {
+            Some(keyframe) => keyframe.clone(),
+            None => return Ok(RenderableContent::Group),
+        };
+        // Documents break lines with carriage returns
+        let value = value.replace("\r\n", "\r").replace('\n', "\r");
+        for doc in [&mut keyframe.start_value, &mut keyframe.end_value] {
+            doc.value = value.clone();
+        }
+        if let TextFit::Shrink {
+            min_size,
+            max_width,
+        } = fit
+        {
+            let original = keyframe.start_value.clone();
+            let fits = |keyframe: &KeyFrame<TextDocument>| {
+                TextDocumentParser::new(
+                    keyframe,
+                    &text.ranges,
+                    &text.options,
+                    text_path.as_ref(),
+                    model,
+                    fontdb,
+                )?
+                .fits(max_width)
+            };
+            let min_size = min_size.max(1.0);
+            if original.size > min_size && !fits(&keyframe)? {
+                // Binary search of the largest size that fits, the minimum
+                // size being kept when none does
+                let (mut low, mut high) = (min_size, original.size);
+                while high - low > original.size * SHRINK_PRECISION {
+                    let size = (low + high) / 2.0;
+                    resize(&mut keyframe, &original, size);
+                    if fits(&keyframe)? {
+                        low = size;
+                    } else {
+                        high = size;
+                    }
+                }
+                resize(&mut keyframe, &original, low);
+            }
+        }
+        Self::from_text_document(&keyframe, text, text_path.as_ref(), model, fontdb)
+    }
+
+    fn from_text_document(
+        keyframe: &KeyFrame<TextDocument>,
+        text: &TextAnimationData,
+        text_path: Option<&TextPath>,
+        model: &Model,
+        fontdb: &FontDB,
+    ) -> Result<RenderableContent, Error> {
+        let parser = TextDocumentParser::new(
+            keyframe,
+            &text.ranges,
+            &text.options,
+            text_path,
+            model,
+            fontdb,
+        )?;
+        let shape = parser.shape_layer()?;
+        Ok(RenderableContent::Shape(ShapeGroup {
+            shapes: vec![shape],
+        }))
+    }
+}
+
+/// Chars of the documents of a text layer that neither its font nor the
+/// fallbacks contain, in order of appearance. They are drawn as `.notdef`
+/// boxes. Documents whose font is not loaded are skipped, as staging the
+/// layer fails on them
+pub(crate) fn missing_chars(text: &TextAnimationData, model: &Model, fontdb: &FontDB) -> Vec<char> {
+    let mut result = vec![];
+    for keyframe in &text.document.keyframes {
+        let Ok(parser) =
+            TextDocumentParser::new(keyframe, &text.ranges, &text.options, None, model, fontdb)
+        else {
+            continue;
+        };
+        let Ok(shaper) = parser.shaper() else {
+            continue;
+        };
+        for c in parser.value().into_iter().flatten() {
+            if !c.is_whitespace() && !c.is_control() && !shaper.contains(c) && !result.contains(&c)
+            {
+                result.push(c);
+            }
+        }
+    }
+    result
+}
+
+/// Scale the document keyframe of `original` to the font size `size`, along
+/// with its line height
+fn resize(keyframe: &mut KeyFrame<TextDocument>, original: &TextDocument, size: f32) {
+    for doc in [&mut keyframe.start_value, &mut keyframe.end_value] {
+        doc.size = size;
+        doc.line_height = original.line_height.map(|h| h * size / original.size);
+    }
+}
+
+/// A line of text, split in spans of the same size
+struct TextLine {
+    spans: Vec<TextSpan>,
+}
+
+struct TextSpan {
+    chars: Vec<char>,
+    size: f32,
+    letter_spacing: f32,
 }
 
 #[derive(Clone)]
 struct Styles {
     fill: Rgb,
     fill_opacity: f32,
+    stroke: Option<StrokeStyles>,
+}
+
+#[derive(Clone)]
+struct StrokeStyles {
+    color: Rgb,
+    opacity: f32,
+    width: f32,
+    above_fill: bool,
 }
 
 struct TextDocumentParser<'a> {
     model: &'a Model,
-    fontdb: &'a FontDB,
-    area: Area<Styles, TextMetrics>,
+    /// Raw data of the font and of the fallbacks for glyphs it lacks, shaped
+    /// with rustybuzz. Empty when the text is drawn with embedded glyphs
+    fonts: Vec<FontFace>,
+    /// Glyphs of the `chars` array, used when they cover the whole text
+    embedded: HashMap<char, &'a Character>,
+    lines: Vec<TextLine>,
+    /// Whether each line ends a paragraph, instead of being broken by
+    /// wrapping
+    paragraph_ends: Vec<bool>,
+    styles: Styles,
     lottie_font: &'a Font,
     keyframe: &'a KeyFrame<TextDocument>,
     text_ranges: &'a Vec<TextRange>,
+    options: &'a TextAlignmentOptions,
+    text_path: Option<&'a TextPath>,
 }
 
 impl<'a> TextDocumentParser<'a> {
     fn new(
         keyframe: &'a KeyFrame<TextDocument>,
         text_ranges: &'a Vec<TextRange>,
+        options: &'a TextAlignmentOptions,
+        text_path: Option<&'a TextPath>,
         model: &'a Model,
         fontdb: &'a FontDB,
     ) -> Result<Self, Error> {
@@ -63,257 +245,386 @@ impl<'a> TextDocumentParser<'a> {
         let lottie_font = model
             .font(&doc.font_name)
             .ok_or_else(|| Error::FontFamilyNotFound(doc.font_name.clone()))?;
-        let font = fontdb
-            .font(lottie_font)
-            .ok_or_else(|| Error::FontNotLoaded(doc.font_name.clone()))?;
-        font.load()?;
+        // chars are looked up after applying caps
+        let chars = match doc.caps {
+            TextCaps::Regular => doc.value.clone(),
+            TextCaps::AllCaps | TextCaps::SmallCaps => doc.value.chars().map(to_upper).collect(),
+        };
+        // Glyphs exported with the animation render the same everywhere, so
+        // they are preferred over fonts when they hold every char
+        let embedded = chars
+            .chars()
+            .filter(|c| *c != '\r')
+            .map(|c| Some((c, model.character(lottie_font, c)?)))
+            .collect::<Option<HashMap<_, _>>>()
+            .filter(|embedded| !embedded.is_empty());
+        let (fonts, embedded) = match embedded {
+            Some(embedded) => (vec![], embedded),
+            None => {
+                let fonts = fontdb
+                    .font_chain(lottie_font, &chars)
+                    .ok_or_else(|| Error::FontNotLoaded(doc.font_name.clone()))?;
+                (fonts, HashMap::new())
+            }
+        };
+        let shaper = new_shaper(&fonts, &embedded, lottie_font)?;
 
         // parse fill/opacity data
         let rgb = Rgb::new_u8(doc.fill_color.r, doc.fill_color.g, doc.fill_color.b);
 
         let opacity = doc.fill_color.a as f32 / 255.0 * 100.0;
+        let stroke = if doc.stroke_width > 0.0 {
+            let color = doc.stroke_color;
+            Some(StrokeStyles {
+                color: Rgb::new_u8(color.r, color.g, color.b),
+                opacity: color.a as f32 / 255.0 * 100.0,
+                width: doc.stroke_width,
+                above_fill: doc.stroke_above_fill,
+            })
+        } else {
+            None
+        };
         let styles = Styles {
             fill: rgb,
             fill_opacity: opacity,
+            stroke,
         };
-        // parse font data
-        let mut area = Area::new();
-        for line in doc.value.split('\r') {
-            let metrics = font.measure(&line)?;
-            let span = Span {
-                font_key: font.key(),
-                letter_spacing: 0.0,
-                line_height: None,
-                size: doc.size,
-                broke_from_prev: false,
-                metrics,
-                swallow_leading_space: false,
-                additional: styles.clone(),
+        // split the text into lines
+        let mut lines = vec![];
+        let mut paragraph_ends = vec![];
+        let text_box = doc.text_box();
+        for paragraph in doc.value.split('\r') {
+            let wrapped = match text_box {
+                Some(text_box) => wrap_paragraph(&shaper, paragraph, doc, text_box.width()),
+                None => vec![paragraph.to_string()],
             };
-            let line = Line::new(span);
-            area.lines.push(line);
+            let count = wrapped.len();
+            for (index, line) in wrapped.into_iter().enumerate() {
+                let mut runs = caps_runs(&line, doc.caps);
+                if runs.is_empty() {
+                    // keep empty lines so they still take vertical space
+                    runs.push((line, 1.0));
+                }
+                let spans = runs
+                    .into_iter()
+                    .map(|(text, scale)| {
+                        let size = doc.size * scale;
+                        TextSpan {
+                            chars: text.chars().collect(),
+                            size,
+                            letter_spacing: doc.tracking / 1000.0 * size,
+                        }
+                    })
+                    .collect();
+                lines.push(TextLine { spans });
+                paragraph_ends.push(index + 1 == count);
+            }
         }
 
         Ok(TextDocumentParser {
             model,
-            fontdb,
-            area,
+            fonts,
+            embedded,
+            lines,
+            paragraph_ends,
+            styles,
             lottie_font,
             keyframe,
             text_ranges,
+            options,
+            text_path,
         })
     }
 
-    fn shape_layer(&self) -> Result<ShapeLayer, Error> {
-        let font = self
-            .fontdb
-            .font(self.lottie_font)
-            .ok_or_else(|| Error::FontNotLoaded(self.lottie_font.name.clone()))?;
-        font.load()?;
-        let units = font.units_per_em() as f32;
-        let doc = &self.keyframe.start_value;
+    fn shaper(&self) -> Result<Shaper<'_>, Error> {
+        new_shaper(&self.fonts, &self.embedded, self.lottie_font)
+    }
 
-        let mut result = vec![];
-        let align_factor = match doc.justify {
-            TextJustify::Left => 0.0,
-            TextJustify::Center => -0.5,
-            TextJustify::Right => -1.0,
-            _ => 0.0, // TODO: support other TextJustify options
-        };
-        let start_shift_y = -doc.baseline_shift;
-        let mut line_y = 0.0;
-        let value = self
-            .area
-            .lines
+    /// Chars of each line, after applying caps
+    fn value(&self) -> Vec<Vec<char>> {
+        self.lines
             .iter()
             .map(|l| {
                 l.spans
                     .iter()
-                    .flat_map(|span| span.metrics.chars())
-                    .collect::<Vec<_>>()
+                    .flat_map(|span| span.chars.iter().cloned())
+                    .collect()
             })
-            .collect::<Vec<_>>();
-        for (line_index, line) in self.area.lines.iter().enumerate() {
-            let mut char_index = 0;
-            let mut adv_base = line.width() * align_factor;
-            for span in &line.spans {
-                let factor = span.size / units;
-                let mut all_beziers = vec![];
-
-                // styles
-                let fill = self
-                    .keyframe
-                    .alter_value(span.additional.fill, span.additional.fill);
-                let fill_opacity = self
-                    .keyframe
-                    .alter_value(span.additional.fill_opacity, span.additional.fill_opacity);
-                let fill_layer = ShapeLayer {
+            .collect()
+    }
+
+    /// Whether the text fits its paragraph box, or `max_width` for point
+    /// text, see [TextFit]
+    fn fits(&self, max_width: Option<f32>) -> Result<bool, Error> {
+        let shaper = self.shaper()?;
+        let doc = &self.keyframe.start_value;
+        let layouts = self.layout_lines(&shaper, &self.value());
+        let width = match (doc.text_box(), max_width) {
+            // Lines overflowing the box are left out of the layout
+            (Some(_), _) if layouts.len() < self.lines.len() => return Ok(false),
+            (Some(text_box), _) => text_box.width(),
+            (None, Some(max_width)) => max_width,
+            (None, None) => return Ok(true),
+        };
+        // Words wider than the box are broken between chars
+        let words_fit = doc
+            .value
+            .split_whitespace()
+            .all(|word| text_width(&shaper, word, doc) <= width);
+        Ok(words_fit && layouts.iter().all(|layout| layout.width <= width))
+    }
+
+    fn shape_layer(&self) -> Result<ShapeLayer, Error> {
+        let shaper = self.shaper()?;
+        let doc = &self.keyframe.start_value;
+
+        let mut result = vec![];
+        let start_shift_y = -doc.baseline_shift;
+        let value = self.value();
+        let layouts = self.layout_lines(&shaper, &value);
+        let styles = &self.styles;
+        let units = Arc::new(
+            self.text_ranges
+                .iter()
+                .enumerate()
+                .map(|(index, range)| range.selector.units(&value, index))
+                .collect::<Vec<_>>(),
+        );
+        let tracking = Arc::new(Mutex::new(TrackingOffsets::default()));
+        for (line_index, layout) in layouts.iter().enumerate() {
+            let line_chars = &value[line_index];
+            let fill = self.keyframe.alter_value(styles.fill, styles.fill);
+            let fill_opacity = self
+                .keyframe
+                .alter_value(styles.fill_opacity, styles.fill_opacity);
+            let fill_layer = ShapeLayer {
+                name: None,
+                hidden: false,
+                shape: Shape::Fill(Fill {
+                    opacity: Animated {
+                        animated: false,
+                        expression: None,
+                        slot: None,
+                        keyframes: vec![fill_opacity],
+                    },
+                    color: Animated {
+                        animated: false,
+                        expression: None,
+                        slot: None,
+                        keyframes: vec![fill],
+                    },
+                    fill_rule: FillRule::NonZero,
+                }),
+            };
+            let stroke_layer = styles.stroke.as_ref().map(|stroke| ShapeLayer {
+                name: None,
+                hidden: false,
+                shape: Shape::Stroke(Stroke::new(
+                    Animated {
+                        animated: false,
+                        expression: None,
+                        slot: None,
+                        keyframes: vec![self.keyframe.alter_value(stroke.color, stroke.color)],
+                    },
+                    Animated {
+                        animated: false,
+                        expression: None,
+                        slot: None,
+                        keyframes: vec![self.keyframe.alter_value(stroke.opacity, stroke.opacity)],
+                    },
+                    Animated {
+                        animated: false,
+                        expression: None,
+                        slot: None,
+                        keyframes: vec![self.keyframe.alter_value(stroke.width, stroke.width)],
+                    },
+                )),
+            });
+            let stroke_above_fill = styles
+                .stroke
+                .as_ref()
+                .map(|stroke| stroke.above_fill)
+                .unwrap_or(false);
+
+            let mut glyphs = vec![];
+            for glyph in &layout.shaped {
+                // Range selectors and anchor grouping address the original chars
+                let char_index = glyph.char_index;
+                let c = line_chars[char_index];
+                let transform = match self.text_path {
+                    Some(text_path) => text_path.transform(
+                        &PathGlyph {
+                            x: glyph.x - layout.x,
+                            width: glyph.advance,
+                            y: start_shift_y + layout.y,
+                            index: char_index,
+                            count: line_chars.len(),
+                            line_width: layout.width,
+                        },
+                        doc.justify,
+                    ),
+                    None => {
+                        // Animators transform the glyph around the anchor
+                        // point of its group
+                        let alignment = self
+                            .options
+                            .alignment
+                            .clone()
+                            .unwrap_or_else(|| Animated::from_value(Vector2D::zero()));
+                        let anchor = |alignment: Vector2D| {
+                            self.glyph_anchor(&layouts, &value, line_index, char_index, alignment)
+                                - Vector2D::new(glyph.x - layout.glyphs[char_index].0, 0.0)
+                        };
+                        let map = |f: &dyn Fn(Vector2D) -> Vector2D| Animated {
+                            animated: alignment.animated,
+                            expression: None,
+                            slot: None,
+                            keyframes: alignment
+                                .keyframes
+                                .iter()
+                                .map(|k| k.alter_value(f(k.start_value), f(k.end_value)))
+                                .collect(),
+                        };
+                        let mut transform = Transform::default();
+                        transform.anchor = Some(map(&anchor));
+                        transform.position =
+                            Some(map(&|a| anchor(a) + Vector2D::new(glyph.x, -glyph.y)));
+                        transform
+                    }
+                };
+                let text_range = |color_glyph: bool| {
+                    if self.text_ranges.is_empty() {
+                        None
+                    } else {
+                        Some(TextRangeInfo {
+                            units: units.clone(),
+                            index: (line_index, char_index),
+                            ranges: self.text_ranges.clone(),
+                            color_glyph,
+                            tracking: tracking.clone(),
+                        })
+                    }
+                };
+                let path_layer = |beziers: Vec<Bezier>, text_range| ShapeLayer {
                     name: None,
                     hidden: false,
-                    shape: Shape::Fill(Fill {
-                        opacity: Animated {
-                            animated: false,
-                            keyframes: vec![fill_opacity],
-                        },
-                        color: Animated {
+                    shape: Shape::Path {
+                        d: Animated {
                             animated: false,
-                            keyframes: vec![fill],
+                            expression: None,
+                            slot: None,
+                            keyframes: vec![self.keyframe.alter_value(beziers.clone(), beziers)],
                         },
-                        fill_rule: FillRule::NonZero,
-                    }),
+                        text_range,
+                    },
                 };
-                for (index, c) in span.metrics.chars().into_iter().enumerate() {
-                    let (glyph, _) = font.outline(c).ok_or_else(|| {
-                        Error::FontGlyphNotFound(self.lottie_font.name.clone(), c)
-                    })?;
-                    let mut bezier = Bezier::default();
-                    let mut beziers = vec![];
-                    let mut last_pt = Vector2D::new(0.0, 0.0);
-                    let adv = adv_base
-                        + span
-                            .metrics
-                            .slice(0, index as u32)
-                            .width(span.size, span.letter_spacing);
-                    let segments = glyph.path.finish();
-                    let segments = segments
-                        .as_ref()
-                        .map(|p| p.segments())
+                let transform_layer = ShapeLayer {
+                    name: None,
+                    hidden: false,
+                    shape: Shape::Transform(transform),
+                };
+                let color_glyph = shaper.color_glyph(glyph, doc.fill_color);
+                let shapes = match (color_glyph, stroke_layer.as_ref()) {
+                    // Color glyphs keep their own colors and are not stroked
+                    (Some(ColorGlyph::Layers(layers)), _) => layers
                         .into_iter()
-                        .flatten();
-                    for segment in segments {
-                        match segment {
-                            PathSegment::MoveTo(p) => {
-                                if !bezier.verticies.is_empty() {
-                                    let mut old = std::mem::replace(&mut bezier, Bezier::default());
-                                    old.out_tangent.push(Vector2D::new(0.0, 0.0));
-                                    beziers.push(old);
-                                }
-                                bezier.in_tangent.push(Vector2D::new(0.0, 0.0));
-                                last_pt = Vector2D::new(p.x, -p.y) * factor;
-                                bezier.verticies.push(last_pt);
-                            }
-                            PathSegment::LineTo(p) => {
-                                let pt = Vector2D::new(p.x, -p.y) * factor;
-                                bezier.out_tangent.push(Vector2D::new(0.0, 0.0));
-                                bezier.in_tangent.push(Vector2D::new(0.0, 0.0));
-                                bezier.verticies.push(pt);
-                                last_pt = pt;
-                            }
-                            PathSegment::CubicTo(p1, p2, p) => {
-                                let pt1 = Vector2D::new(p1.x, -p1.y) * factor;
-                                let pt2 = Vector2D::new(p2.x, -p2.y) * factor;
-                                let pt = Vector2D::new(p.x, -p.y) * factor;
-
-                                bezier.out_tangent.push(pt1 - last_pt);
-                                bezier.in_tangent.push(pt2 - pt);
-                                bezier.verticies.push(pt);
-                                last_pt = pt;
-                            }
-                            PathSegment::QuadTo(p1, p) => {
-                                let pt1 = Vector2D::new(p1.x, -p1.y) * factor;
-                                let pt = Vector2D::new(p.x, -p.y) * factor;
-
-                                bezier.out_tangent.push(pt1 - last_pt);
-                                bezier.in_tangent.push(pt1 - pt);
-                                bezier.verticies.push(pt);
-                                last_pt = pt;
-                            }
-                            PathSegment::Close => {
-                                bezier.closed = true;
-                            }
-                        }
-                    }
-                    if !bezier.verticies.is_empty() {
-                        bezier.out_tangent.push(Vector2D::new(0.0, 0.0));
-                        beziers.push(bezier);
-                    }
-                    all_beziers.push(GlyphData {
-                        c,
-                        beziers,
-                        offset_x: adv,
-                    });
-                }
-
-                let mut glyphs = all_beziers
-                    .into_iter()
-                    .map(|data| {
-                        let GlyphData {
-                            c,
-                            beziers,
-                            offset_x,
-                        } = data;
-
-                        let mut transform = Transform::default();
-                        transform.position = Some(Animated {
-                            animated: false,
-                            keyframes: vec![KeyFrame::from_value(Vector2D::new(offset_x, 0.0))],
-                        });
-                        let text_range = if self.text_ranges.is_empty() {
-                            None
-                        } else {
-                            Some(TextRangeInfo {
-                                value: value.clone(),
-                                index: (line_index, char_index),
-                                ranges: self.text_ranges.clone(),
-                            })
-                        };
-                        char_index += 1;
-                        ShapeLayer {
-                            name: Some(format!("{}", c)),
+                        .map(|(beziers, style)| ShapeLayer {
+                            name: None,
                             hidden: false,
                             shape: Shape::Group {
                                 shapes: vec![
+                                    path_layer(beziers, text_range(true)),
                                     ShapeLayer {
                                         name: None,
                                         hidden: false,
-                                        shape: Shape::Path {
-                                            d: Animated {
-                                                animated: false,
-                                                keyframes: vec![self
-                                                    .keyframe
-                                                    .alter_value(beziers.clone(), beziers)],
-                                            },
-                                            text_range,
-                                        },
+                                        shape: style,
                                     },
+                                    transform_layer.clone(),
+                                ],
+                            },
+                        })
+                        .collect(),
+                    // The fill only carries the opacity of bitmap glyphs
+                    (Some(ColorGlyph::Image(image)), _) => vec![
+                        ShapeLayer {
+                            name: None,
+                            hidden: false,
+                            shape: Shape::Image {
+                                image,
+                                text_range: text_range(true),
+                            },
+                        },
+                        fill_layer.clone(),
+                        transform_layer,
+                    ],
+                    (None, None) => vec![
+                        path_layer(self.glyph_outline(&shaper, glyph, c), text_range(false)),
+                        fill_layer.clone(),
+                        transform_layer,
+                    ],
+                    // Fill and stroke are painted as separate groups, the first one on
+                    // top, so that the stroke could go below the fill. Each group holds
+                    // the glyph transform, so that text animators see it on the path
+                    (None, Some(stroke_layer)) => {
+                        let path_layer =
+                            path_layer(self.glyph_outline(&shaper, glyph, c), text_range(false));
+                        let filled = ShapeLayer {
+                            name: None,
+                            hidden: false,
+                            shape: Shape::Group {
+                                shapes: vec![
+                                    path_layer.clone(),
                                     fill_layer.clone(),
-                                    ShapeLayer {
-                                        name: None,
-                                        hidden: false,
-                                        shape: Shape::Transform(transform),
-                                    },
+                                    transform_layer.clone(),
                                 ],
                             },
+                        };
+                        let stroked = ShapeLayer {
+                            name: None,
+                            hidden: false,
+                            shape: Shape::Group {
+                                shapes: vec![path_layer, stroke_layer.clone(), transform_layer],
+                            },
+                        };
+                        if stroke_above_fill {
+                            vec![stroked, filled]
+                        } else {
+                            vec![filled, stroked]
                         }
-                    })
-                    .collect::<Vec<_>>();
-
-                let shift = Vector2D::new(0.0, start_shift_y + line_y);
-                let transform_position = self.keyframe.alter_value(shift, shift);
-                let mut transform = Transform::default();
-                transform.position = Some(Animated {
-                    animated: false,
-                    keyframes: vec![transform_position],
-                });
-
+                    }
+                };
                 glyphs.push(ShapeLayer {
-                    name: None,
-                    hidden: false,
-                    shape: Shape::Transform(transform),
-                });
-                let line_values = line
-                    .spans()
-                    .iter()
-                    .map(|span| span.metrics.value().to_string())
-                    .collect::<Vec<_>>();
-                let line_value = line_values.join("");
-                result.push(ShapeLayer {
-                    name: Some(line_value),
+                    name: Some(format!("{}", c)),
                     hidden: false,
-                    shape: Shape::Group { shapes: glyphs },
+                    shape: Shape::Group { shapes },
                 });
             }
-            line_y += line.height();
+
+            // Glyphs on a path carry their own offset from the path
+            let shift = if self.text_path.is_some() {
+                Vector2D::zero()
+            } else {
+                Vector2D::new(0.0, start_shift_y + layout.y)
+            };
+            let transform_position = self.keyframe.alter_value(shift, shift);
+            let mut transform = Transform::default();
+            transform.position = Some(Animated {
+                animated: false,
+                expression: None,
+                slot: None,
+                keyframes: vec![transform_position],
+            });
+
+            glyphs.push(ShapeLayer {
+                name: None,
+                hidden: false,
+                shape: Shape::Transform(transform),
+            });
+            result.push(ShapeLayer {
+                name: Some(line_chars.iter().collect()),
+                hidden: false,
+                shape: Shape::Group { shapes: glyphs },
+            });
         }
         Ok(ShapeLayer {
             name: None,
@@ -321,4 +632,290 @@ impl<'a> TextDocumentParser<'a> {
             shape: Shape::Group { shapes: result },
         })
     }
+
+    /// Outline of a glyph, a box if no font contains it
+    fn glyph_outline(&self, shaper: &Shaper, glyph: &ShapedGlyph, c: char) -> Vec<Bezier> {
+        let doc = &self.keyframe.start_value;
+        let mut beziers = shaper.outline(glyph);
+        if glyph.glyph_id == 0 && glyph.embedded.is_none() && !c.is_whitespace() && !c.is_control()
+        {
+            log::warn!(
+                "Neither font {} nor its fallbacks contain the glyph for {:?}",
+                self.lottie_font.name,
+                c
+            );
+            if beziers.is_empty() {
+                let width = if glyph.advance > 0.0 {
+                    glyph.advance
+                } else {
+                    glyph.size / 2.0
+                };
+                beziers = notdef_box(width, self.ascent() * glyph.size / doc.size);
+            }
+        }
+        beziers
+    }
+
+    fn ascent(&self) -> f32 {
+        let doc = &self.keyframe.start_value;
+        doc.size * self.lottie_font.ascent.unwrap_or(DEFAULT_ASCENT) / 100.0
+    }
+
+    /// Place lines and their glyphs. Lines overflowing the paragraph box are
+    /// left out
+    fn layout_lines(&self, shaper: &Shaper, value: &[Vec<char>]) -> Vec<LineLayout> {
+        let doc = &self.keyframe.start_value;
+        // Point text is aligned around x = 0, while paragraph text is aligned
+        // inside its box, with the first baseline one ascent below the box top
+        let text_box = doc.text_box();
+        let ascent = self.ascent();
+        let (origin_x, available_width) = text_box
+            .map(|text_box| (text_box.min_x(), text_box.width()))
+            .unwrap_or((0.0, 0.0));
+        let mut line_y = text_box
+            .map(|text_box| text_box.min_y() + ascent)
+            .unwrap_or(0.0);
+        let mut result = vec![];
+        for (line_index, line) in self.lines.iter().enumerate() {
+            let line_advance = doc.line_height.unwrap_or_else(|| {
+                line.spans
+                    .iter()
+                    .map(|span| shaper.line_height(span.size))
+                    .fold(0.0, f32::max)
+            });
+            if let Some(text_box) = text_box {
+                // Lines overflowing the box are not rendered, like After Effects does
+                if line_y - ascent + line_advance > text_box.max_y() + f32::EPSILON {
+                    break;
+                }
+            }
+            let last_line = self.paragraph_ends[line_index];
+            let chars = &value[line_index];
+            let styles = line
+                .spans
+                .iter()
+                .flat_map(|span| {
+                    let style = (span.size, span.letter_spacing);
+                    span.chars.iter().map(move |_| style)
+                })
+                .collect::<Vec<_>>();
+            let mut shaped = shaper.shape_line(chars, &styles);
+            // Trailing spaces do not count in the width of a line
+            let visible = chars.len() - chars.iter().rev().take_while(|c| **c == ' ').count();
+            let width = shaped
+                .iter()
+                .filter(|glyph| glyph.char_index < visible)
+                .map(|glyph| glyph.advance)
+                .sum::<f32>();
+            let x = origin_x + (available_width - width) * doc.justify.align_factor(last_line);
+            // Extra advance of each space when the line is stretched to the box width
+            let inner_spaces = chars[..visible].iter().filter(|c| **c == ' ').count();
+            let space_extra =
+                if text_box.is_some() && doc.justify.is_full(last_line) && inner_spaces > 0 {
+                    (available_width - width).max(0.0) / inner_spaces as f32
+                } else {
+                    0.0
+                };
+            let mut glyphs: Vec<Option<(f32, f32)>> = vec![None; chars.len()];
+            let mut spaces_before = 0;
+            for glyph in &mut shaped {
+                glyph.x += x + spaces_before as f32 * space_extra;
+                match &mut glyphs[glyph.char_index] {
+                    Some((_, width)) => *width += glyph.advance,
+                    entry => *entry = Some((glyph.x, glyph.advance)),
+                }
+                if chars[glyph.char_index] == ' ' && glyph.char_index < visible {
+                    spaces_before += 1;
+                }
+            }
+            // Chars merged into a ligature sit at the end of the previous char
+            let mut previous = (x, 0.0);
+            let glyphs = glyphs
+                .into_iter()
+                .map(|glyph| {
+                    let glyph = glyph.unwrap_or((previous.0 + previous.1, 0.0));
+                    previous = glyph;
+                    glyph
+                })
+                .collect();
+            result.push(LineLayout {
+                x,
+                y: line_y,
+                width,
+                glyphs,
+                shaped,
+            });
+            line_y += line_advance;
+        }
+        result
+    }
+
+    /// Anchor point of a glyph relative to its origin, at the center of its
+    /// group offset by `alignment`
+    fn glyph_anchor(
+        &self,
+        layouts: &[LineLayout],
+        value: &[Vec<char>],
+        line_index: usize,
+        index: usize,
+        alignment: Vector2D,
+    ) -> Vector2D {
+        let layout = &layouts[line_index];
+        let chars = &value[line_index];
+        let (x, width) = layout.glyphs[index];
+        let ((min_x, max_x), center_y) = match self.options.grouping {
+            TextGrouping::Words if !chars[index].is_whitespace() => {
+                let in_word = |i: &usize| !chars[*i].is_whitespace();
+                let start = (0..index).rev().take_while(in_word).last().unwrap_or(index);
+                let end = (index + 1..chars.len())
+                    .take_while(in_word)
+                    .last()
+                    .unwrap_or(index);
+                // words may run right to left, so take the extent of all their chars
+                let word = &layout.glyphs[start..=end];
+                let min_x = word.iter().map(|(x, _)| *x).fold(f32::MAX, f32::min);
+                let max_x = word.iter().map(|(x, w)| x + w).fold(f32::MIN, f32::max);
+                ((min_x, max_x), 0.0)
+            }
+            TextGrouping::Characters | TextGrouping::Words => ((x, x + width), 0.0),
+            TextGrouping::Lines => ((layout.x, layout.x + layout.width), 0.0),
+            TextGrouping::All => {
+                let min_x = layouts.iter().map(|l| l.x).fold(f32::MAX, f32::min);
+                let max_x = layouts
+                    .iter()
+                    .map(|l| l.x + l.width)
+                    .fold(f32::MIN, f32::max);
+                let first_y = layouts[0].y;
+                let last_y = layouts[layouts.len() - 1].y;
+                ((min_x, max_x), (first_y + last_y) / 2.0 - layout.y)
+            }
+        };
+        let half_width = (max_x - min_x) / 2.0;
+        Vector2D::new(
+            min_x + half_width * (1.0 + alignment.x / 100.0) - x,
+            center_y + alignment.y / 100.0 * self.ascent() / 2.0,
+        )
+    }
+}
+
+/// Shaper of the embedded glyphs when there are some, else of the font chain
+fn new_shaper<'f>(
+    fonts: &'f [FontFace],
+    embedded: &HashMap<char, &'f Character>,
+    lottie_font: &Font,
+) -> Result<Shaper<'f>, Error> {
+    if !embedded.is_empty() {
+        return Ok(Shaper::embedded(embedded.clone()));
+    }
+    Shaper::new(fonts).ok_or_else(|| Error::FontNotLoaded(lottie_font.name.clone()))
+}
+
+/// Splits text into runs of the same size after applying `caps`, as pairs of
+/// the transformed text and its scale relative to the font size. Every char
+/// maps to exactly one char, so indices of range selectors stay valid.
+fn caps_runs(text: &str, caps: TextCaps) -> Vec<(String, f32)> {
+    let mut runs: Vec<(String, f32)> = vec![];
+    for c in text.chars() {
+        let (c, scale) = match caps {
+            TextCaps::Regular => (c, 1.0),
+            TextCaps::AllCaps => (to_upper(c), 1.0),
+            TextCaps::SmallCaps if c.is_lowercase() => (to_upper(c), SMALL_CAPS_SCALE),
+            TextCaps::SmallCaps => (c, 1.0),
+        };
+        match runs.last_mut() {
+            Some((run, run_scale)) if *run_scale == scale => run.push(c),
+            _ => runs.push((c.to_string(), scale)),
+        }
+    }
+    runs
+}
+
+/// Uppercase of a char, or the char itself if the uppercase takes more than
+/// one char (e.g. `ß`)
+fn to_upper(c: char) -> char {
+    let mut upper = c.to_uppercase();
+    match (upper.next(), upper.next()) {
+        (Some(upper), None) => upper,
+        _ => c,
+    }
+}
+
+/// Width of a text laid out with the size, tracking and caps of `doc`
+fn text_width(shaper: &Shaper, text: &str, doc: &TextDocument) -> f32 {
+    char_advances(shaper, text, doc).iter().sum()
+}
+
+/// Advance of each char of a text laid out with the size, tracking and caps
+/// of `doc`. Chars merged into the glyph of a previous char have none
+fn char_advances(shaper: &Shaper, text: &str, doc: &TextDocument) -> Vec<f32> {
+    let runs = caps_runs(text, doc.caps);
+    let (chars, styles): (Vec<_>, Vec<_>) = runs
+        .iter()
+        .flat_map(|(run, scale)| {
+            let size = doc.size * scale;
+            let style = (size, doc.tracking / 1000.0 * size);
+            run.chars().map(move |c| (c, style))
+        })
+        .unzip();
+    let mut result = vec![0.0; chars.len()];
+    for glyph in shaper.shape_line(&chars, &styles) {
+        result[glyph.char_index] += glyph.advance;
+    }
+    result
+}
+
+/// Greedily breaks a paragraph into lines no wider than `width`. Spaces are
+/// kept at the end of the line they break, so that every char of the
+/// paragraph still belongs to exactly one line. The paragraph is shaped once,
+/// lines are measured with the advances of their chars
+fn wrap_paragraph(shaper: &Shaper, paragraph: &str, doc: &TextDocument, width: f32) -> Vec<String> {
+    let mut advances = char_advances(shaper, paragraph, doc).into_iter();
+    let mut lines = vec![];
+    // the current line, with its width and its width without trailing spaces
+    let mut current = String::new();
+    let (mut total, mut visible) = (0.0, 0.0);
+    for word in paragraph.split_inclusive(' ') {
+        let chars = word.chars().zip(advances.by_ref()).collect::<Vec<_>>();
+        let word_total = chars.iter().map(|(_, advance)| advance).sum::<f32>();
+        let word_visible = chars
+            .iter()
+            .filter(|(c, _)| *c != ' ')
+            .map(|(_, advance)| advance)
+            .sum::<f32>();
+        let spaces = chars.iter().all(|(c, _)| *c == ' ');
+        let candidate = if spaces {
+            visible
+        } else {
+            total + word_visible
+        };
+        if candidate <= width {
+            current.push_str(word);
+            (total, visible) = (total + word_total, candidate);
+            continue;
+        }
+        if !current.is_empty() {
+            lines.push(std::mem::take(&mut current));
+        }
+        if word_visible <= width {
+            current = word.to_string();
+            (total, visible) = (word_total, word_visible);
+            continue;
+        }
+        // The word alone is wider than the box, break it between chars
+        (total, visible) = (0.0, 0.0);
+        for (c, advance) in chars {
+            let candidate = if c == ' ' { visible } else { total + advance };
+            if !current.is_empty() && candidate > width {
+                lines.push(std::mem::take(&mut current));
+                (total, visible) = (0.0, 0.0);
+            }
+            current.push(c);
+            if c != ' ' {
+                visible = total + advance;
+            }
+            total += advance;
+        }
+    }
+    lines.push(current);
+    lines
 }
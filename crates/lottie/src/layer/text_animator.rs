@@ -0,0 +1,427 @@
+use glam::{Mat4, Vec3};
+
+use crate::model::*;
+use crate::Lerp;
+
+/// Base styles of a glyph, which text animators interpolate from
+#[derive(Debug, Clone, Copy)]
+pub struct GlyphStyle {
+    pub fill_color: Rgb,
+    pub stroke_color: Rgb,
+    pub stroke_width: f32,
+}
+
+impl Default for GlyphStyle {
+    fn default() -> Self {
+        GlyphStyle {
+            fill_color: Rgb::new_u8(0, 0, 0),
+            stroke_color: Rgb::new_u8(0, 0, 0),
+            stroke_width: 0.0,
+        }
+    }
+}
+
+/// Combined effect of all text animators on a single glyph at a given frame
+#[derive(Debug, Clone)]
+pub struct GlyphAnimation {
+    pub anchor: Vector2D,
+    pub position: Vector2D,
+    /// Scale factor, `1.0` leaves the glyph untouched
+    pub scale: Vector2D,
+    /// Rotation in degrees
+    pub rotation: f32,
+    /// Skew in degrees
+    pub skew: f32,
+    /// Skew axis in degrees
+    pub skew_axis: f32,
+    /// Opacity factor in `0.0..=1.0`
+    pub opacity: f32,
+    /// Stroke opacity factor in `0.0..=1.0`, applied on top of `opacity`
+    pub stroke_opacity: f32,
+    /// Horizontal shift caused by the tracking of preceding glyphs in the line
+    pub tracking: f32,
+    pub style: GlyphStyle,
+}
+
+impl GlyphAnimation {
+    fn new(style: GlyphStyle) -> Self {
+        GlyphAnimation {
+            anchor: Vector2D::zero(),
+            position: Vector2D::zero(),
+            scale: Vector2D::new(1.0, 1.0),
+            rotation: 0.0,
+            skew: 0.0,
+            skew_axis: 0.0,
+            opacity: 1.0,
+            stroke_opacity: 1.0,
+            tracking: 0.0,
+            style,
+        }
+    }
+
+    /// Transform of the glyph relative to its laid out position
+    pub fn matrix(&self) -> Mat4 {
+        let position = Vec3::new(self.position.x + self.tracking, self.position.y, 0.0);
+        let anchor = Vec3::new(self.anchor.x, self.anchor.y, 0.0);
+        let axis = self.skew_axis.to_radians();
+        let shear = (-self.skew.to_radians()).tan();
+        #[rustfmt::skip]
+        let skew = Mat4::from_rotation_z(axis)
+            * Mat4::from_cols_array(&[
+                1.0, 0.0, 0.0, 0.0,
+                shear, 1.0, 0.0, 0.0,
+                0.0, 0.0, 1.0, 0.0,
+                0.0, 0.0, 0.0, 1.0,
+            ])
+            * Mat4::from_rotation_z(-axis);
+        Mat4::from_translation(position)
+            * Mat4::from_rotation_z(self.rotation.to_radians())
+            * skew
+            * Mat4::from_scale(Vec3::new(self.scale.x, self.scale.y, 1.0))
+            * Mat4::from_translation(-anchor)
+    }
+
+    /// Matrix of a glyph `transform` with this animation applied around the
+    /// glyph anchor point
+    pub fn glyph_matrix(&self, transform: &Transform, frame: f32) -> Mat4 {
+        let anchor = transform
+            .anchor
+            .as_ref()
+            .map(|a| a.value(frame))
+            .unwrap_or_default();
+        let anchor = Vec3::new(anchor.x, anchor.y, 0.0);
+        transform.value(frame)
+            * Mat4::from_translation(anchor)
+            * self.matrix()
+            * Mat4::from_translation(-anchor)
+    }
+
+    fn apply(&mut self, style: &TextStyle, frame: f32, amount: f32) {
+        let value =
+            |animated: &Option<Animated<f32>>| animated.as_ref().map(|a| a.value(frame) * amount);
+        if let Some(transform) = style.transform.as_ref() {
+            if let Some(anchor) = transform.anchor.as_ref() {
+                self.anchor += anchor.value(frame) * amount;
+            }
+            if let Some(position) = transform.position.as_ref() {
+                self.position += position.value(frame) * amount;
+            }
+            let scale = transform.scale.value(frame) / 100.0;
+            self.scale.x *= 1.0 + (scale.x - 1.0) * amount;
+            self.scale.y *= 1.0 + (scale.y - 1.0) * amount;
+            self.rotation += transform.rotation.value(frame) * amount;
+            self.skew += value(&transform.skew).unwrap_or(0.0);
+            self.skew_axis += value(&transform.skew_axis).unwrap_or(0.0);
+            self.opacity *= 1.0 + (transform.opacity.value(frame) / 100.0 - 1.0) * amount;
+        }
+        if let Some(opacity) = style.stroke_opacity.as_ref() {
+            self.stroke_opacity *= 1.0 + (opacity.value(frame) / 100.0 - 1.0) * amount;
+        }
+        if let Some(width) = value(&style.stroke_width) {
+            self.style.stroke_width += width;
+        }
+        self.style.fill_color = animate_color(
+            self.style.fill_color,
+            style.fill_color.as_ref().map(|c| c.value(frame)),
+            value(&style.fill_hue),
+            value(&style.fill_saturation),
+            value(&style.fill_brightness),
+            amount,
+        );
+        self.style.stroke_color = animate_color(
+            self.style.stroke_color,
+            style.stroke_color.as_ref().map(|c| c.value(frame)),
+            value(&style.stroke_hue),
+            value(&style.stroke_saturation),
+            value(&style.stroke_brightness),
+            amount,
+        );
+    }
+}
+
+/// Mixes `color` towards `target` by `amount`, then offsets hue (degrees),
+/// saturation and brightness (percentage)
+fn animate_color(
+    color: Rgb,
+    target: Option<Rgb>,
+    hue: Option<f32>,
+    saturation: Option<f32>,
+    brightness: Option<f32>,
+    amount: f32,
+) -> Rgb {
+    let color = match target {
+        Some(target) => target.lerp(&color, amount),
+        None => color,
+    };
+    if hue.is_none() && saturation.is_none() && brightness.is_none() {
+        return color;
+    }
+    let (h, s, b) = color.to_hsb();
+    Rgb::from_hsb(
+        h + hue.unwrap_or(0.0),
+        s + saturation.unwrap_or(0.0) / 100.0,
+        b + brightness.unwrap_or(0.0) / 100.0,
+    )
+}
+
+impl TextRangeInfo {
+    /// Evaluate all text animators for this glyph at `frame`, starting from
+    /// the base `style` of the glyph
+    pub fn animate(&self, frame: f32, style: GlyphStyle) -> GlyphAnimation {
+        let (line, c) = self.index;
+        let mut result = GlyphAnimation::new(style);
+        for (range, units) in self.ranges.iter().zip(self.units.iter()) {
+            let style = match range.style.as_ref() {
+                Some(style) => style,
+                None => continue,
+            };
+            let selection = range.selector.selection(frame, units.total);
+            let line_units = units.indices.get(line).map(Vec::as_slice).unwrap_or(&[]);
+            let amount = selection.amount(line_units.get(c).copied().flatten());
+            if amount != 0.0 {
+                result.apply(style, frame, amount);
+            }
+        }
+        result.tracking = self.tracking_offset(frame);
+        result
+    }
+
+    /// Shift of this glyph caused by the tracking of the preceding glyphs of
+    /// its line. Offsets of the whole text are computed once per frame
+    fn tracking_offset(&self, frame: f32) -> f32 {
+        let (line, c) = self.index;
+        let Ok(mut tracking) = self.tracking.lock() else {
+            return 0.0;
+        };
+        if tracking.frame != Some(frame) {
+            tracking.offsets = self.tracking_offsets(frame);
+            tracking.frame = Some(frame);
+        }
+        tracking
+            .offsets
+            .get(line)
+            .and_then(|offsets| offsets.get(c))
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// Tracking of a glyph pushes all following glyphs in the same line, so
+    /// the offset of each char is the running sum of the preceding amounts
+    fn tracking_offsets(&self, frame: f32) -> Vec<Vec<f32>> {
+        let lines = self
+            .units
+            .first()
+            .map(|u| u.indices.as_slice())
+            .unwrap_or(&[]);
+        let mut result = lines
+            .iter()
+            .map(|line| vec![0.0; line.len()])
+            .collect::<Vec<_>>();
+        for (range, units) in self.ranges.iter().zip(self.units.iter()) {
+            let tracking = match range.style.as_ref().and_then(|s| s.letter_spacing.as_ref()) {
+                Some(tracking) => tracking.value(frame),
+                None => continue,
+            };
+            let selection = range.selector.selection(frame, units.total);
+            for (offsets, line_units) in result.iter_mut().zip(units.indices.iter()) {
+                let mut amounts = 0.0;
+                for (offset, unit) in offsets.iter_mut().zip(line_units) {
+                    *offset += tracking * amounts;
+                    amounts += selection.amount(*unit);
+                }
+            }
+        }
+        result
+    }
+}
+
+impl TextRangeSelector {
+    /// Units of the chars of `value` for this selector. `seed` makes
+    /// randomized order deterministic, and should differ between selectors
+    /// of the same text.
+    pub fn units(&self, value: &[Vec<char>], seed: usize) -> TextUnits {
+        let (mut indices, total) = unit_indices(self.based_on, value);
+        if self.randomize {
+            let order = shuffled(total, seed);
+            for index in indices.iter_mut().flatten().flatten() {
+                *index = order[*index];
+            }
+        }
+        TextUnits { indices, total }
+    }
+
+    /// Selected range of a text of `total` units at `frame`
+    fn selection(&self, frame: f32, total: usize) -> Selection<'_> {
+        let (divisor, default_end) = match self.range_units {
+            TextRangeUnits::Percentage => (100.0 / total as f32, 100.0),
+            TextRangeUnits::Index => (1.0, total as f32),
+        };
+        let value_of = |animated: &Option<Animated<f32>>, default: f32| {
+            animated.as_ref().map(|a| a.value(frame)).unwrap_or(default)
+        };
+        let offset = value_of(&self.offset, 0.0) / divisor;
+        let start = value_of(&self.start, 0.0) / divisor + offset;
+        let end = value_of(&self.end, default_end) / divisor + offset;
+        Selection {
+            shape: &self.shape,
+            start: start.min(end),
+            end: start.max(end),
+            easing: self.easing(frame),
+            max_amount: self.max_amount.value(frame) / 100.0,
+        }
+    }
+
+    /// Min/max ease applied the same way keyframe easing is applied, `None`
+    /// without easing
+    fn easing(&self, frame: f32) -> Option<KeyFrame<f32>> {
+        let min_ease = self.min_ease.value(frame) / 100.0;
+        let max_ease = self.max_ease.value(frame) / 100.0;
+        if min_ease == 0.0 && max_ease == 0.0 {
+            return None;
+        }
+        let (x1, y1) = if min_ease > 0.0 {
+            (0.0, min_ease)
+        } else {
+            (-min_ease, 0.0)
+        };
+        let (x2, y2) = if max_ease > 0.0 {
+            (1.0, 1.0 - max_ease)
+        } else {
+            (1.0 + max_ease, 1.0)
+        };
+        let mut easing = KeyFrame::from_value(0.0f32);
+        easing.end_value = 1.0;
+        easing.end_frame = 1.0;
+        easing.easing_out = Some(Easing {
+            x: vec![x1],
+            y: vec![y1],
+        });
+        easing.easing_in = Some(Easing {
+            x: vec![x2],
+            y: vec![y2],
+        });
+        Some(easing)
+    }
+}
+
+/// Range of a selector evaluated at a frame, in units
+struct Selection<'a> {
+    shape: &'a TextShape,
+    start: f32,
+    end: f32,
+    easing: Option<KeyFrame<f32>>,
+    max_amount: f32,
+}
+
+impl Selection<'_> {
+    /// Amount of the unit at `index`, nothing for chars outside of any unit
+    fn amount(&self, index: Option<usize>) -> f32 {
+        let Some(index) = index else {
+            return 0.0;
+        };
+        let mult = self.shape.amount(index as f32, self.start, self.end);
+        let mult = match &self.easing {
+            Some(easing) => easing.value(mult.clamp(0.0, 1.0)),
+            None => mult,
+        };
+        mult * self.max_amount
+    }
+}
+
+impl TextShape {
+    /// Amount of the unit at `index` inside the selection `start..end`,
+    /// expressed in units
+    fn amount(&self, index: f32, start: f32, end: f32) -> f32 {
+        let range = end - start;
+        // Ratio of the unit center inside the selection, for ramps and triangle
+        let ramp = || (0.5 / range + (index - start) / range).clamp(0.0, 1.0);
+        match self {
+            TextShape::Square => {
+                if index < start.floor() {
+                    0.0
+                } else if index < start {
+                    (end.min(1.0) - (start - index)).clamp(0.0, 1.0)
+                } else {
+                    (end - index).clamp(0.0, 1.0)
+                }
+            }
+            TextShape::RampUp if range == 0.0 => (index >= end) as u8 as f32,
+            TextShape::RampUp => ramp(),
+            TextShape::RampDown if range == 0.0 => (index < end) as u8 as f32,
+            TextShape::RampDown => 1.0 - ramp(),
+            _ if range == 0.0 => 0.0,
+            TextShape::Triangle => {
+                let ratio = ramp();
+                if ratio < 0.5 {
+                    ratio * 2.0
+                } else {
+                    1.0 - 2.0 * (ratio - 0.5)
+                }
+            }
+            TextShape::Round => {
+                let index = (index + 0.5 - start).clamp(0.0, range);
+                let half = range / 2.0;
+                let x = index - half;
+                (1.0 - (x * x) / (half * half)).max(0.0).sqrt()
+            }
+            TextShape::Smooth => {
+                let index = (index + 0.5 - start).clamp(0.0, range);
+                (1.0 + (std::f32::consts::PI + std::f32::consts::TAU * index / range).cos()) / 2.0
+            }
+        }
+    }
+}
+
+/// Unit of each char of `value` by line, together with the total count of
+/// units. Spaces are not part of any unit when based on characters excluding
+/// spaces.
+fn unit_indices(based_on: TextBased, value: &[Vec<char>]) -> (Vec<Vec<Option<usize>>>, usize) {
+    let mut count = 0;
+    let mut result = Vec::with_capacity(value.len());
+    for (line_index, chars) in value.iter().enumerate() {
+        let mut after_space = true;
+        let mut line = Vec::with_capacity(chars.len());
+        for ch in chars {
+            let space = ch.is_whitespace();
+            line.push(match based_on {
+                TextBased::Characters => {
+                    count += 1;
+                    Some(count - 1)
+                }
+                TextBased::CharactersExcludingSpaces if space => None,
+                TextBased::CharactersExcludingSpaces => {
+                    count += 1;
+                    Some(count - 1)
+                }
+                TextBased::Words => {
+                    if !space && after_space {
+                        count += 1;
+                    }
+                    after_space = space;
+                    Some(count.max(1) - 1)
+                }
+                TextBased::Lines => {
+                    count = value.len();
+                    Some(line_index)
+                }
+            });
+        }
+        result.push(line);
+    }
+    (result, count)
+}
+
+/// A deterministic permutation of `0..len`
+fn shuffled(len: usize, seed: usize) -> Vec<usize> {
+    let mut result = (0..len).collect::<Vec<_>>();
+    // xorshift64*, seeded per selector
+    let mut state = (seed as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    for i in (1..len).rev() {
+        state ^= state >> 12;
+        state ^= state << 25;
+        state ^= state >> 27;
+        let random = state.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        result.swap(i, (random % (i as u64 + 1)) as usize);
+    }
+    result
+}
@@ -0,0 +1,286 @@
+use crate::model::*;
+
+/// Samples per bezier segment when flattening a mask path
+const FLATTEN_STEPS: usize = 32;
+/// Upper bound of the frames glyph placement is sampled at, past which
+/// samples are spread more than a frame apart
+const MAX_SAMPLES: usize = 1024;
+
+/// Layout of text along a layer mask path
+pub(crate) struct TextPath {
+    /// The path measured at each frame glyph placement is sampled at: every
+    /// frame between the first and last keyframe of the path and margins, a
+    /// single frame if nothing is animated
+    samples: Vec<PathSample>,
+}
+
+/// The path, its margins and flags at a frame
+struct PathSample {
+    frame: f32,
+    measure: Option<PathMeasure>,
+    first_margin: f32,
+    last_margin: f32,
+    force_alignment: bool,
+    perpendicular: bool,
+}
+
+/// A glyph to be placed along the path
+pub(crate) struct PathGlyph {
+    /// Left edge of the glyph relative to the start of its line
+    pub x: f32,
+    pub width: f32,
+    /// Distance of the baseline from the path, positive goes below it
+    pub y: f32,
+    /// Index of the glyph in its line, and char count of that line
+    pub index: usize,
+    pub count: usize,
+    /// Width of the line without trailing spaces
+    pub line_width: f32,
+}
+
+impl TextPath {
+    pub(crate) fn new(follow: &TextFollowPath, masks: &[Mask]) -> Option<Self> {
+        let mask = masks.get(follow.mask?)?;
+        let mut frames = [
+            &follow.first_margin,
+            &follow.last_margin,
+            &follow.reversed,
+            &follow.force_alignment,
+            &follow.perpendicular,
+        ]
+        .into_iter()
+        .flatten()
+        .filter(|margin| margin.is_animated())
+        .flat_map(|margin| margin.keyframes())
+        .collect::<Vec<_>>();
+        if mask.points.is_animated() {
+            frames.extend(mask.points.keyframes());
+        }
+        frames.sort_by(f32::total_cmp);
+        frames.dedup();
+        // Glyphs move along the curve and with the margin easing between
+        // keyframes, so keyframe times alone would cut across the path
+        if let (Some(&start), Some(&end)) = (frames.first(), frames.last()) {
+            let step = ((end - start) / MAX_SAMPLES as f32).max(1.0);
+            let steps = ((end - start) / step).ceil() as usize;
+            frames.extend((1..steps).map(|i| start + i as f32 * step));
+            frames.sort_by(f32::total_cmp);
+            frames.dedup();
+        }
+        if frames.is_empty() {
+            frames.push(0.0);
+        }
+        let margin = |m: &Option<Animated<f32>>, frame| m.as_ref().map_or(0.0, |m| m.value(frame));
+        let flag = |f: &Option<Animated<f32>>, frame| margin(f, frame) != 0.0;
+        let samples = frames
+            .into_iter()
+            .map(|frame| {
+                let mut beziers = mask.points.value(frame);
+                if flag(&follow.reversed, frame) {
+                    beziers.iter_mut().for_each(reverse);
+                }
+                PathSample {
+                    frame,
+                    measure: beziers.first().map(PathMeasure::new),
+                    first_margin: margin(&follow.first_margin, frame),
+                    last_margin: margin(&follow.last_margin, frame),
+                    force_alignment: flag(&follow.force_alignment, frame),
+                    perpendicular: flag(&follow.perpendicular, frame),
+                }
+            })
+            .collect();
+        Some(TextPath { samples })
+    }
+
+    /// Transform placing `glyph` on the path, centered horizontally on the
+    /// path point and optionally rotated to the path tangent
+    pub(crate) fn transform(&self, glyph: &PathGlyph, justify: TextJustify) -> Transform {
+        let mut samples = self
+            .samples
+            .iter()
+            .map(|sample| (sample.frame, self.place(glyph, justify, sample)))
+            .collect::<Vec<_>>();
+        // Keep the rotation continuous where the tangent angle wraps around
+        for index in 1..samples.len() {
+            let previous = samples[index - 1].1 .1;
+            let rotation = &mut samples[index].1 .1;
+            *rotation -= ((*rotation - previous) / 360.0).round() * 360.0;
+        }
+        let keyframes = |value: &dyn Fn(&(Vector2D, f32)) -> f32| {
+            samples
+                .windows(2)
+                .map(|w| KeyFrame {
+                    start_value: value(&w[0].1),
+                    end_value: value(&w[1].1),
+                    start_frame: w[0].0,
+                    end_frame: w[1].0,
+                    easing_out: None,
+                    easing_in: None,
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut transform = Transform::default();
+        let (position, rotation) = if samples.len() > 1 {
+            let xs = keyframes(&|(p, _)| p.x);
+            let ys = keyframes(&|(p, _)| p.y);
+            let position = xs
+                .into_iter()
+                .zip(ys)
+                .map(|(x, y)| {
+                    x.alter_value(
+                        Vector2D::new(x.start_value, y.start_value),
+                        Vector2D::new(x.end_value, y.end_value),
+                    )
+                })
+                .collect();
+            (
+                Animated {
+                    animated: true,
+                    expression: None,
+                    slot: None,
+                    keyframes: position,
+                },
+                Animated {
+                    animated: true,
+                    expression: None,
+                    slot: None,
+                    keyframes: keyframes(&|(_, r)| *r),
+                },
+            )
+        } else {
+            let (position, rotation) = samples[0].1;
+            (
+                Animated::from_value(position),
+                Animated::from_value(rotation),
+            )
+        };
+        transform.position = Some(position);
+        transform.rotation = rotation;
+        transform.anchor = Some(Animated::from_value(Vector2D::new(
+            glyph.width / 2.0,
+            -glyph.y,
+        )));
+        transform
+    }
+
+    /// Position and rotation of a glyph center on the path of `sample`
+    fn place(
+        &self,
+        glyph: &PathGlyph,
+        justify: TextJustify,
+        sample: &PathSample,
+    ) -> (Vector2D, f32) {
+        let measure = match &sample.measure {
+            Some(measure) => measure,
+            None => return (Vector2D::zero(), 0.0),
+        };
+        let first = sample.first_margin;
+        let available = measure.length - first - sample.last_margin;
+
+        let center = glyph.x + glyph.width / 2.0;
+        let distance = if sample.force_alignment && glyph.count > 1 {
+            let extra = (available - glyph.line_width) / (glyph.count - 1) as f32;
+            first + center + extra * glyph.index as f32
+        } else {
+            let factor = justify.align_factor(true);
+            first + (available - glyph.line_width) * factor + center
+        };
+        let (point, tangent) = measure.sample(distance);
+        let rotation = if sample.perpendicular {
+            tangent.y.atan2(tangent.x).to_degrees()
+        } else {
+            0.0
+        };
+        (point, rotation)
+    }
+}
+
+fn reverse(bezier: &mut Bezier) {
+    bezier.verticies.reverse();
+    let in_tangent = std::mem::take(&mut bezier.in_tangent);
+    bezier.in_tangent = bezier.out_tangent.iter().rev().cloned().collect();
+    bezier.out_tangent = in_tangent.into_iter().rev().collect();
+}
+
+/// A flattened path measured by arc length
+struct PathMeasure {
+    points: Vec<Vector2D>,
+    /// Arc length from the start of the path to each point
+    lengths: Vec<f32>,
+    length: f32,
+    closed: bool,
+}
+
+impl PathMeasure {
+    fn new(bezier: &Bezier) -> Self {
+        let count = bezier.verticies.len();
+        let segments = if bezier.closed {
+            count
+        } else {
+            count.saturating_sub(1)
+        };
+        let mut points = bezier
+            .verticies
+            .first()
+            .cloned()
+            .into_iter()
+            .collect::<Vec<_>>();
+        for index in 0..segments {
+            let next = (index + 1) % count;
+            let p0 = bezier.verticies[index];
+            let p1 = p0 + bezier.out_tangent.get(index).cloned().unwrap_or_default();
+            let p3 = bezier.verticies[next];
+            let p2 = p3 + bezier.in_tangent.get(next).cloned().unwrap_or_default();
+            for step in 1..=FLATTEN_STEPS {
+                let t = step as f32 / FLATTEN_STEPS as f32;
+                let mt = 1.0 - t;
+                points.push(
+                    p0 * (mt * mt * mt)
+                        + p1 * (3.0 * mt * mt * t)
+                        + p2 * (3.0 * mt * t * t)
+                        + p3 * (t * t * t),
+                );
+            }
+        }
+        let mut lengths = vec![0.0];
+        for pair in points.windows(2) {
+            let last = *lengths.last().unwrap_or(&0.0);
+            lengths.push(last + (pair[1] - pair[0]).length());
+        }
+        let length = *lengths.last().unwrap_or(&0.0);
+        PathMeasure {
+            points,
+            lengths,
+            length,
+            closed: bezier.closed,
+        }
+    }
+
+    /// Point and unit tangent at `distance` along the path. Closed paths wrap
+    /// around, open paths are extended along their end tangents
+    fn sample(&self, distance: f32) -> (Vector2D, Vector2D) {
+        if self.points.len() < 2 || self.length == 0.0 {
+            let point = self.points.first().cloned().unwrap_or_default();
+            return (point, Vector2D::new(1.0, 0.0));
+        }
+        let distance = if self.closed {
+            distance.rem_euclid(self.length)
+        } else {
+            distance
+        };
+        let index = match self.lengths.iter().position(|l| *l > distance) {
+            Some(0) => 1,
+            Some(index) => index,
+            None => self.points.len() - 1,
+        };
+        let (p0, p1) = (self.points[index - 1], self.points[index]);
+        let segment = self.lengths[index] - self.lengths[index - 1];
+        let tangent = (p1 - p0).try_normalize().unwrap_or(Vector2D::new(1.0, 0.0));
+        let t = if segment == 0.0 {
+            0.0
+        } else {
+            (distance - self.lengths[index - 1]) / segment
+        };
+        (p0 + (p1 - p0) * t, tangent)
+    }
+}
@@ -1,29 +1,51 @@
+use std::collections::HashMap;
 use std::io::Read;
+use std::sync::Arc;
 
+use crate::limits::LimitedResolver;
 use crate::model::Model;
+use crate::prelude::{Id, LayerPath, TextFit};
+pub use diagnostics::{analyze, Diagnostic, Feature, Severity};
+#[cfg(feature = "dotlottie")]
+pub use dotlottie::{DotLottie, DotLottieWriter, Manifest, ManifestAnimation, ManifestTheme};
 pub use error::Error;
-use font::FontDB;
+pub use font::{FontDB, FontRequest};
 pub use fontkit::tiny_skia_path;
 use fontkit::FontKit;
 pub use lerp::*;
+pub use limits::{Limit, LoadLimits};
 pub use renderer::*;
+pub use resolver::*;
+pub use tgs::{TgsProfile, TgsViolation, TgsViolationKind};
 use timeline::Timeline;
+pub use validation::{validate, Violation, ViolationKind};
 
+mod diagnostics;
+#[cfg(feature = "dotlottie")]
+mod dotlottie;
 mod error;
 mod font;
 mod layer;
 mod lerp;
+mod limits;
 mod model;
 mod renderer;
+mod resolver;
+mod tgs;
 mod timeline;
+mod validation;
+mod visit;
 
 pub mod prelude {
     pub use crate::layer::frame::*;
     pub use crate::layer::hierarchy::*;
+    pub use crate::layer::path::LayerPath;
     pub use crate::layer::shape::{
         AnyFill, AnyStroke, PathFactory, StyledShape, StyledShapeIterator, TrimInfo,
     };
     pub use crate::layer::staged::{RenderableContent, StagedLayer};
+    pub use crate::layer::text::TextFit;
+    pub use crate::layer::text_animator::{GlyphAnimation, GlyphStyle};
     pub use crate::model::*;
     pub use crate::timeline::{Id, TimelineAction};
 }
@@ -37,13 +59,43 @@ pub struct Lottie {
 
 impl Lottie {
     /// Initiate a new `Lottie` by providing a raw `Model`, a `FontKit` for font
-    /// management, and a root path.Root path will be used to resolve relative
-    /// paths of media files in this lottie model
-    pub fn new(model: Model, fontkit: FontKit, root_path: &str) -> Result<Self, Error> {
-        let mut fontdb = FontDB::new(fontkit);
-        fontdb.load_fonts_from_model(&model)?;
+    /// management, and an [AssetResolver]. The resolver provides the media
+    /// files and the fonts loaded from urls of this lottie model
+    pub fn new(
+        model: Model,
+        fontkit: FontKit,
+        resolver: &dyn AssetResolver,
+    ) -> Result<Self, Error> {
+        Lottie::with_fontdb(model, FontDB::new(fontkit), resolver)
+    }
+
+    /// Same as [Lottie::new], with a `FontDB` configured beforehand, e.g.
+    /// with its own fallback chain
+    pub fn with_fontdb(
+        model: Model,
+        fontdb: FontDB,
+        resolver: &dyn AssetResolver,
+    ) -> Result<Self, Error> {
+        Lottie::with_limits(model, fontdb, resolver, &LoadLimits::default())
+    }
+
+    /// Same as [Lottie::with_fontdb], rejecting animations and assets beyond
+    /// `limits` with [Error::LimitExceeded], e.g. for untrusted files. The
+    /// JSON size and keyframe count are checked by [LoadLimits::read_model]
+    pub fn with_limits(
+        model: Model,
+        mut fontdb: FontDB,
+        resolver: &dyn AssetResolver,
+        limits: &LoadLimits,
+    ) -> Result<Self, Error> {
+        limits.check(&model)?;
+        let resolver = LimitedResolver {
+            resolver,
+            max_bytes: limits.max_asset_bytes,
+        };
+        fontdb.load_fonts_from_model(&model, &resolver)?;
 
-        let timeline = Timeline::new(&model, &fontdb, root_path)?;
+        let timeline = Timeline::new(&model, &fontdb, &resolver)?;
         Ok(Lottie {
             model,
             timeline,
@@ -52,15 +104,61 @@ impl Lottie {
         })
     }
 
+    /// Same as [Lottie::with_limits], fetching the assets of `model`
+    /// concurrently through `resolver` before building the timeline, instead
+    /// of one by one while staging layers
     #[cfg(not(all(target_os = "unknown", target_arch = "wasm32")))]
+    pub async fn load_async(
+        model: Model,
+        fontdb: FontDB,
+        resolver: Arc<dyn AssetResolver>,
+        limits: &LoadLimits,
+    ) -> Result<Self, Error> {
+        limits.check(&model)?;
+        let max_bytes = limits.max_asset_bytes;
+        let tasks = resolver::asset_references(&model)
+            .into_iter()
+            .map(|(path, kind)| {
+                let resolver = resolver.clone();
+                blocking::unblock(move || {
+                    let resolver = LimitedResolver {
+                        resolver: &*resolver,
+                        max_bytes,
+                    };
+                    let data = resolver.resolve(&path, kind);
+                    (path, data)
+                })
+            })
+            .collect::<Vec<_>>();
+        // tasks run on a thread pool from their creation, they are awaited in
+        // order only to collect their results
+        let mut assets = MemoryResolver::new();
+        for task in tasks {
+            let (path, data) = task.await;
+            assets.insert(path, data?);
+        }
+        Lottie::with_limits(model, fontdb, &assets, limits)
+    }
+
+    /// Parse a lottie JSON, or a gzip compressed one such as a TGS sticker,
+    /// and load it with the fonts installed on the system. Assets are
+    /// resolved by [default_resolver] from `root_path`
     pub fn from_reader<R: Read>(r: R, root_path: &str) -> Result<Self, Error> {
-        let fontkit = FontKit::new();
-        let path = dirs::font_dir().unwrap();
-        fontkit.search_fonts_from_path(path)?;
-        #[cfg(target_os = "macos")]
-        fontkit.search_fonts_from_path(std::path::PathBuf::from("/System/Library/Fonts"))?;
-        let model = Model::from_reader(r)?;
-        Ok(Lottie::new(model, fontkit, root_path)?)
+        let mut fontdb = FontDB::default();
+        fontdb.set_system_fonts(true);
+        Lottie::from_reader_with_fontdb(r, fontdb, &*default_resolver(root_path))
+    }
+
+    /// Same as [Lottie::from_reader], with a `FontDB` configured beforehand,
+    /// e.g. with fonts registered from memory and no system font scanning,
+    /// and its own [AssetResolver]
+    pub fn from_reader_with_fontdb<R: Read>(
+        r: R,
+        fontdb: FontDB,
+        resolver: &dyn AssetResolver,
+    ) -> Result<Self, Error> {
+        let model = LoadLimits::default().read_model(r)?;
+        Lottie::with_fontdb(model, fontdb, resolver)
     }
 
     pub fn timeline(&self) -> &Timeline {
@@ -70,4 +168,51 @@ impl Lottie {
     pub fn fontdb(&self) -> &FontDB {
         &self.fontdb
     }
+
+    /// Unsupported or partially supported features of this animation, see
+    /// [analyze], and text layers with chars missing from the fonts of the
+    /// font database
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = analyze(&self.model);
+        diagnostics.extend(diagnostics::missing_glyphs(&self.model, &self.fontdb));
+        diagnostics
+    }
+
+    /// Show or hide layers addressed by `path` at runtime, returns ids of the
+    /// affected staged layers so that renderers could update them
+    pub fn set_layer_visibility(&mut self, path: &LayerPath, visible: bool) -> Vec<Id> {
+        self.timeline.set_visibility(path, visible)
+    }
+
+    /// Replace the text of the text layers addressed by `path` at runtime,
+    /// e.g. with a user name or a price, and lay them out again. Line breaks
+    /// may be written `\n`. Returns ids of the affected staged layers so that
+    /// renderers could update them
+    pub fn set_text(
+        &mut self,
+        path: &LayerPath,
+        text: &str,
+        fit: TextFit,
+    ) -> Result<Vec<Id>, Error> {
+        self.timeline
+            .set_text(path, text, fit, &self.model, &self.fontdb)
+    }
+
+    /// Replace texts from a table of layer paths to strings, e.g. the strings
+    /// of a locale, see [Lottie::set_text]
+    pub fn set_texts(
+        &mut self,
+        table: &HashMap<String, String>,
+        fit: TextFit,
+    ) -> Result<Vec<Id>, Error> {
+        let mut ids = vec![];
+        for (path, text) in table {
+            let found = self.set_text(&LayerPath::from(path.as_str()), text, fit)?;
+            if found.is_empty() {
+                log::warn!("no text layer found at {}", path);
+            }
+            ids.extend(found);
+        }
+        Ok(ids)
+    }
 }
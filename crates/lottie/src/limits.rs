@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{BufRead, BufReader, Read};
+
+use flate2::bufread::GzDecoder;
+
+use crate::model::{self, Asset, Layer, LayerContent, Model};
+use crate::resolver::{AssetKind, AssetResolver};
+use crate::Error;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// A bound of [LoadLimits]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Limit {
+    JsonBytes,
+    Layers,
+    Depth,
+    Keyframes,
+    AssetBytes,
+    Dimension,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Limit::JsonBytes => "JSON size",
+            Limit::Layers => "layer count",
+            Limit::Depth => "nesting depth",
+            Limit::Keyframes => "keyframe count",
+            Limit::AssetBytes => "asset size",
+            Limit::Dimension => "dimension",
+        })
+    }
+}
+
+/// Bounds on the resources an animation may use, so that untrusted files
+/// fail to load with [Error::LimitExceeded] instead of exhausting memory or
+/// time
+#[derive(Debug, Clone)]
+pub struct LoadLimits {
+    /// Size of the JSON document, in bytes
+    pub max_json_bytes: usize,
+    /// Layers staged from the animation, each use of a precomposition
+    /// counting its layers again
+    pub max_layers: usize,
+    /// Nesting depth of precompositions
+    pub max_depth: usize,
+    /// Keyframes of all animated properties
+    pub max_keyframes: usize,
+    /// Size of each media or font file, in bytes
+    pub max_asset_bytes: usize,
+    /// Width and height of the animation, its precompositions, solid layers
+    /// and media
+    pub max_dimension: u32,
+}
+
+impl Default for LoadLimits {
+    fn default() -> Self {
+        LoadLimits {
+            max_json_bytes: 64 * 1_024 * 1_024,
+            max_layers: 10_000,
+            max_depth: 32,
+            max_keyframes: 1_000_000,
+            max_asset_bytes: 32 * 1_024 * 1_024,
+            max_dimension: 16_384,
+        }
+    }
+}
+
+impl LoadLimits {
+    /// Parse a lottie JSON, checking its size and keyframe count before
+    /// building the model, then the other limits. Gzip compressed JSON, e.g.
+    /// a TGS sticker, is decompressed transparently, its size is checked
+    /// once decompressed
+    pub fn read_model<R: Read>(&self, r: R) -> Result<Model, Error> {
+        let mut r = BufReader::new(r);
+        let limit = self.max_json_bytes as u64 + 1;
+        let mut bytes = vec![];
+        if r.fill_buf()?.starts_with(&GZIP_MAGIC) {
+            GzDecoder::new(r).take(limit).read_to_end(&mut bytes)?;
+        } else {
+            r.take(limit).read_to_end(&mut bytes)?;
+        }
+        exceeds(Limit::JsonBytes, bytes.len(), self.max_json_bytes)?;
+        let value = serde_json::from_slice(&bytes).map_err(model::Error::from)?;
+        self.model_from_json(value, Some(&bytes))
+    }
+
+    /// Build the model of the parsed lottie JSON `value`, checking its
+    /// keyframe count first, then the other limits. `bytes` locate failures
+    /// as in [Model::from_json]
+    pub(crate) fn model_from_json(
+        &self,
+        value: serde_json::Value,
+        bytes: Option<&[u8]>,
+    ) -> Result<Model, Error> {
+        exceeds(Limit::Keyframes, keyframe_count(&value), self.max_keyframes)?;
+        let model = Model::from_json(value, bytes)?;
+        self.check(&model)?;
+        Ok(model)
+    }
+
+    /// Check the layer count, the nesting depth, the dimensions and the
+    /// parenting of `model`
+    pub fn check(&self, model: &Model) -> Result<(), Error> {
+        exceeds(
+            Limit::Dimension,
+            model.width as usize,
+            self.max_dimension as usize,
+        )?;
+        exceeds(
+            Limit::Dimension,
+            model.height as usize,
+            self.max_dimension as usize,
+        )?;
+        for asset in &model.assets {
+            match asset {
+                Asset::Media(media) => {
+                    let size = media.width.unwrap_or(0).max(media.height.unwrap_or(0));
+                    exceeds(Limit::Dimension, size as usize, self.max_dimension as usize)?;
+                }
+                Asset::Precomposition(precomp) => check_parents(&precomp.layers)?,
+                Asset::Sound => {}
+            }
+        }
+        check_parents(&model.layers)?;
+        let mut count = 0;
+        self.check_layers(model, &model.layers, 0, &mut count)
+    }
+
+    fn check_layers(
+        &self,
+        model: &Model,
+        layers: &[Layer],
+        depth: usize,
+        count: &mut usize,
+    ) -> Result<(), Error> {
+        exceeds(Limit::Depth, depth, self.max_depth)?;
+        for layer in layers {
+            *count += 1;
+            exceeds(Limit::Layers, *count, self.max_layers)?;
+            let size = match &layer.content {
+                LayerContent::PreCompositionRef(r) => r.width.max(r.height) as f32,
+                LayerContent::SolidColor { width, height, .. } => width.max(*height),
+                _ => 0.0,
+            };
+            if size.is_nan() || size > self.max_dimension as f32 {
+                return Err(Error::LimitExceeded {
+                    limit: Limit::Dimension,
+                    max: self.max_dimension as usize,
+                });
+            }
+            if let LayerContent::PreCompositionRef(r) = &layer.content {
+                let precomp = model.assets.iter().find_map(|asset| match asset {
+                    Asset::Precomposition(precomp) if precomp.id == r.ref_id => Some(precomp),
+                    _ => None,
+                });
+                if let Some(precomp) = precomp {
+                    self.check_layers(model, &precomp.layers, depth + 1, count)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn exceeds(limit: Limit, value: usize, max: usize) -> Result<(), Error> {
+    if value > max {
+        Err(Error::LimitExceeded { limit, max })
+    } else {
+        Ok(())
+    }
+}
+
+/// Keyframes of the animated properties of a lottie JSON, i.e. the elements
+/// of `k` arrays made of keyframe objects
+fn keyframe_count(value: &serde_json::Value) -> usize {
+    let mut count = 0;
+    let mut stack = vec![value];
+    while let Some(value) = stack.pop() {
+        match value {
+            serde_json::Value::Object(object) => {
+                if let Some(serde_json::Value::Array(keyframes)) = object.get("k") {
+                    count += keyframes
+                        .iter()
+                        .filter(|keyframe| {
+                            keyframe
+                                .as_object()
+                                .map(|keyframe| {
+                                    keyframe.contains_key("t") || keyframe.contains_key("s")
+                                })
+                                .unwrap_or(false)
+                        })
+                        .count();
+                }
+                stack.extend(object.values());
+            }
+            serde_json::Value::Array(values) => stack.extend(values),
+            _ => {}
+        }
+    }
+    count
+}
+
+/// Parenting of the layers of a composition must not loop, nor refer to an
+/// `ind` shared by several layers
+fn check_parents(layers: &[Layer]) -> Result<(), Error> {
+    let mut parents = HashMap::new();
+    for layer in layers {
+        if let Some(index) = layer.index {
+            let duplicate = parents.insert(index, layer.parent_index).is_some();
+            if duplicate && layers.iter().any(|l| l.parent_index == Some(index)) {
+                return Err(Error::AmbiguousParent(index));
+            }
+        }
+    }
+    for layer in layers {
+        let mut parent = layer.parent_index;
+        let mut steps = 0;
+        while let Some(index) = parent {
+            steps += 1;
+            if steps > layers.len() {
+                return Err(Error::ParentCycle(index));
+            }
+            parent = parents.get(&index).copied().flatten();
+        }
+    }
+    Ok(())
+}
+
+/// Rejects assets of `resolver` larger than `max_bytes`, for resolvers that
+/// do not bound them themselves
+pub(crate) struct LimitedResolver<'a> {
+    pub resolver: &'a dyn AssetResolver,
+    pub max_bytes: usize,
+}
+
+impl AssetResolver for LimitedResolver<'_> {
+    fn resolve(&self, path: &str, kind: AssetKind) -> Result<Vec<u8>, Error> {
+        let data = self.resolver.resolve(path, kind)?;
+        exceeds(Limit::AssetBytes, data.len(), self.max_bytes)?;
+        Ok(data)
+    }
+}
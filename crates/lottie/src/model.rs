@@ -1,17 +1,22 @@
+use std::sync::{Arc, Mutex};
+
 pub use euclid::default::Rect;
 pub use euclid::rect;
 use glam::{Mat4, Vec3};
 use serde::{Deserialize, Serialize};
-pub use serde_json::Error;
 pub type Vector2D = euclid::default::Vector2D<f32>;
 
 mod animated;
 mod color;
+mod error;
 mod helpers;
+mod migrate;
 
 pub use animated::*;
 pub use color::*;
+pub use error::Error;
 use helpers::*;
+pub use migrate::Version;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Model {
@@ -34,11 +39,59 @@ pub struct Model {
     pub assets: Vec<Asset>,
     #[serde(default)]
     pub fonts: FontList,
+    /// Glyph outlines exported along with the animation
+    #[serde(default)]
+    pub chars: Vec<Character>,
 }
 
 impl Model {
-    pub fn from_reader<R: std::io::Read>(r: R) -> Result<Self, serde_json::Error> {
-        serde_json::from_reader(r)
+    /// Parse a lottie JSON, failures are located by their JSON path and
+    /// position, see [Error]. Structures of old exports are migrated to the
+    /// current format according to the version `v`, see [Model::version].
+    /// Failures in documents rewritten by migrations are located by their
+    /// JSON path only, as the rewritten values carry no position
+    pub fn from_reader<R: std::io::Read>(mut r: R) -> Result<Self, Error> {
+        let mut bytes = vec![];
+        r.read_to_end(&mut bytes).map_err(serde_json::Error::io)?;
+        // only old exports go through a `Value` to be migrated, the version is
+        // read without building one
+        let version = serde_json::from_slice::<ExportVersion>(&bytes)
+            .ok()
+            .and_then(|header| Version::parse(header.version?.as_str()?));
+        if version.is_some_and(migrate::outdated) {
+            let value = serde_json::from_slice(&bytes)?;
+            return Model::from_json(value, Some(&bytes));
+        }
+        Model::from_slice(&bytes)
+    }
+
+    /// Build a model from the parsed lottie JSON `value`. `bytes` are the
+    /// document `value` was parsed from as is, if any, failures are located
+    /// by their JSON path only without them
+    pub(crate) fn from_json(
+        mut value: serde_json::Value,
+        bytes: Option<&[u8]>,
+    ) -> Result<Self, Error> {
+        let migrated = migrate::migrate(&mut value);
+        match (serde_path_to_error::deserialize(value), bytes) {
+            (Ok(model), _) => Ok(model),
+            // values carry no position, the failure is located again in the
+            // document itself
+            (Err(_), Some(bytes)) if !migrated => Model::from_slice(bytes),
+            (Err(e), _) => Err(e.into()),
+        }
+    }
+
+    fn from_slice(bytes: &[u8]) -> Result<Self, Error> {
+        let mut d = serde_json::Deserializer::from_slice(bytes);
+        let model = serde_path_to_error::deserialize(&mut d)?;
+        d.end()?;
+        Ok(model)
+    }
+
+    /// Version of the exporter that wrote the animation, `v`, if any
+    pub fn version(&self) -> Option<Version> {
+        self.version.as_deref().and_then(Version::parse)
     }
 
     pub fn duration(&self) -> f32 {
@@ -48,6 +101,22 @@ impl Model {
     pub fn font(&self, name: &str) -> Option<&Font> {
         self.fonts.list.iter().find(|f| f.name == name)
     }
+
+    /// Embedded glyph of `c` in the family and style of `font`
+    pub fn character(&self, font: &Font, c: char) -> Option<&Character> {
+        let mut buffer = [0; 4];
+        let c = &*c.encode_utf8(&mut buffer);
+        self.chars
+            .iter()
+            .find(|ch| ch.ch == c && ch.family == font.family && ch.style == font.style)
+    }
+}
+
+/// Version `v` of a lottie JSON, the rest of the document is skipped
+#[derive(Deserialize)]
+struct ExportVersion {
+    #[serde(rename = "v", default)]
+    version: Option<serde_json::Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -58,7 +127,7 @@ pub struct Layer {
         rename = "ddd",
         default
     )]
-    is_3d: bool,
+    pub(crate) is_3d: bool,
     #[serde(rename = "hd", default)]
     pub hidden: bool,
     #[serde(rename = "ind", default)]
@@ -94,6 +163,8 @@ pub struct Layer {
     pub has_mask: bool,
     #[serde(default, rename = "masksProperties")]
     pub masks_properties: Vec<Mask>,
+    #[serde(rename = "ef", default)]
+    pub effects: Vec<Effect>,
 }
 
 impl Layer {
@@ -123,6 +194,7 @@ impl Layer {
             blend_mode: None,
             has_mask: false,
             masks_properties: vec![],
+            effects: vec![],
         }
     }
 }
@@ -140,6 +212,9 @@ pub enum LayerContent {
     Shape(ShapeGroup),
     Text(TextAnimationData),
     Media(Media),
+    /// Layer of a type that is not rendered, e.g. a camera, staged as an
+    /// empty layer
+    Unsupported(u64),
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -153,9 +228,9 @@ pub struct PreCompositionRef {
     #[serde(rename = "refId")]
     pub ref_id: String,
     #[serde(rename = "w")]
-    width: u32,
+    pub width: u32,
     #[serde(rename = "h")]
-    height: u32,
+    pub height: u32,
     #[serde(rename = "tm")]
     pub time_remapping: Option<Animated<f32>>,
 }
@@ -212,8 +287,18 @@ impl Transform {
             .as_ref()
             .and_then(|a| Some(a.keyframes.last()?.end_frame))
             .unwrap_or(0.0);
-        let scale_frames = self.scale.keyframes.last().unwrap().end_frame;
-        let rotation_frames = self.rotation.keyframes.last().unwrap().end_frame;
+        let scale_frames = self
+            .scale
+            .keyframes
+            .last()
+            .map(|k| k.end_frame)
+            .unwrap_or(0.0);
+        let rotation_frames = self
+            .rotation
+            .keyframes
+            .last()
+            .map(|k| k.end_frame)
+            .unwrap_or(0.0);
         anchor_frames
             .max(pos_frames)
             .max(scale_frames)
@@ -307,23 +392,46 @@ pub struct FontList {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Font {
     #[serde(default)]
-    ascent: Option<f32>,
+    pub ascent: Option<f32>,
     #[serde(rename = "fFamily")]
     pub family: String,
     #[serde(rename = "fName")]
     pub name: String,
     #[serde(rename = "fStyle")]
-    style: String,
+    pub style: String,
     #[serde(rename = "fPath", default)]
     pub path: Option<String>,
     #[serde(rename = "fWeight")]
-    weight: Option<String>,
+    pub weight: Option<String>,
     #[serde(default)]
     pub origin: FontPathOrigin,
     #[serde(rename = "fClass", default)]
     class: Option<String>,
 }
 
+/// Outline of a char, exported with the animation so that text renders
+/// without its font
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Character {
+    pub ch: String,
+    pub size: f32,
+    pub style: String,
+    /// Advance width for a font size of 100
+    #[serde(rename = "w")]
+    pub width: f32,
+    #[serde(rename = "fFamily")]
+    pub family: String,
+    #[serde(default)]
+    pub data: CharacterData,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct CharacterData {
+    /// Shapes of the glyph for a font size of 100, the baseline at y = 0
+    #[serde(default)]
+    pub shapes: Vec<ShapeLayer>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ShapeLayer {
     #[serde(rename = "nm", default)]
@@ -336,9 +444,44 @@ pub struct ShapeLayer {
 
 #[derive(Debug, Clone)]
 pub struct TextRangeInfo {
-    pub value: Vec<Vec<char>>,
+    /// Units of the text for each range, shared by all of its glyphs
+    pub units: Arc<Vec<TextUnits>>,
     pub index: (usize, usize), // line, char
     pub ranges: Vec<TextRange>,
+    /// Layer of a color font glyph, whose colors animators don't change
+    pub color_glyph: bool,
+    /// Tracking offsets of the text at the last animated frame, shared by
+    /// all of its glyphs
+    pub tracking: Arc<Mutex<TrackingOffsets>>,
+}
+
+/// Unit of each char of a text as counted by a range selector, in random
+/// order when the selector is randomized
+#[derive(Debug, Clone)]
+pub struct TextUnits {
+    /// Unit of each char by line, spaces being in none when based on
+    /// characters excluding spaces
+    pub indices: Vec<Vec<Option<usize>>>,
+    pub total: usize,
+}
+
+/// Horizontal shift of each char of a text caused by the tracking of the
+/// preceding chars of its line, by line
+#[derive(Debug, Default)]
+pub struct TrackingOffsets {
+    pub(crate) frame: Option<f32>,
+    pub(crate) offsets: Vec<Vec<f32>>,
+}
+
+/// Bitmap or SVG glyph of a color font
+#[derive(Debug, Clone)]
+pub struct GlyphImage {
+    /// Encoded image, PNG for bitmap glyphs or an SVG document in font units
+    /// with the baseline at `y = 0`
+    pub content: Vec<u8>,
+    /// Top left corner of the image relative to the glyph origin
+    pub origin: Vector2D,
+    pub size: Vector2D,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -357,6 +500,12 @@ pub enum Shape {
         #[serde(skip)]
         text_range: Option<TextRangeInfo>,
     },
+    /// Bitmap or SVG glyph, only produced when staging text layers
+    #[serde(skip)]
+    Image {
+        image: GlyphImage,
+        text_range: Option<TextRangeInfo>,
+    },
     #[serde(rename = "fl")]
     Fill(Fill),
     #[serde(rename = "st")]
@@ -498,7 +647,9 @@ pub enum Composite {
     Below = 2,
 }
 
-#[derive(serde_repr::Serialize_repr, serde_repr::Deserialize_repr, Debug, Clone, Copy)]
+#[derive(
+    serde_repr::Serialize_repr, serde_repr::Deserialize_repr, Debug, Clone, Copy, PartialEq,
+)]
 #[repr(u8)]
 pub enum TrimMultipleShape {
     Individually = 1,
@@ -562,7 +713,34 @@ impl Default for TextJustify {
     }
 }
 
-#[derive(serde_repr::Serialize_repr, serde_repr::Deserialize_repr, Debug, Clone, Copy)]
+impl TextJustify {
+    /// Where a line is placed in the available width, `0.0` for left, `0.5`
+    /// for center and `1.0` for right
+    pub fn align_factor(&self, last_line: bool) -> f32 {
+        if self.is_full(last_line) {
+            return 0.0;
+        }
+        match self {
+            TextJustify::Left | TextJustify::LastLineLeft | TextJustify::LastLineFull => 0.0,
+            TextJustify::Center | TextJustify::LastLineCenter => 0.5,
+            TextJustify::Right | TextJustify::LastLineRight => 1.0,
+        }
+    }
+
+    /// Whether a line is stretched to fill the available width. Last lines of
+    /// paragraphs are only stretched by [TextJustify::LastLineFull]
+    pub fn is_full(&self, last_line: bool) -> bool {
+        match self {
+            TextJustify::Left | TextJustify::Right | TextJustify::Center => false,
+            TextJustify::LastLineFull => true,
+            _ => !last_line,
+        }
+    }
+}
+
+#[derive(
+    serde_repr::Serialize_repr, serde_repr::Deserialize_repr, Debug, Clone, Copy, PartialEq,
+)]
 #[repr(u8)]
 pub enum TextCaps {
     Regular = 0,
@@ -585,6 +763,29 @@ pub enum TextBased {
     Lines = 4,
 }
 
+/// Which glyphs share the anchor point animators transform around
+#[derive(serde_repr::Serialize_repr, Debug, Clone, Copy)]
+#[repr(u8)]
+pub enum TextGrouping {
+    Characters = 1,
+    Words = 2,
+    Lines = 3,
+    All = 4,
+}
+
+impl Default for TextGrouping {
+    fn default() -> Self {
+        TextGrouping::Characters
+    }
+}
+
+#[derive(serde_repr::Serialize_repr, serde_repr::Deserialize_repr, Debug, Clone, Copy)]
+#[repr(u8)]
+pub enum TextRangeUnits {
+    Percentage = 1,
+    Index = 2,
+}
+
 #[derive(serde_repr::Serialize_repr, serde_repr::Deserialize_repr, Debug, Clone, Copy)]
 #[repr(u8)]
 pub enum TextShape {
@@ -660,10 +861,14 @@ impl Fill {
         Fill {
             opacity: Animated {
                 animated: false,
+                expression: None,
+                slot: None,
                 keyframes: vec![KeyFrame::from_value(0.0)],
             },
             color: Animated {
                 animated: false,
+                expression: None,
+                slot: None,
                 keyframes: vec![KeyFrame::from_value(Rgb::new_u8(0, 0, 0))],
             },
             fill_rule: FillRule::NonZero,
@@ -676,10 +881,14 @@ impl From<Rgba> for Fill {
         Fill {
             opacity: Animated {
                 animated: false,
+                expression: None,
+                slot: None,
                 keyframes: vec![KeyFrame::from_value(color.a as f32 / 255.0)],
             },
             color: Animated {
                 animated: false,
+                expression: None,
+                slot: None,
                 keyframes: vec![KeyFrame::from_value(Rgb::new_u8(color.r, color.g, color.b))],
             },
             fill_rule: FillRule::NonZero,
@@ -700,18 +909,41 @@ pub struct Stroke {
     #[serde(rename = "w")]
     pub width: Animated<f32>,
     #[serde(rename = "d", default)]
-    dashes: Vec<StrokeDash>,
+    pub(crate) dashes: Vec<StrokeDash>,
     #[serde(rename = "c")]
     pub color: Animated<Rgb>,
 }
 
+impl Stroke {
+    pub fn new(color: Animated<Rgb>, opacity: Animated<f32>, width: Animated<f32>) -> Stroke {
+        Stroke {
+            line_cap: LineCap::Butt,
+            line_join: LineJoin::Miter,
+            miter_limit: 4.0,
+            opacity,
+            width,
+            dashes: vec![],
+            color,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
-#[serde(from = "ColorListHelper", into = "ColorListHelper")]
+#[serde(try_from = "ColorListHelper", into = "ColorListHelper")]
 pub struct ColorList {
     color_count: usize,
     pub colors: Animated<Vec<GradientColor>>,
 }
 
+impl ColorList {
+    pub fn new(colors: Vec<GradientColor>) -> ColorList {
+        ColorList {
+            color_count: colors.len(),
+            colors: Animated::from_value(colors),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct GradientColor {
     pub offset: f32,
@@ -753,7 +985,7 @@ pub struct GradientStroke {
     #[serde(rename = "w")]
     pub width: Animated<f32>,
     #[serde(rename = "d", default)]
-    dashes: Vec<StrokeDash>,
+    pub(crate) dashes: Vec<StrokeDash>,
     #[serde(flatten)]
     pub gradient: Gradient,
 }
@@ -802,7 +1034,7 @@ pub struct PolyStar {
     pub star_type: PolyStarType,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Debug, Clone)]
 #[serde(untagged)]
 pub enum Asset {
     Media(Media),
@@ -811,11 +1043,12 @@ pub enum Asset {
 }
 
 impl Asset {
-    pub fn id(&self) -> &str {
+    /// Id layers reference the asset by, sounds have none
+    pub fn id(&self) -> Option<&str> {
         match self {
-            Asset::Media(i) => i.id.as_str(),
-            Asset::Precomposition(p) => p.id.as_str(),
-            _ => todo!(),
+            Asset::Media(i) => Some(i.id.as_str()),
+            Asset::Precomposition(p) => Some(p.id.as_str()),
+            Asset::Sound => None,
         }
     }
 }
@@ -842,6 +1075,26 @@ pub struct Media {
     pub height: Option<u32>,
 }
 
+impl Media {
+    /// Location of the file, the directory `u` joined with the file name `p`.
+    /// Embedded files are data urls
+    pub fn path(&self) -> String {
+        if self.pwd.is_empty() || self.filename.starts_with("data:") {
+            return self.filename.clone();
+        }
+        match url::Url::parse(&self.pwd) {
+            Ok(url) => url
+                .join(&self.filename)
+                .map(String::from)
+                .unwrap_or_else(|_| self.filename.clone()),
+            Err(_) => std::path::Path::new(&self.pwd)
+                .join(&self.filename)
+                .to_string_lossy()
+                .into_owned(),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Precomposition {
     pub id: String,
@@ -881,92 +1134,100 @@ pub struct Bezier {
     pub out_tangent: Vec<Vector2D>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TextAnimationData {
     #[serde(rename = "a")]
     pub ranges: Vec<TextRange>,
     #[serde(rename = "d")]
     pub document: TextData,
-    #[serde(rename = "m")]
-    options: TextAlignmentOptions,
-    #[serde(rename = "p")]
-    follow_path: TextFollowPath,
+    #[serde(rename = "m", default)]
+    pub options: TextAlignmentOptions,
+    #[serde(rename = "p", default)]
+    pub follow_path: TextFollowPath,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TextStyle {
     #[serde(rename = "sw", default)]
-    stroke_width: Option<Animated<f32>>,
+    pub stroke_width: Option<Animated<f32>>,
     #[serde(rename = "sc", default)]
-    stroke_color: Option<Animated<Rgb>>,
+    pub stroke_color: Option<Animated<Rgb>>,
     #[serde(rename = "sh", default)]
-    stroke_hue: Option<Animated<f32>>,
+    pub stroke_hue: Option<Animated<f32>>,
     #[serde(rename = "ss", default)]
-    stroke_saturation: Option<Animated<f32>>,
+    pub stroke_saturation: Option<Animated<f32>>,
     #[serde(rename = "sb", default)]
-    stroke_brightness: Option<Animated<f32>>,
+    pub stroke_brightness: Option<Animated<f32>>,
     #[serde(rename = "so", default)]
-    stroke_opacity: Option<Animated<f32>>,
+    pub stroke_opacity: Option<Animated<f32>>,
     #[serde(rename = "fc", default)]
-    fill_color: Option<Animated<Rgb>>,
+    pub fill_color: Option<Animated<Rgb>>,
     #[serde(rename = "fh", default)]
-    fill_hue: Option<Animated<f32>>,
+    pub fill_hue: Option<Animated<f32>>,
     #[serde(rename = "fs", default)]
-    fill_saturation: Option<Animated<f32>>,
+    pub fill_saturation: Option<Animated<f32>>,
     #[serde(rename = "fb", default)]
-    fill_brightness: Option<Animated<f32>>,
+    pub fill_brightness: Option<Animated<f32>>,
     #[serde(rename = "t", default)]
     pub letter_spacing: Option<Animated<f32>>,
     #[serde(rename = "bl", default)]
-    blur: Option<Animated<f32>>,
+    pub blur: Option<Animated<f32>>,
     #[serde(rename = "ls", default)]
     pub line_spacing: Option<Animated<f32>>,
     #[serde(flatten)]
-    transform: Option<Transform>,
+    pub transform: Option<Transform>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TextRange {
     #[serde(rename = "nm", default)]
-    name: Option<String>,
+    pub name: Option<String>,
     #[serde(rename = "a", default)]
     pub style: Option<TextStyle>,
     #[serde(rename = "s")]
     pub selector: TextRangeSelector,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TextRangeSelector {
-    #[serde(rename = "t", deserialize_with = "bool_from_int")]
-    expressible: bool,
+    #[serde(
+        rename = "t",
+        deserialize_with = "bool_from_int",
+        serialize_with = "int_from_bool"
+    )]
+    pub expressible: bool,
     #[serde(rename = "xe")]
-    max_ease: Animated<f32>,
+    pub max_ease: Animated<f32>,
     #[serde(rename = "ne")]
-    min_ease: Animated<f32>,
+    pub min_ease: Animated<f32>,
     #[serde(rename = "a")]
-    max_amount: Animated<f32>,
+    pub max_amount: Animated<f32>,
     #[serde(rename = "b")]
-    based_on: TextBased,
-    #[serde(rename = "rn", deserialize_with = "bool_from_int")]
-    randomize: bool,
+    pub based_on: TextBased,
+    #[serde(
+        rename = "rn",
+        deserialize_with = "bool_from_int",
+        serialize_with = "int_from_bool"
+    )]
+    pub randomize: bool,
     #[serde(rename = "sh")]
-    shape: TextShape,
+    pub shape: TextShape,
     #[serde(rename = "o", default)]
-    offset: Option<Animated<f32>>,
+    pub offset: Option<Animated<f32>>,
     #[serde(rename = "r")]
-    pub range_units: TextBased,
+    pub range_units: TextRangeUnits,
     #[serde(rename = "sm", default)]
-    selector_smoothness: Option<Animated<f32>>,
+    pub selector_smoothness: Option<Animated<f32>>,
     #[serde(rename = "s", default)]
     pub start: Option<Animated<f32>>,
     #[serde(rename = "e", default)]
     pub end: Option<Animated<f32>>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TextData {
     #[serde(rename = "x", default)]
-    expression: Option<String>,
+    pub(crate) expression: Option<String>,
     #[serde(
         deserialize_with = "keyframes_from_array",
         serialize_with = "array_from_keyframes",
@@ -975,11 +1236,35 @@ pub struct TextData {
     pub keyframes: Vec<KeyFrame<TextDocument>>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct TextAlignmentOptions {}
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TextAlignmentOptions {
+    /// Offset of the anchor point from the center of its group, in
+    /// percentage of half the group size
+    #[serde(rename = "a", default)]
+    pub alignment: Option<Animated<Vector2D>>,
+    #[serde(rename = "g", default)]
+    pub grouping: TextGrouping,
+}
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct TextFollowPath {}
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TextFollowPath {
+    /// Index of the layer mask whose path the text follows
+    #[serde(rename = "m", default)]
+    pub mask: Option<usize>,
+    #[serde(rename = "f", default)]
+    pub first_margin: Option<Animated<f32>>,
+    #[serde(rename = "l", default)]
+    pub last_margin: Option<Animated<f32>>,
+    /// Reverse the path direction, nonzero to enable
+    #[serde(rename = "r", deserialize_with = "animated_from_number", default)]
+    pub reversed: Option<Animated<f32>>,
+    /// Spread chars evenly between the margins, nonzero to enable
+    #[serde(rename = "a", deserialize_with = "animated_from_number", default)]
+    pub force_alignment: Option<Animated<f32>>,
+    /// Rotate chars to the path tangent, nonzero to enable
+    #[serde(rename = "p", deserialize_with = "animated_from_number", default)]
+    pub perpendicular: Option<Animated<f32>>,
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TextDocument {
@@ -1002,24 +1287,38 @@ pub struct TextDocument {
         serialize_with = "array_from_rgba",
         default
     )]
-    stroke_color: Rgba,
+    pub stroke_color: Rgba,
     #[serde(rename = "sw", default)]
-    stroke_width: f32,
+    pub stroke_width: f32,
     #[serde(rename = "of", default)]
-    stroke_above_fill: bool,
+    pub stroke_above_fill: bool,
+    /// Distance between baselines of consecutive lines
     #[serde(rename = "lh", default)]
-    line_height: Option<f32>,
+    pub line_height: Option<f32>,
+    /// Extra spacing between chars, in 1/1000 em
+    #[serde(rename = "tr", default)]
+    pub tracking: f32,
     #[serde(rename = "j", default)]
     pub justify: TextJustify,
     #[serde(rename = "ls", default)]
     pub baseline_shift: f32,
-    // TODO:
-    #[serde(default)]
-    sz: Vec<f32>,
-    #[serde(default)]
-    ps: Vec<f32>,
-    #[serde(default)]
-    ca: TextCaps,
+    #[serde(rename = "sz", default)]
+    pub box_size: Vec<f32>,
+    #[serde(rename = "ps", default)]
+    pub box_position: Vec<f32>,
+    #[serde(rename = "ca", default)]
+    pub caps: TextCaps,
+}
+
+impl TextDocument {
+    /// The paragraph text box, `None` for point text
+    pub fn text_box(&self) -> Option<Rect<f32>> {
+        match (self.box_size.as_slice(), self.box_position.as_slice()) {
+            ([width, height, ..], [x, y, ..]) => Some(rect(*x, *y, *width, *height)),
+            ([width, height, ..], _) => Some(rect(0.0, 0.0, *width, *height)),
+            _ => None,
+        }
+    }
 }
 
 impl Default for TextDocument {
@@ -1032,12 +1331,13 @@ impl Default for TextDocument {
             stroke_width: 0.0,
             stroke_above_fill: false,
             line_height: None,
+            tracking: 0.0,
             baseline_shift: 0.0,
             value: String::new(),
             justify: TextJustify::Left,
-            sz: vec![],
-            ps: vec![],
-            ca: TextCaps::Regular,
+            box_size: vec![],
+            box_position: vec![],
+            caps: TextCaps::Regular,
         }
     }
 }
@@ -1049,17 +1349,27 @@ pub struct Mask {
     #[serde(rename = "mn", default)]
     match_name: String,
     #[serde(rename = "inv", default)]
-    inverted: bool,
+    pub(crate) inverted: bool,
     #[serde(rename = "pt")]
     pub points: Animated<Vec<Bezier>>,
     #[serde(rename = "o")]
     pub opacity: Animated<f32>,
     pub mode: MaskMode,
     #[serde(rename = "e", default)]
-    expand: Option<Animated<f32>>,
+    pub(crate) expand: Option<Animated<f32>>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+/// Layer effect, e.g. a blur or a drop shadow. Effects are not rendered, only
+/// their name and type are kept
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Effect {
+    #[serde(rename = "nm", default)]
+    pub name: Option<String>,
+    #[serde(rename = "ty", default)]
+    pub ty: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 pub enum MaskMode {
     #[serde(rename = "n")]
     None,
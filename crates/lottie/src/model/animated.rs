@@ -17,10 +17,19 @@ pub struct Animated<T> {
         default
     )]
     pub animated: bool,
+    /// Expression computing the value, not evaluated
+    #[serde(rename = "x", default, skip_serializing_if = "Option::is_none")]
+    pub expression: Option<String>,
+    /// Slot bound to the property, whose value a dotLottie theme replaces
+    #[serde(rename = "sid", default, skip_serializing_if = "Option::is_none")]
+    pub slot: Option<String>,
     #[serde(
         deserialize_with = "keyframes_from_array",
         serialize_with = "array_from_keyframes",
-        bound = "T: FromTo<helpers::Value>",
+        bound(
+            serialize = "T: FromTo<helpers::Value> + Clone",
+            deserialize = "T: FromTo<helpers::Value>"
+        ),
         rename = "k"
     )]
     pub keyframes: Vec<KeyFrame<T>>,
@@ -30,6 +39,8 @@ impl<T: Clone + Lerp<Target = T>> Animated<T> {
     pub fn from_value(value: T) -> Self {
         Animated {
             animated: false,
+            expression: None,
+            slot: None,
             keyframes: vec![KeyFrame {
                 start_value: value.clone(),
                 end_value: value,
@@ -66,7 +77,11 @@ impl<T: Clone + Lerp<Target = T>> Animated<T> {
     }
 
     pub fn is_animated(&self) -> bool {
-        self.keyframes.len() > 1 || self.keyframes[0].easing_in.is_some()
+        self.keyframes.len() > 1
+            || self
+                .keyframes
+                .first()
+                .map_or(false, |keyframe| keyframe.easing_in.is_some())
     }
 
     pub fn align_to_sorted_frames(&mut self, mut frames: impl Iterator<Item = f32>) {
@@ -110,6 +125,8 @@ where
     fn default() -> Self {
         Self {
             animated: false,
+            expression: None,
+            slot: None,
             keyframes: vec![KeyFrame::default()],
         }
     }
@@ -1,3 +1,4 @@
+use std::fmt;
 use std::str::FromStr;
 
 use super::helpers::{FromTo, Value};
@@ -44,14 +45,19 @@ impl FromStr for Rgba {
         if s.starts_with("#") {
             chars.next();
         }
-        let (rgb, a) = read_color::rgb_maybe_a(&mut chars).unwrap();
+        let (rgb, a) = read_color::rgb_maybe_a(&mut chars).ok_or(())?;
         Ok(Rgba::new_u8(rgb[0], rgb[1], rgb[2], a.unwrap_or(255)))
     }
 }
 
-impl ToString for Rgba {
-    fn to_string(&self) -> String {
-        todo!()
+/// Formats as `#rrggbb`, with an alpha byte appended when not opaque
+impl fmt::Display for Rgba {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)?;
+        if self.a != 255 {
+            write!(f, "{:02x}", self.a)?;
+        }
+        Ok(())
     }
 }
 
@@ -74,21 +80,66 @@ impl Rgb {
     pub fn new_u8(r: u8, g: u8, b: u8) -> Rgb {
         Rgb { r, g, b }
     }
+
+    /// Hue in degrees, saturation and brightness in `0.0..=1.0`
+    pub fn to_hsb(&self) -> (f32, f32, f32) {
+        let (r, g, b) = (
+            self.r as f32 / 255.0,
+            self.g as f32 / 255.0,
+            self.b as f32 / 255.0,
+        );
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let delta = max - min;
+        let hue = if delta == 0.0 {
+            0.0
+        } else if max == r {
+            60.0 * ((g - b) / delta).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / delta + 2.0)
+        } else {
+            60.0 * ((r - g) / delta + 4.0)
+        };
+        let saturation = if max == 0.0 { 0.0 } else { delta / max };
+        (hue, saturation, max)
+    }
+
+    /// Inverse of [Rgb::to_hsb]. Hue wraps around, saturation and brightness
+    /// are clamped
+    pub fn from_hsb(hue: f32, saturation: f32, brightness: f32) -> Rgb {
+        let hue = hue.rem_euclid(360.0);
+        let saturation = saturation.clamp(0.0, 1.0);
+        let brightness = brightness.clamp(0.0, 1.0);
+        let c = brightness * saturation;
+        let x = c * (1.0 - ((hue / 60.0).rem_euclid(2.0) - 1.0).abs());
+        let m = brightness - c;
+        let (r, g, b) = match (hue / 60.0) as u32 {
+            0 => (c, x, 0.0),
+            1 => (x, c, 0.0),
+            2 => (0.0, c, x),
+            3 => (0.0, x, c),
+            4 => (x, 0.0, c),
+            _ => (c, 0.0, x),
+        };
+        let channel = |v: f32| ((v + m) * 255.0).round() as u8;
+        Rgb::new_u8(channel(r), channel(g), channel(b))
+    }
 }
 
 impl FromTo<Value> for Rgba {
-    fn from(v: Value) -> Self {
-        let v = v.as_f32_vec().unwrap();
-        if v[0] > 1.0 && v[0] <= 255.0 {
+    fn from(v: Value) -> Option<Self> {
+        let v = v.as_f32_vec()?;
+        let (r, g, b) = (*v.first()?, *v.get(1)?, *v.get(2)?);
+        Some(if r > 1.0 && r <= 255.0 {
             Rgba::new_u8(
-                v[0] as u8,
-                v[1] as u8,
-                v[2] as u8,
+                r as u8,
+                g as u8,
+                b as u8,
                 v.get(3).cloned().unwrap_or(255.0) as u8,
             )
         } else {
-            Rgba::new_f32(v[0], v[1], v[2], v.get(3).cloned().unwrap_or(1.0))
-        }
+            Rgba::new_f32(r, g, b, v.get(3).cloned().unwrap_or(1.0))
+        })
     }
 
     fn to(self) -> Value {
@@ -0,0 +1,131 @@
+use std::fmt;
+
+use serde_json::error::Category;
+
+/// Failure to parse a lottie JSON, located by the JSON path of the value that
+/// failed, e.g. `layers[3].shapes[1].it[2].ks.k[0].s`, and its position in
+/// the document
+#[derive(Debug)]
+pub struct Error {
+    message: String,
+    path: String,
+    line: usize,
+    column: usize,
+    inner: serde_json::Error,
+}
+
+impl Error {
+    /// What failed, without its location
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// JSON path of the value that failed, empty for the document itself
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// One-based line of the failure, 0 when unknown
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// One-based column of the failure, 0 when unknown
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    /// Whether the document could not be read, is not JSON or is not a lottie
+    pub fn classify(&self) -> Category {
+        self.inner.classify()
+    }
+
+    fn new(path: &str, inner: serde_json::Error) -> Self {
+        // serde_json appends the position to the message
+        let message = inner.to_string();
+        let suffix = format!(" at line {} column {}", inner.line(), inner.column());
+        let message = message.strip_suffix(&suffix).unwrap_or(&message);
+        let (path, message) = match split_nested(message) {
+            Some((nested, message)) => (join(path, nested), message.to_string()),
+            None => (join(path, ""), message.to_string()),
+        };
+        Error {
+            message,
+            path,
+            line: inner.line(),
+            column: inner.column(),
+            inner,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)?;
+        if !self.path.is_empty() {
+            write!(f, " at `{}`", self.path)?;
+        }
+        if self.line > 0 {
+            write!(f, ", line {} column {}", self.line, self.column)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.inner)
+    }
+}
+
+impl From<serde_path_to_error::Error<serde_json::Error>> for Error {
+    fn from(e: serde_path_to_error::Error<serde_json::Error>) -> Self {
+        let path = e.path().to_string();
+        Error::new(&path, e.into_inner())
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::new("", e)
+    }
+}
+
+impl From<Error> for std::io::Error {
+    fn from(e: Error) -> Self {
+        match e.classify() {
+            Category::Io => e.inner.into(),
+            _ => std::io::Error::new(std::io::ErrorKind::InvalidData, e),
+        }
+    }
+}
+
+const NESTED: &str = "at `";
+
+/// Message of a failure at `path` below a value buffered by a custom
+/// deserializer, whose own path is joined when the error reaches the top
+pub(crate) fn nested_message(path: &str, message: &str) -> String {
+    let (path, message) = match split_nested(message) {
+        Some((nested, message)) => (join(path, nested), message),
+        None => (join(path, ""), message),
+    };
+    if path.is_empty() {
+        message.to_string()
+    } else {
+        format!("{}{}`: {}", NESTED, path, message)
+    }
+}
+
+fn split_nested(message: &str) -> Option<(&str, &str)> {
+    message.strip_prefix(NESTED)?.split_once("`: ")
+}
+
+fn join(path: &str, nested: &str) -> String {
+    // serde_path_to_error writes the root as `.`
+    let path = if path == "." { "" } else { path };
+    if path.is_empty() || nested.is_empty() || nested.starts_with('[') {
+        format!("{}{}", path, nested)
+    } else {
+        format!("{}.{}", path, nested)
+    }
+}
@@ -52,12 +52,34 @@ where
     serializer.serialize_u8(if *b { 1 } else { 0 })
 }
 
+/// Reads a property either in its animated form or as a plain number, which
+/// older exporters wrote for the text path flags
+pub(crate) fn animated_from_number<'de, D>(
+    deserializer: D,
+) -> Result<Option<Animated<f32>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Property {
+        Plain(f32),
+        Animated(Animated<f32>),
+    }
+    Ok(
+        Option::<Property>::deserialize(deserializer)?.map(|property| match property {
+            Property::Plain(value) => Animated::from_value(value),
+            Property::Animated(animated) => animated,
+        }),
+    )
+}
+
 pub fn array_to_rgba<'de, D>(deserializer: D) -> Result<Rgba, D::Error>
 where
     D: Deserializer<'de>,
 {
     let s = Value::deserialize(deserializer)?;
-    Ok(<Rgba as FromTo<Value>>::from(s))
+    <Rgba as FromTo<Value>>::from(s).ok_or_else(|| D::Error::custom("invalid color"))
 }
 
 pub fn str_to_rgba<'de, D>(deserializer: D) -> Result<Rgba, D::Error>
@@ -65,7 +87,8 @@ where
     D: Deserializer<'de>,
 {
     let s = String::deserialize(deserializer)?;
-    Ok(s.parse().unwrap())
+    s.parse()
+        .map_err(|_| D::Error::invalid_value(serde::de::Unexpected::Str(&s), &"a hex color"))
 }
 
 pub fn str_from_rgba<S>(b: &Rgba, serializer: S) -> Result<S::Ok, S::Error>
@@ -106,22 +129,28 @@ impl<'de> serde::Deserialize<'de> for LayerContent {
             width: f32,
         }
 
+        #[derive(Deserialize)]
+        struct TextLayer {
+            #[serde(rename = "t")]
+            data: TextAnimationData,
+        }
+
         Ok(
-            match value.get("ty").and_then(serde_json::Value::as_u64).unwrap() {
-                0 => LayerContent::PreCompositionRef(
-                    PreCompositionRef::deserialize(value).map_err(D::Error::custom)?,
-                ),
+            match value
+                .get("ty")
+                .and_then(serde_json::Value::as_u64)
+                .ok_or_else(|| D::Error::missing_field("ty"))?
+            {
+                0 => LayerContent::PreCompositionRef(from_value(&value).map_err(D::Error::custom)?),
                 1 => {
-                    let color = SolidColor::deserialize(value).unwrap();
+                    let color: SolidColor = from_value(&value).map_err(D::Error::custom)?;
                     LayerContent::SolidColor {
                         color: color.color,
                         height: color.height,
                         width: color.width,
                     }
                 }
-                2 | 6 => {
-                    LayerContent::MediaRef(MediaRef::deserialize(value).map_err(D::Error::custom)?)
-                }
+                2 | 6 => LayerContent::MediaRef(from_value(&value).map_err(D::Error::custom)?),
                 3 => LayerContent::Empty,
                 4 => {
                     let shapes = value
@@ -133,17 +162,46 @@ impl<'de> serde::Deserialize<'de> for LayerContent {
                     LayerContent::Shape(ShapeGroup { shapes })
                 }
                 5 => {
-                    let v = value.get("t").ok_or_else(|| D::Error::missing_field("t"))?;
-                    let v = TextAnimationData::deserialize(v).map_err(D::Error::custom)?;
-                    LayerContent::Text(v)
+                    let text: TextLayer = from_value(&value).map_err(D::Error::custom)?;
+                    LayerContent::Text(text.data)
                 }
                 // 7 => LayerContent::Null(Type3::deserialize(value).unwrap()),
-                _type => LayerContent::Empty, //panic!("unsupported type {:?}", type_),
+                ty => LayerContent::Unsupported(ty),
             },
         )
     }
 }
 
+impl<'de> serde::Deserialize<'de> for Asset {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let value = serde_json::Value::deserialize(d)?;
+        let mut untagged = Untagged::new("Asset", &value);
+        if let Some(media) = untagged.attempt("Media") {
+            return Ok(Asset::Media(media));
+        }
+        if untagged.attempt::<()>("Sound").is_some() {
+            return Ok(Asset::Sound);
+        }
+        if let Some(precomp) = untagged.attempt("Precomposition") {
+            return Ok(Asset::Precomposition(precomp));
+        }
+        Err(D::Error::custom(untagged))
+    }
+}
+
+/// Exporters write the grouping as a float at times, unknown values fall back
+/// to characters
+impl<'de> serde::Deserialize<'de> for TextGrouping {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        Ok(match d.deserialize_any(NumberVistor)? {
+            Some(2) => TextGrouping::Words,
+            Some(3) => TextGrouping::Lines,
+            Some(4) => TextGrouping::All,
+            _ => TextGrouping::Characters,
+        })
+    }
+}
+
 impl Serialize for LayerContent {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -152,9 +210,23 @@ impl Serialize for LayerContent {
         #[derive(Serialize)]
         #[serde(untagged)]
         enum LayerContent_<'a> {
-            // T1(&'a Type1),
-            SolidColor { sc: String, sh: f32, sw: f32 },
-            Shape { shapes: &'a Vec<ShapeLayer> },
+            PreCompositionRef(&'a PreCompositionRef),
+            SolidColor {
+                sc: String,
+                sh: f32,
+                sw: f32,
+            },
+            MediaRef {
+                #[serde(rename = "refId")]
+                ref_id: &'a str,
+            },
+            Empty {},
+            Shape {
+                shapes: &'a Vec<ShapeLayer>,
+            },
+            Text {
+                t: &'a TextAnimationData,
+            },
         }
 
         #[derive(Serialize)]
@@ -166,9 +238,9 @@ impl Serialize for LayerContent {
         }
 
         let msg = match self {
-            LayerContent::Shape(ShapeGroup { shapes }) => TypedLayerContent {
-                t: 4,
-                content: LayerContent_::Shape { shapes },
+            LayerContent::PreCompositionRef(r) => TypedLayerContent {
+                t: 0,
+                content: LayerContent_::PreCompositionRef(r),
             },
             LayerContent::SolidColor {
                 color,
@@ -182,42 +254,110 @@ impl Serialize for LayerContent {
                     sw: *width,
                 },
             },
-            _ => unimplemented!(),
+            // audio layers are written as image layers, both only reference
+            // their media asset
+            LayerContent::MediaRef(r) => TypedLayerContent {
+                t: 2,
+                content: LayerContent_::MediaRef { ref_id: &r.ref_id },
+            },
+            // media staged from a reference is written back as the reference
+            LayerContent::Media(media) => TypedLayerContent {
+                t: 2,
+                content: LayerContent_::MediaRef { ref_id: &media.id },
+            },
+            LayerContent::Empty => TypedLayerContent {
+                t: 3,
+                content: LayerContent_::Empty {},
+            },
+            LayerContent::Shape(ShapeGroup { shapes }) => TypedLayerContent {
+                t: 4,
+                content: LayerContent_::Shape { shapes },
+            },
+            LayerContent::Text(data) => TypedLayerContent {
+                t: 5,
+                content: LayerContent_::Text { t: data },
+            },
+            LayerContent::Unsupported(ty) => TypedLayerContent {
+                t: *ty,
+                content: LayerContent_::Empty {},
+            },
         };
         msg.serialize(serializer)
     }
 }
 
+/// Deserialize a value buffered by a custom deserializer, keeping the JSON
+/// path of the failure below it in the error message
+pub(crate) fn from_value<'de, T: Deserialize<'de>>(
+    value: &'de serde_json::Value,
+) -> Result<T, String> {
+    serde_path_to_error::deserialize(value).map_err(|e| {
+        let path = e.path().to_string();
+        super::error::nested_message(&path, &e.into_inner().to_string())
+    })
+}
+
+/// Variants of an untagged enum tried in order on a buffered value, keeping
+/// why each of them failed
+pub(crate) struct Untagged<'a> {
+    name: &'static str,
+    value: &'a serde_json::Value,
+    failures: Vec<String>,
+}
+
+impl<'a> Untagged<'a> {
+    pub fn new(name: &'static str, value: &'a serde_json::Value) -> Self {
+        Untagged {
+            name,
+            value,
+            failures: vec![],
+        }
+    }
+
+    pub fn attempt<T: Deserialize<'a>>(&mut self, variant: &str) -> Option<T> {
+        match from_value(self.value) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                self.failures.push(format!("{}: {}", variant, e));
+                None
+            }
+        }
+    }
+}
+
+impl fmt::Display for Untagged<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "data did not match any variant of {} ({})",
+            self.name,
+            self.failures.join("; ")
+        )
+    }
+}
+
 pub(crate) fn keyframes_from_array<'de, D, T>(deserializer: D) -> Result<Vec<KeyFrame<T>>, D::Error>
 where
     D: Deserializer<'de>,
     T: FromTo<Value>,
 {
     let result = AnimatedHelper::deserialize(deserializer)?;
-    Ok(result.into())
+    result.try_into().map_err(D::Error::custom)
 }
 
 pub fn array_from_keyframes<S, T>(b: &Vec<KeyFrame<T>>, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
+    T: FromTo<Value> + Clone,
 {
-    todo!()
-    // let animated = AnimatedHelper::from(b);
-    // match animated {
-    //     AnimatedHelper::Plain(data) => data.serialize(serializer),
-    //     AnimatedHelper::AnimatedHelper(data) => {
-    //         let mut seq = serializer.serialize_seq(Some(data.len()))?;
-    //         for keyframe in data {
-    //             seq.serialize_element(&keyframe)?;
-    //         }
-    //         seq.end()
-    //     }
-    // }
+    AnimatedHelper::from(b).serialize(serializer)
 }
 
 pub fn default_vec2_100() -> Animated<Vector2D> {
     Animated {
         animated: false,
+        expression: None,
+        slot: None,
         keyframes: vec![KeyFrame::from_value(Vector2D::new(100.0, 100.0))],
     }
 }
@@ -225,6 +365,8 @@ pub fn default_vec2_100() -> Animated<Vector2D> {
 pub fn default_number_100() -> Animated<f32> {
     Animated {
         animated: false,
+        expression: None,
+        slot: None,
         keyframes: vec![KeyFrame::from_value(100.0)],
     }
 }
@@ -286,11 +428,12 @@ where
     D: Deserializer<'de>,
 {
     let value = Value::deserialize(deserializer)?;
-    Ok(match value {
-        Value::Primitive(f) => vec![f],
-        Value::List(f) => f,
-        _ => unreachable!(),
-    })
+    match value {
+        Value::Primitive(f) => Ok(vec![f]),
+        // easings are read from their first value
+        Value::List(f) if !f.is_empty() => Ok(f),
+        _ => Err(D::Error::custom("expected a number or a non-empty array")),
+    }
 }
 
 #[derive(Deserialize, Serialize)]
@@ -301,49 +444,59 @@ pub(crate) struct ColorListHelper {
     colors: Animated<Vec<f32>>,
 }
 
-impl From<ColorListHelper> for ColorList {
-    fn from(helper: ColorListHelper) -> Self {
+impl TryFrom<ColorListHelper> for ColorList {
+    type Error = &'static str;
+
+    fn try_from(helper: ColorListHelper) -> Result<Self, Self::Error> {
         let color_count = helper.color_count;
-        ColorList {
+        Ok(ColorList {
             color_count,
             colors: Animated {
                 animated: helper.colors.animated,
+                expression: helper.colors.expression,
+                slot: helper.colors.slot,
                 keyframes: helper
                     .colors
                     .keyframes
                     .into_iter()
                     .map(|keyframe| {
-                        let start = f32_to_gradient_colors(&keyframe.start_value, color_count);
-                        let end = f32_to_gradient_colors(&keyframe.end_value, color_count);
-                        keyframe.alter_value(start, end)
+                        let start = f32_to_gradient_colors(&keyframe.start_value, color_count)?;
+                        let end = f32_to_gradient_colors(&keyframe.end_value, color_count)?;
+                        Some(keyframe.alter_value(start, end))
                     })
-                    .collect(),
+                    .collect::<Option<_>>()
+                    .ok_or("gradient colors don't match the color count")?,
             },
-        }
+        })
     }
 }
 
-fn f32_to_gradient_colors(data: &Vec<f32>, color_count: usize) -> Vec<GradientColor> {
-    if data.len() == color_count * 4 {
+fn f32_to_gradient_colors(data: &Vec<f32>, color_count: usize) -> Option<Vec<GradientColor>> {
+    let rgb_len = color_count.checked_mul(4)?;
+    if data.len() == rgb_len {
         // Rgb color
-        data.chunks(4)
-            .map(|chunk| GradientColor {
-                offset: chunk[0],
-                color: Rgba::new_f32(chunk[1], chunk[2], chunk[3], 1.0),
-            })
-            .collect()
-    } else if data.len() == color_count * 4 + color_count * 2 {
+        Some(
+            data.chunks(4)
+                .map(|chunk| GradientColor {
+                    offset: chunk[0],
+                    color: Rgba::new_f32(chunk[1], chunk[2], chunk[3], 1.0),
+                })
+                .collect(),
+        )
+    } else if Some(data.len()) == color_count.checked_mul(6) {
         // Rgba color
-        (&data[0..(color_count * 4)])
-            .chunks(4)
-            .zip((&data[(color_count * 4)..]).chunks(2))
-            .map(|(chunk, opacity)| GradientColor {
-                offset: chunk[0],
-                color: Rgba::new_f32(chunk[1], chunk[2], chunk[3], opacity[1]),
-            })
-            .collect()
+        Some(
+            (&data[0..rgb_len])
+                .chunks(4)
+                .zip((&data[rgb_len..]).chunks(2))
+                .map(|(chunk, opacity)| GradientColor {
+                    offset: chunk[0],
+                    color: Rgba::new_f32(chunk[1], chunk[2], chunk[3], opacity[1]),
+                })
+                .collect(),
+        )
     } else {
-        unimplemented!()
+        None
     }
 }
 
@@ -353,6 +506,8 @@ impl From<ColorList> for ColorListHelper {
             color_count: list.color_count,
             colors: Animated {
                 animated: list.colors.animated,
+                expression: list.colors.expression,
+                slot: list.colors.slot,
                 keyframes: list
                     .colors
                     .keyframes
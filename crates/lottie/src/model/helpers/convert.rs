@@ -1,25 +1,26 @@
 use super::{Bezier, Rgb, TextDocument, Value, Vector2D};
 
-pub trait FromTo<T> {
-    fn from(v: T) -> Self;
+/// Conversion of the loosely typed values of a lottie JSON, `from` fails
+/// when the value has the wrong shape
+pub trait FromTo<T>: Sized {
+    fn from(v: T) -> Option<Self>;
     fn to(self) -> T;
 }
 
 impl FromTo<Value> for Vector2D {
-    fn from(v: Value) -> Self {
-        let v = v.as_f32_vec().unwrap();
-        Vector2D::new(v[0], v.get(1).cloned().unwrap_or(0.0))
+    fn from(v: Value) -> Option<Self> {
+        let v = v.as_f32_vec()?;
+        Some(Vector2D::new(*v.first()?, v.get(1).cloned().unwrap_or(0.0)))
     }
 
     fn to(self) -> Value {
-        todo!()
+        Value::List(vec![self.x, self.y])
     }
 }
 
 impl FromTo<Value> for f32 {
-    fn from(v: Value) -> Self {
-        let v = v.as_f32_vec().unwrap();
-        v[0]
+    fn from(v: Value) -> Option<Self> {
+        v.as_f32_vec()?.first().cloned()
     }
 
     fn to(self) -> Value {
@@ -28,13 +29,14 @@ impl FromTo<Value> for f32 {
 }
 
 impl FromTo<Value> for Rgb {
-    fn from(v: Value) -> Self {
-        let v = v.as_f32_vec().unwrap();
-        if v[0] > 1.0 && v[0] <= 255.0 {
-            Rgb::new_u8(v[0] as u8, v[1] as u8, v[2] as u8)
+    fn from(v: Value) -> Option<Self> {
+        let v = v.as_f32_vec()?;
+        let (r, g, b) = (*v.first()?, *v.get(1)?, *v.get(2)?);
+        Some(if r > 1.0 && r <= 255.0 {
+            Rgb::new_u8(r as u8, g as u8, b as u8)
         } else {
-            Rgb::new_f32(v[0], v[1], v[2])
-        }
+            Rgb::new_f32(r, g, b)
+        })
     }
 
     fn to(self) -> Value {
@@ -47,11 +49,11 @@ impl FromTo<Value> for Rgb {
 }
 
 impl FromTo<Value> for Vec<Bezier> {
-    fn from(v: Value) -> Self {
+    fn from(v: Value) -> Option<Self> {
         match v {
-            Value::ComplexBezier(b) => b,
-            Value::Bezier(b) => vec![b],
-            _ => todo!(),
+            Value::ComplexBezier(b) => Some(b),
+            Value::Bezier(b) => Some(vec![b]),
+            _ => None,
         }
     }
 
@@ -61,11 +63,11 @@ impl FromTo<Value> for Vec<Bezier> {
 }
 
 impl FromTo<Value> for Vec<f32> {
-    fn from(v: Value) -> Self {
+    fn from(v: Value) -> Option<Self> {
         match v {
-            Value::Primitive(f) => vec![f],
-            Value::List(l) => l,
-            _ => todo!(),
+            Value::Primitive(f) => Some(vec![f]),
+            Value::List(l) => Some(l),
+            _ => None,
         }
     }
 
@@ -75,10 +77,10 @@ impl FromTo<Value> for Vec<f32> {
 }
 
 impl FromTo<Value> for TextDocument {
-    fn from(v: Value) -> Self {
+    fn from(v: Value) -> Option<Self> {
         match v {
-            Value::TextDocument(t) => t,
-            _ => todo!(),
+            Value::TextDocument(t) => Some(t),
+            _ => None,
         }
     }
 
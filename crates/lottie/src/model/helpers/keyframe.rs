@@ -1,71 +1,163 @@
-use serde::Deserialize;
+use serde::de::Error;
+use serde::ser::SerializeSeq;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-use super::{Easing, FromTo, KeyFrame, Value};
+use super::{Easing, FromTo, KeyFrame, Untagged, Value};
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 #[serde(transparent)]
 pub(super) struct AnimatedHelper {
     data: TolerantAnimatedHelper,
 }
 
-#[derive(Deserialize)]
-#[serde(untagged)]
 enum TolerantAnimatedHelper {
     Plain(Value),
     AnimatedHelper(Vec<LegacyTolerantKeyFrame>),
 }
 
+impl<'de> Deserialize<'de> for TolerantAnimatedHelper {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let value = serde_json::Value::deserialize(d)?;
+        let mut untagged = Untagged::new("TolerantAnimatedHelper", &value);
+        if let Some(value) = untagged.attempt("Plain") {
+            return Ok(TolerantAnimatedHelper::Plain(value));
+        }
+        if let Some(keyframes) = untagged.attempt("AnimatedHelper") {
+            return Ok(TolerantAnimatedHelper::AnimatedHelper(keyframes));
+        }
+        Err(D::Error::custom(untagged))
+    }
+}
+
+impl Serialize for TolerantAnimatedHelper {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            TolerantAnimatedHelper::Plain(value) => value.serialize(serializer),
+            TolerantAnimatedHelper::AnimatedHelper(keyframes) => {
+                let mut seq = serializer.serialize_seq(Some(keyframes.len()))?;
+                for keyframe in keyframes {
+                    seq.serialize_element(keyframe)?;
+                }
+                seq.end()
+            }
+        }
+    }
+}
+
 fn default_none<T>() -> Option<T> {
     None
 }
 
-#[derive(Deserialize, Default, Debug, Clone)]
+/// Keyframe with an optional end value `e`. Versioned files are migrated
+/// beforehand, see `migrate`; end values remain for files without a version
+/// and for jumps between keyframes
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
 struct LegacyKeyFrame<T> {
     #[serde(rename = "s")]
     start_value: T,
-    #[serde(rename = "e", default = "default_none")]
+    #[serde(
+        rename = "e",
+        default = "default_none",
+        skip_serializing_if = "Option::is_none"
+    )]
     end_value: Option<T>,
     #[serde(rename = "t", default)]
     start_frame: f32,
     #[serde(skip)]
     end_frame: f32,
-    #[serde(rename = "o", default)]
+    #[serde(rename = "o", default, skip_serializing_if = "Option::is_none")]
     easing_out: Option<Easing>,
-    #[serde(rename = "i", default)]
+    #[serde(rename = "i", default, skip_serializing_if = "Option::is_none")]
     easing_in: Option<Easing>,
-    #[serde(rename = "h", default, deserialize_with = "super::bool_from_int")]
+    #[serde(
+        rename = "h",
+        default,
+        deserialize_with = "super::bool_from_int",
+        skip_serializing
+    )]
     hold: bool,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 #[serde(untagged)]
 enum LegacyTolerantKeyFrame {
     LegacyKeyFrame(LegacyKeyFrame<Value>),
     TOnly { t: f32 },
 }
 
-impl<'a, T> From<&'a Vec<KeyFrame<T>>> for AnimatedHelper {
-    fn from(_: &'a Vec<KeyFrame<T>>) -> Self {
-        todo!()
+/// Whether two values are written the same, values of the model having no
+/// equality of their own
+fn same_value(a: &Value, b: &Value) -> bool {
+    match (serde_json::to_value(a), serde_json::to_value(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
     }
 }
 
-impl<T> From<AnimatedHelper> for Vec<KeyFrame<T>>
+impl<'a, T> From<&'a Vec<KeyFrame<T>>> for AnimatedHelper
+where
+    T: FromTo<Value> + Clone,
+{
+    fn from(keyframes: &'a Vec<KeyFrame<T>>) -> Self {
+        if let [keyframe] = keyframes.as_slice() {
+            let plain = keyframe.start_frame == 0.0
+                && keyframe.end_frame == 0.0
+                && keyframe.easing_in.is_none()
+                && keyframe.easing_out.is_none();
+            if plain {
+                return AnimatedHelper {
+                    data: TolerantAnimatedHelper::Plain(keyframe.start_value.clone().to()),
+                };
+            }
+        }
+        let mut result = vec![];
+        for (index, keyframe) in keyframes.iter().enumerate() {
+            let start_value = keyframe.start_value.clone().to();
+            let end_value = keyframe.end_value.clone().to();
+            // end values are implied by the next keyframe, only jumps and
+            // the end of a last keyframe lasting past its start are written
+            let end_value = match keyframes.get(index + 1) {
+                Some(next) if same_value(&end_value, &next.start_value.clone().to()) => None,
+                Some(_) => Some(end_value),
+                None if keyframe.end_frame > keyframe.start_frame => Some(end_value),
+                None => None,
+            };
+            result.push(LegacyTolerantKeyFrame::LegacyKeyFrame(LegacyKeyFrame {
+                start_value,
+                end_value,
+                start_frame: keyframe.start_frame,
+                end_frame: keyframe.end_frame,
+                easing_out: keyframe.easing_out.clone(),
+                easing_in: keyframe.easing_in.clone(),
+                hold: false,
+            }));
+        }
+        if let Some(last) = keyframes.last().filter(|k| k.end_frame > k.start_frame) {
+            result.push(LegacyTolerantKeyFrame::TOnly { t: last.end_frame });
+        }
+        AnimatedHelper {
+            data: TolerantAnimatedHelper::AnimatedHelper(result),
+        }
+    }
+}
+
+impl<T> TryFrom<AnimatedHelper> for Vec<KeyFrame<T>>
 where
     T: FromTo<Value>,
 {
-    fn from(animated: AnimatedHelper) -> Self {
+    type Error = &'static str;
+
+    fn try_from(animated: AnimatedHelper) -> Result<Self, Self::Error> {
+        const INVALID_VALUE: &str = "invalid keyframe value";
         match animated.data {
-            TolerantAnimatedHelper::Plain(v) => {
-                vec![KeyFrame {
-                    start_value: T::from(v.clone()),
-                    end_value: T::from(v),
-                    start_frame: 0.0,
-                    end_frame: 0.0,
-                    easing_in: None,
-                    easing_out: None,
-                }]
-            }
+            TolerantAnimatedHelper::Plain(v) => Ok(vec![KeyFrame {
+                start_value: T::from(v.clone()).ok_or(INVALID_VALUE)?,
+                end_value: T::from(v).ok_or(INVALID_VALUE)?,
+                start_frame: 0.0,
+                end_frame: 0.0,
+                easing_in: None,
+                easing_out: None,
+            }]),
             TolerantAnimatedHelper::AnimatedHelper(v) => {
                 let mut result: Vec<LegacyKeyFrame<Value>> = vec![];
                 // Sometimes keyframes especially from TextData do not have an ending frame, so
@@ -106,21 +198,28 @@ where
                 {
                     result.pop();
                 }
+                // animated properties are read from their first keyframe
+                if result.is_empty() {
+                    return Err("empty keyframe array");
+                }
                 result
                     .into_iter()
-                    .map(|keyframe| KeyFrame {
-                        end_value: T::from(
-                            keyframe
-                                .end_value
-                                .unwrap_or_else(|| keyframe.start_value.clone()),
-                        ),
-                        start_value: T::from(keyframe.start_value),
-                        start_frame: keyframe.start_frame,
-                        end_frame: keyframe.end_frame.max(keyframe.start_frame),
-                        easing_in: keyframe.easing_in,
-                        easing_out: keyframe.easing_out,
+                    .map(|keyframe| {
+                        Some(KeyFrame {
+                            end_value: T::from(
+                                keyframe
+                                    .end_value
+                                    .unwrap_or_else(|| keyframe.start_value.clone()),
+                            )?,
+                            start_value: T::from(keyframe.start_value)?,
+                            start_frame: keyframe.start_frame,
+                            end_frame: keyframe.end_frame.max(keyframe.start_frame),
+                            easing_in: keyframe.easing_in,
+                            easing_out: keyframe.easing_out,
+                        })
                     })
-                    .collect()
+                    .collect::<Option<_>>()
+                    .ok_or(INVALID_VALUE)
             }
         }
     }
@@ -0,0 +1,286 @@
+use std::fmt;
+
+use serde_json::{json, Value};
+
+/// Version of the Bodymovin exporter that wrote an animation, `v` in the
+/// JSON
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl Version {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Version {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Parse `major.minor.patch`, missing parts being 0
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut parts = s.trim().split('.').map(str::parse::<u32>);
+        let major = parts.next()?.ok()?;
+        let minor = parts.next().unwrap_or(Ok(0)).ok()?;
+        let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+        Some(Version::new(major, minor, patch))
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Exports before this version write the colors of fills and strokes in the
+/// 0-255 range
+const COLORS: Version = Version::new(4, 1, 9);
+/// Exports before this version write the text document of a text layer
+/// directly, without keyframes
+const TEXT_DOCUMENTS: Version = Version::new(4, 4, 14);
+/// Exports before this version write whether a path is closed on the shape,
+/// `closed`, or the mask, `cl`, instead of on its bezier
+const CLOSED_PATHS: Version = Version::new(4, 4, 18);
+/// Exports before this version may write gradient colors as a bare array,
+/// without their count `p` or the animated property around them
+const GRADIENTS: Version = Version::new(5, 0, 0);
+/// Exports before this version write the end value `e` of each keyframe,
+/// and end animations with a keyframe made of its time `t` only
+const KEYFRAMES: Version = Version::new(5, 5, 0);
+
+/// Whether exports of `version` may have structures to migrate, [KEYFRAMES]
+/// being the latest of the versions migrated from
+pub(crate) fn outdated(version: Version) -> bool {
+    version < KEYFRAMES
+}
+
+/// Rewrite the structures older than the version `v` of a lottie JSON into
+/// their current form, so that they parse like recent exports. Files
+/// without a version are left untouched. Returns whether anything was
+/// rewritten
+pub(crate) fn migrate(value: &mut Value) -> bool {
+    let Some(version) = value
+        .get("v")
+        .and_then(Value::as_str)
+        .and_then(Version::parse)
+    else {
+        return false;
+    };
+    if !outdated(version) {
+        return false;
+    }
+    let mut migration = Migration {
+        version,
+        migrated: false,
+    };
+    let Value::Object(root) = value else {
+        return false;
+    };
+    for (key, value) in root.iter_mut() {
+        match (key.as_str(), value) {
+            ("layers", Value::Array(layers)) => migration.layers(layers),
+            ("assets", Value::Array(assets)) => {
+                for asset in assets {
+                    if let Some(Value::Array(layers)) = asset.get_mut("layers") {
+                        migration.layers(layers);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    // end values are folded once the other migrations have used them
+    if version < KEYFRAMES {
+        migration.keyframes(value);
+    }
+    migration.migrated
+}
+
+struct Migration {
+    version: Version,
+    migrated: bool,
+}
+
+impl Migration {
+    fn layers(&mut self, layers: &mut [Value]) {
+        for layer in layers {
+            if self.version < TEXT_DOCUMENTS && layer.get("ty").and_then(Value::as_u64) == Some(5) {
+                if let Some(document) = layer.pointer_mut("/t/d") {
+                    if document.get("k").is_none() {
+                        let value = document.take();
+                        *document = json!({"k": [{"s": value, "t": 0}]});
+                        self.migrated = true;
+                    }
+                }
+            }
+            if self.version < CLOSED_PATHS {
+                if let Some(Value::Array(masks)) = layer.get_mut("masksProperties") {
+                    for mask in masks {
+                        if let Some(closed) = mask.get("cl").and_then(Value::as_bool) {
+                            self.close(mask.get_mut("pt"), closed);
+                        }
+                    }
+                }
+            }
+            if let Some(Value::Array(shapes)) = layer.get_mut("shapes") {
+                self.shapes(shapes);
+            }
+        }
+    }
+
+    fn shapes(&mut self, shapes: &mut [Value]) {
+        for shape in shapes {
+            match shape.get("ty").and_then(Value::as_str) {
+                Some("gr") => {
+                    if let Some(Value::Array(shapes)) = shape.get_mut("it") {
+                        self.shapes(shapes);
+                    }
+                }
+                Some("fl" | "st") if self.version < COLORS => {
+                    if let Some(color) = shape.get_mut("c") {
+                        self.colors(color);
+                    }
+                }
+                Some("gf" | "gs") if self.version < GRADIENTS => {
+                    if let Some(gradient) = shape.get_mut("g") {
+                        self.gradient(gradient);
+                    }
+                }
+                Some("sh") if self.version < CLOSED_PATHS => {
+                    if let Some(closed) = shape.get("closed").and_then(Value::as_bool) {
+                        self.close(shape.get_mut("ks"), closed);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Scale the static or keyframed color `property` from 0-255 to 0-1
+    fn colors(&mut self, property: &mut Value) {
+        let scale = |color: &mut Value| {
+            if let Value::Array(channels) = color {
+                for channel in channels.iter_mut().take(4) {
+                    if let Some(v) = channel.as_f64() {
+                        *channel = json!(v / 255.0);
+                    }
+                }
+            }
+        };
+        match property.get_mut("k") {
+            Some(Value::Array(keyframes)) if keyframes.first().is_some_and(Value::is_object) => {
+                for keyframe in keyframes {
+                    for key in ["s", "e"] {
+                        if let Some(color) = keyframe.get_mut(key) {
+                            scale(color);
+                        }
+                    }
+                }
+            }
+            Some(color) => scale(color),
+            None => return,
+        }
+        self.migrated = true;
+    }
+
+    /// Wrap bare gradient colors into `{"p": count, "k": {"a": 0, "k":
+    /// colors}}`
+    fn gradient(&mut self, gradient: &mut Value) {
+        if gradient.is_array() {
+            let colors = gradient.take();
+            *gradient = json!({ "k": colors });
+            self.migrated = true;
+        }
+        let Value::Object(gradient) = gradient else {
+            return;
+        };
+        if let Some(colors) = gradient.get_mut("k").filter(|colors| colors.is_array()) {
+            let value = colors.take();
+            *colors = json!({"a": 0, "k": value});
+            self.migrated = true;
+        }
+        if !gradient.contains_key("p") {
+            // without a count, all of the stops are assumed to be colors
+            let count = gradient
+                .get("k")
+                .and_then(|colors| colors.get("k"))
+                .and_then(Value::as_array)
+                .map(|colors| colors.len() / 4);
+            if let Some(count) = count {
+                gradient.insert("p".to_string(), json!(count));
+                self.migrated = true;
+            }
+        }
+    }
+
+    /// Mark the static or keyframed bezier of `property` as `closed`
+    fn close(&mut self, property: Option<&mut Value>, closed: bool) {
+        let set = |bezier: &mut Value| {
+            if let Value::Object(bezier) = bezier {
+                bezier.insert("c".to_string(), Value::Bool(closed));
+            }
+        };
+        match property.and_then(|property| property.get_mut("k")) {
+            Some(Value::Array(keyframes)) => {
+                for keyframe in keyframes {
+                    for key in ["s", "e"] {
+                        if let Some(Value::Array(beziers)) = keyframe.get_mut(key) {
+                            for bezier in beziers {
+                                set(bezier);
+                            }
+                        }
+                    }
+                }
+            }
+            Some(bezier) => set(bezier),
+            None => return,
+        }
+        self.migrated = true;
+    }
+
+    /// Fold the end value of each keyframe into the next one, the closing
+    /// `t` only keyframe getting the last end value. End values differing
+    /// from the next start value are kept
+    fn keyframes(&mut self, value: &mut Value) {
+        let mut stack = vec![value];
+        while let Some(value) = stack.pop() {
+            match value {
+                Value::Object(object) => {
+                    if let Some(Value::Array(keyframes)) = object.get_mut("k") {
+                        self.fold_end_values(keyframes);
+                    }
+                    stack.extend(object.values_mut());
+                }
+                Value::Array(values) => stack.extend(values.iter_mut()),
+                _ => {}
+            }
+        }
+    }
+
+    fn fold_end_values(&mut self, keyframes: &mut [Value]) {
+        for index in 0..keyframes.len() {
+            let (current, next) = keyframes.split_at_mut(index + 1);
+            let Some(keyframe) = current[index].as_object_mut() else {
+                continue;
+            };
+            let Some(end) = keyframe.get("e") else {
+                continue;
+            };
+            match next.first_mut().and_then(Value::as_object_mut) {
+                Some(next) if !next.contains_key("s") && next.contains_key("t") => {
+                    next.insert("s".to_string(), end.clone());
+                }
+                Some(next) if next.get("s") == Some(end) => {}
+                // a jump between keyframes, or the end of a property without
+                // a closing keyframe
+                _ => continue,
+            }
+            keyframe.remove("e");
+            self.migrated = true;
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::PathBuf;
+
+use url::Url;
+
+use crate::model::{Asset, FontPathOrigin, LayerContent, Model};
+use crate::{Error, Limit, LoadLimits};
+
+/// Kind of an asset requested from an [AssetResolver]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AssetKind {
+    /// Image or audio file of a media asset
+    Media,
+    /// Font file of a font loaded from an url
+    Font,
+}
+
+/// Maps asset references of an animation to their bytes. Embedded data urls
+/// are decoded without going through the resolver
+pub trait AssetResolver: Send + Sync {
+    /// Bytes of the asset at `path`, a path or an url as written in the
+    /// animation
+    fn resolve(&self, path: &str, kind: AssetKind) -> Result<Vec<u8>, Error>;
+}
+
+/// Reads assets from the file system. Relative paths are resolved from a
+/// root directory, and files outside of it are denied, as are urls other
+/// than `file://`
+#[derive(Debug, Clone)]
+pub struct FileResolver {
+    root: PathBuf,
+    max_bytes: usize,
+}
+
+impl FileResolver {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        FileResolver {
+            root: root.into(),
+            max_bytes: LoadLimits::default().max_asset_bytes,
+        }
+    }
+
+    /// Refuse files larger than `max_bytes`
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+}
+
+impl AssetResolver for FileResolver {
+    fn resolve(&self, path: &str, _kind: AssetKind) -> Result<Vec<u8>, Error> {
+        let file = match Url::parse(path) {
+            Ok(url) if url.scheme() == "file" => url
+                .to_file_path()
+                .map_err(|_| Error::AssetNotFound(path.to_string()))?,
+            // a single letter scheme is a windows drive
+            Ok(url) if url.scheme().len() > 1 => return Err(Error::AssetDenied(path.to_string())),
+            _ => self.root.join(path),
+        };
+        // absolute paths, `..` and symbolic links must not leave the root
+        let file = file.canonicalize()?;
+        if !file.starts_with(self.root.canonicalize()?) {
+            return Err(Error::AssetDenied(path.to_string()));
+        }
+        read_bounded(std::fs::File::open(file)?, self.max_bytes)
+    }
+}
+
+/// Serves assets from memory, e.g. files bundled with an application
+#[derive(Debug, Clone, Default)]
+pub struct MemoryResolver {
+    assets: HashMap<String, Vec<u8>>,
+}
+
+impl MemoryResolver {
+    pub fn new() -> Self {
+        MemoryResolver::default()
+    }
+
+    /// Serve `data` for the asset at `path`, as written in the animation
+    pub fn insert(&mut self, path: impl Into<String>, data: Vec<u8>) {
+        self.assets.insert(path.into(), data);
+    }
+}
+
+impl AssetResolver for MemoryResolver {
+    fn resolve(&self, path: &str, _kind: AssetKind) -> Result<Vec<u8>, Error> {
+        self.assets
+            .get(path)
+            .cloned()
+            .ok_or_else(|| Error::AssetNotFound(path.to_string()))
+    }
+}
+
+/// Refuses every asset, so that loading an animation never reads files nor
+/// opens sockets
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DenyResolver;
+
+impl AssetResolver for DenyResolver {
+    fn resolve(&self, path: &str, _kind: AssetKind) -> Result<Vec<u8>, Error> {
+        Err(Error::AssetDenied(path.to_string()))
+    }
+}
+
+/// Fetches `http` and `https` urls, other assets are left to `local`
+#[cfg(feature = "network")]
+#[derive(Debug, Clone)]
+pub struct NetworkResolver<R> {
+    local: R,
+    max_bytes: usize,
+}
+
+#[cfg(feature = "network")]
+impl<R: AssetResolver> NetworkResolver<R> {
+    pub fn new(local: R) -> Self {
+        NetworkResolver {
+            local,
+            max_bytes: LoadLimits::default().max_asset_bytes,
+        }
+    }
+
+    /// Refuse responses larger than `max_bytes`
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+}
+
+#[cfg(feature = "network")]
+impl<R: AssetResolver> AssetResolver for NetworkResolver<R> {
+    fn resolve(&self, path: &str, kind: AssetKind) -> Result<Vec<u8>, Error> {
+        let url = match Url::parse(path) {
+            Ok(url) if url.scheme() == "http" || url.scheme() == "https" => url,
+            _ => return self.local.resolve(path, kind),
+        };
+        let response = ureq::get(url.as_str()).call()?;
+        // the announced length only lets us fail early, the body is bounded
+        // while reading since the header can't be trusted
+        let len = response
+            .header("Content-Length")
+            .and_then(|len| len.parse::<u64>().ok());
+        if len.map_or(false, |len| len > self.max_bytes as u64) {
+            return Err(Error::LimitExceeded {
+                limit: Limit::AssetBytes,
+                max: self.max_bytes,
+            });
+        }
+        read_bounded(response.into_reader(), self.max_bytes)
+    }
+}
+
+/// Read `reader` to the end, failing once more than `max_bytes` are read
+pub(crate) fn read_bounded(reader: impl Read, max_bytes: usize) -> Result<Vec<u8>, Error> {
+    let mut bytes = vec![];
+    reader.take(max_bytes as u64 + 1).read_to_end(&mut bytes)?;
+    if bytes.len() > max_bytes {
+        return Err(Error::LimitExceeded {
+            limit: Limit::AssetBytes,
+            max: max_bytes,
+        });
+    }
+    Ok(bytes)
+}
+
+/// Assets `model` loads through a resolver, without duplicates: the media
+/// files of image and audio layers, and the fonts loaded from urls
+pub(crate) fn asset_references(model: &Model) -> Vec<(String, AssetKind)> {
+    let compositions = model.assets.iter().filter_map(|asset| match asset {
+        Asset::Precomposition(precomp) => Some(&precomp.layers),
+        _ => None,
+    });
+    let media = std::iter::once(&model.layers)
+        .chain(compositions)
+        .flatten()
+        .filter_map(|layer| match &layer.content {
+            LayerContent::MediaRef(media) => Some(&media.ref_id),
+            _ => None,
+        })
+        .filter_map(|id| {
+            model.assets.iter().find_map(|asset| match asset {
+                Asset::Media(media) if asset.id() == Some(id.as_str()) => Some(media.path()),
+                _ => None,
+            })
+        })
+        .filter(|path| !path.starts_with("data:"))
+        .map(|path| (path, AssetKind::Media));
+    let fonts = model
+        .fonts
+        .list
+        .iter()
+        .filter(|font| font.origin == FontPathOrigin::FontUrl)
+        .filter_map(|font| Some((font.path.clone()?, AssetKind::Font)));
+    let mut result: Vec<(String, AssetKind)> = vec![];
+    for reference in media.chain(fonts) {
+        if !result.contains(&reference) {
+            result.push(reference);
+        }
+    }
+    result
+}
+
+/// Resolver used by [Lottie::from_reader](crate::Lottie::from_reader): files
+/// relative to `root_path`, and urls when the `network` feature is enabled
+pub fn default_resolver(root_path: &str) -> Box<dyn AssetResolver> {
+    let resolver = FileResolver::new(root_path);
+    #[cfg(feature = "network")]
+    let resolver = NetworkResolver::new(resolver);
+    Box::new(resolver)
+}
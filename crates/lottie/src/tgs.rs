@@ -0,0 +1,163 @@
+use std::fmt;
+
+use crate::model::{Asset, Model};
+use crate::{analyze, Error, Feature, LoadLimits};
+
+/// A constraint of [TgsProfile] broken by a sticker
+#[derive(Debug, Clone, PartialEq)]
+pub enum TgsViolationKind {
+    /// The file is not gzip compressed
+    Uncompressed,
+    /// The compressed file is larger than allowed
+    FileSize {
+        bytes: usize,
+        max: usize,
+    },
+    Dimensions {
+        width: u32,
+        height: u32,
+    },
+    FrameRate(f32),
+    /// The animation lasts longer than allowed, in seconds
+    Duration(f32),
+    /// Image or audio assets
+    Media,
+    Expression,
+}
+
+impl fmt::Display for TgsViolationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TgsViolationKind::Uncompressed => f.write_str("sticker is not gzip compressed"),
+            TgsViolationKind::FileSize { bytes, max } => {
+                write!(f, "sticker is {} bytes, more than {}", bytes, max)
+            }
+            TgsViolationKind::Dimensions { width, height } => {
+                write!(f, "sticker is {}x{}", width, height)
+            }
+            TgsViolationKind::FrameRate(frame_rate) => {
+                write!(f, "sticker runs at {} fps", frame_rate)
+            }
+            TgsViolationKind::Duration(duration) => {
+                write!(f, "sticker lasts {} seconds", duration)
+            }
+            TgsViolationKind::Media => f.write_str("media assets are not allowed"),
+            TgsViolationKind::Expression => f.write_str("expressions are not allowed"),
+        }
+    }
+}
+
+/// A broken constraint, at the JSON path of the value breaking it, empty
+/// for the file itself
+#[derive(Debug, Clone, PartialEq)]
+pub struct TgsViolation {
+    pub path: String,
+    pub kind: TgsViolationKind,
+}
+
+impl fmt::Display for TgsViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.kind)
+        } else {
+            write!(f, "`{}`: {}", self.path, self.kind)
+        }
+    }
+}
+
+/// Constraints of Telegram animated stickers (`.tgs`), gzip compressed
+/// lottie JSON. The defaults are the constraints Telegram enforces
+#[derive(Debug, Clone)]
+pub struct TgsProfile {
+    pub width: u32,
+    pub height: u32,
+    pub frame_rate: f32,
+    /// Duration of the animation, in seconds
+    pub max_duration: f32,
+    /// Size of the compressed file, in bytes
+    pub max_bytes: usize,
+}
+
+impl Default for TgsProfile {
+    fn default() -> Self {
+        TgsProfile {
+            width: 512,
+            height: 512,
+            frame_rate: 60.0,
+            max_duration: 3.0,
+            max_bytes: 64 * 1_024,
+        }
+    }
+}
+
+impl TgsProfile {
+    /// Check a `.tgs` file, returning all of the violations found. Fails
+    /// only if the file cannot be parsed
+    pub fn check_file(&self, data: &[u8]) -> Result<Vec<TgsViolation>, Error> {
+        let model = LoadLimits::default().read_model(data)?;
+        let mut violations = vec![];
+        if !data.starts_with(&[0x1f, 0x8b]) {
+            violations.push(TgsViolation {
+                path: String::new(),
+                kind: TgsViolationKind::Uncompressed,
+            });
+        }
+        if data.len() > self.max_bytes {
+            violations.push(TgsViolation {
+                path: String::new(),
+                kind: TgsViolationKind::FileSize {
+                    bytes: data.len(),
+                    max: self.max_bytes,
+                },
+            });
+        }
+        violations.extend(self.check(&model));
+        Ok(violations)
+    }
+
+    /// Check the animation of a sticker, returning all of the violations
+    /// found. The file size is checked by [TgsProfile::check_file]
+    pub fn check(&self, model: &Model) -> Vec<TgsViolation> {
+        let mut violations = vec![];
+        if model.width != self.width || model.height != self.height {
+            violations.push(TgsViolation {
+                path: "w".to_string(),
+                kind: TgsViolationKind::Dimensions {
+                    width: model.width,
+                    height: model.height,
+                },
+            });
+        }
+        if model.frame_rate != self.frame_rate {
+            violations.push(TgsViolation {
+                path: "fr".to_string(),
+                kind: TgsViolationKind::FrameRate(model.frame_rate),
+            });
+        }
+        let duration = model.duration();
+        if duration.is_nan() || duration > self.max_duration {
+            violations.push(TgsViolation {
+                path: "op".to_string(),
+                kind: TgsViolationKind::Duration(duration),
+            });
+        }
+        for (index, asset) in model.assets.iter().enumerate() {
+            if let Asset::Media(_) = asset {
+                violations.push(TgsViolation {
+                    path: format!("assets[{}]", index),
+                    kind: TgsViolationKind::Media,
+                });
+            }
+        }
+        violations.extend(
+            analyze(model)
+                .into_iter()
+                .filter(|diagnostic| diagnostic.feature == Feature::Expression)
+                .map(|diagnostic| TgsViolation {
+                    path: diagnostic.path,
+                    kind: TgsViolationKind::Expression,
+                }),
+        );
+        violations
+    }
+}
@@ -2,14 +2,17 @@ use std::cell::RefCell;
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::rc::Rc;
 
-use crate::model::{Animated, Asset, Layer, LayerContent, MatteMode, Model, Shape};
+use crate::model::{Animated, Asset, Layer, LayerContent, MatteMode, Model, Shape, Vector2D};
+use glam::Vec3;
 use slotmap::SlotMap;
 
 use crate::font::FontDB;
 use crate::layer::frame::{FrameInfo, FrameTransformHierarchy};
 use crate::layer::hierarchy::TransformHierarchy;
-use crate::layer::staged::{ContentInfo, StagedLayer, TargetRef};
-use crate::prelude::{RenderableContent, StagedLayerMask};
+use crate::layer::staged::{ContentInfo, StagedLayer, TargetRef, TextSource};
+use crate::layer::text::TextFit;
+use crate::prelude::{LayerPath, RenderableContent, StagedLayerMask};
+use crate::resolver::AssetResolver;
 use crate::Error;
 
 slotmap::new_key_type! {
@@ -74,7 +77,11 @@ impl Timeline {
         self.store.get(id)
     }
 
-    pub(crate) fn new(model: &Model, fontdb: &FontDB, root_path: &str) -> Result<Self, Error> {
+    pub(crate) fn new(
+        model: &Model,
+        fontdb: &FontDB,
+        resolver: &dyn AssetResolver,
+    ) -> Result<Self, Error> {
         let mut timeline = Timeline {
             start_frame: 0.0,
             end_frame: 0.0,
@@ -88,13 +95,16 @@ impl Timeline {
         let mut layers = model
             .layers
             .iter()
+            .zip(LayerUsage::of_composition(&model.layers))
             .enumerate()
-            .map(|(index, layer)| LayerInfo {
+            .map(|(index, (layer, usage))| LayerInfo {
                 layer: layer.clone(),
                 zindex: index as f32,
                 child_index_window: 1.0,
                 target_ref: TargetRef::Layer(layer.id),
                 parent: None,
+                precomp: None,
+                usage,
                 parent_map: default_parent_map.clone(),
                 standby_map: default_standby_map.clone(),
                 time_remapping: layer.time_remapping(),
@@ -104,27 +114,43 @@ impl Timeline {
         let mut previous = None;
         while !layers.is_empty() {
             let LayerInfo {
-                layer,
+                mut layer,
                 zindex,
                 child_index_window,
                 target_ref,
                 parent,
+                precomp,
+                usage,
                 parent_map,
                 standby_map,
                 time_remapping,
             } = layers.pop_front().unwrap();
+            if layer.hidden && !usage.matte_source {
+                if !usage.parent {
+                    continue;
+                }
+                // Hidden layers still transform their children
+                layer.content = LayerContent::Empty;
+            }
             let index = layer.index;
             let parent_index = layer.parent_index;
             let mut assets = vec![];
             match &layer.content {
                 LayerContent::PreCompositionRef(r) => {
-                    match model.assets.iter().find(|asset| asset.id() == r.ref_id) {
+                    match model
+                        .assets
+                        .iter()
+                        .find(|asset| asset.id() == Some(r.ref_id.as_str()))
+                    {
                         Some(Asset::Precomposition(asset)) => {
                             let step = child_index_window / (asset.layers.len() as f32 + 1.0);
                             let default_parent_map: Rc<RefCell<HashMap<u32, Id>>> = Rc::default();
                             let default_standby_map: Rc<RefCell<HashMap<u32, Vec<Id>>>> =
                                 Rc::default();
-                            for (index, asset_layer) in asset.layers.iter().enumerate() {
+                            let usages = LayerUsage::of_composition(&asset.layers);
+                            for (index, (asset_layer, usage)) in
+                                asset.layers.iter().zip(usages).enumerate()
+                            {
                                 let asset_layer = asset_layer.clone();
                                 let zindex = index as f32 * step;
                                 assets.push(LayerInfo {
@@ -133,6 +159,8 @@ impl Timeline {
                                     child_index_window: step,
                                     target_ref: TargetRef::Asset(r.ref_id.clone()),
                                     parent: None,
+                                    precomp: None,
+                                    usage,
                                     standby_map: default_standby_map.clone(),
                                     parent_map: default_parent_map.clone(),
                                     time_remapping: None,
@@ -143,7 +171,11 @@ impl Timeline {
                     }
                 }
                 LayerContent::MediaRef(i) => {
-                    match model.assets.iter().find(|asset| asset.id() == i.ref_id) {
+                    match model
+                        .assets
+                        .iter()
+                        .find(|asset| asset.id() == Some(i.ref_id.as_str()))
+                    {
                         Some(Asset::Media(media)) => {
                             let content = LayerContent::Media(media.clone());
                             let layer = Layer::new(
@@ -158,6 +190,8 @@ impl Timeline {
                                 child_index_window: child_index_window / 2.0,
                                 target_ref: TargetRef::Asset(i.ref_id.clone()),
                                 parent: None,
+                                precomp: None,
+                                usage: LayerUsage::default(),
                                 parent_map: Default::default(),
                                 standby_map: Default::default(),
                                 time_remapping: None,
@@ -169,7 +203,7 @@ impl Timeline {
                 _ => {}
             }
 
-            let content = ContentInfo::from_layer(layer.clone(), model, fontdb, root_path)?;
+            let content = ContentInfo::from_layer(layer.clone(), model, fontdb, resolver)?;
             let mut ids = vec![];
             match content {
                 ContentInfo::Simple(c) => ids.push(timeline.add_item(c.into_stage_layer(&layer))),
@@ -177,14 +211,34 @@ impl Timeline {
                     if let Some(end) = keyframes.last_mut().map(|info| &mut info.end_frame) {
                         *end = layer.end_frame;
                     }
-                    for keyframe in keyframes {
-                        let mut layer = keyframe.content.into_stage_layer(&layer);
-                        layer.start_frame = keyframe.start_frame;
-                        layer.end_frame = keyframe.end_frame;
-                        ids.push(timeline.add_item(layer))
+                    for (index, keyframe) in keyframes.into_iter().enumerate() {
+                        let mut staged = keyframe.content.into_stage_layer(&layer);
+                        staged.start_frame = keyframe.start_frame;
+                        staged.end_frame = keyframe.end_frame;
+                        if let LayerContent::Text(data) = &layer.content {
+                            staged.text = Some(TextSource {
+                                data: data.clone(),
+                                masks: layer.masks_properties.clone(),
+                                keyframe: index,
+                            });
+                        }
+                        ids.push(timeline.add_item(staged))
                     }
                 }
-                ContentInfo::ContentWithMasks { content, masks } => {
+                ContentInfo::ContentWithMasks {
+                    content,
+                    mut masks,
+                    clip,
+                } => {
+                    let container = match precomp.and_then(|id| timeline.store.get(id)) {
+                        Some(precomp) => precomp.precomp_size.unwrap_or_default(),
+                        None => Vector2D::new(model.width as f32, model.height as f32),
+                    };
+                    // A clip covering all that shows of the containing composition
+                    // cuts nothing, and would only take a mask slot from the renderer
+                    if clip && !clips_container(&layer, container) {
+                        masks.clear();
+                    }
                     let (layers, matte_modes): (Vec<StagedLayer>, Vec<MatteMode>) = masks
                         .into_iter()
                         .map(|m| (m.0.into_stage_layer(&layer), m.1))
@@ -194,6 +248,9 @@ impl Timeline {
                         .into_iter()
                         .map(|mut layer| {
                             layer.is_mask = true;
+                            // Masks are not track matte targets, nor do they clip
+                            layer.matte_mode = None;
+                            layer.precomp_size = None;
                             timeline.add_item(layer)
                         })
                         .collect();
@@ -201,14 +258,18 @@ impl Timeline {
                         target_layer.mask_hierarchy.stack.push(StagedLayerMask {
                             id: *id,
                             mode: matte_mode,
+                            clip,
                         })
                     }
                     ids.push(timeline.add_item(target_layer));
                 }
             }
+            // Track mattes always come from the previous layer, not from the masks
+            // or keyframes staged for this one
+            let matte_source = previous;
             for id in &ids {
                 let matte_mode = timeline.store.get(*id).unwrap().matte_mode;
-                if let (Some(id), Some(mode)) = (previous, matte_mode) {
+                if let (Some(id), Some(mode)) = (matte_source, matte_mode) {
                     if mode != MatteMode::Normal {
                         let prev = timeline.store.get_mut(id).unwrap();
                         prev.is_mask = true;
@@ -217,23 +278,28 @@ impl Timeline {
                 let staged = timeline.store.get_mut(*id).unwrap();
                 staged.target = target_ref.clone();
                 staged.parent = parent;
+                staged.precomp = precomp;
                 staged.zindex = zindex;
                 staged.frame_rate = default_frame_rate;
                 staged.frame_transform.time_remapping = time_remapping.clone();
                 staged.frame_transform.frame_rate = default_frame_rate;
-                if let (Some(id), Some(mode)) = (previous, matte_mode) {
+                if let (Some(id), Some(mode)) = (matte_source, matte_mode) {
                     if mode != MatteMode::Normal {
-                        staged
-                            .mask_hierarchy
-                            .stack
-                            .push(StagedLayerMask { id, mode });
+                        staged.mask_hierarchy.stack.push(StagedLayerMask {
+                            id,
+                            mode,
+                            clip: false,
+                        });
                     }
                 }
                 previous = Some(*id);
             }
-            let id = previous.unwrap().clone();
+            let Some(id) = previous else {
+                continue;
+            };
             for mut info in assets {
                 info.parent = Some(id);
+                info.precomp = Some(id);
                 layers.push_back(info);
             }
             if let Some(ind) = index {
@@ -275,6 +341,63 @@ impl Timeline {
         Ok(timeline)
     }
 
+    /// Find staged layers addressed by `path`. Masks are not included
+    pub fn find(&self, path: &LayerPath) -> Vec<Id> {
+        let mut containers = vec![None];
+        let mut result = vec![];
+        for segment in path.segments() {
+            result = self
+                .store
+                .iter()
+                .filter(|(_, layer)| {
+                    !layer.is_mask
+                        && containers.contains(&layer.precomp)
+                        && LayerPath::matches(segment, layer)
+                })
+                .map(|(id, _)| id)
+                .collect::<Vec<_>>();
+            containers = result.iter().map(|id| Some(*id)).collect();
+        }
+        result
+    }
+
+    /// Show or hide layers addressed by `path`, returns ids of the affected
+    /// layers
+    pub fn set_visibility(&mut self, path: &LayerPath, visible: bool) -> Vec<Id> {
+        let ids = self.find(path);
+        for id in &ids {
+            if let Some(layer) = self.store.get_mut(*id) {
+                layer.hidden = !visible;
+            }
+        }
+        ids
+    }
+
+    /// Replaces the text of the text layers addressed by `path` and lays them
+    /// out again, returns ids of the affected layers
+    pub(crate) fn set_text(
+        &mut self,
+        path: &LayerPath,
+        value: &str,
+        fit: TextFit,
+        model: &Model,
+        fontdb: &FontDB,
+    ) -> Result<Vec<Id>, Error> {
+        let mut ids = vec![];
+        for id in self.find(path) {
+            let layer = match self.store.get_mut(id) {
+                Some(layer) => layer,
+                None => continue,
+            };
+            if let Some(source) = &layer.text {
+                layer.content =
+                    RenderableContent::from_text_source(source, value, fit, model, fontdb)?;
+                ids.push(id);
+            }
+        }
+        Ok(ids)
+    }
+
     /// Lottie's parenting does not share zindex, so we have to fix it to align
     /// to the usual transformation hierarchy logic in almost every renderer
     fn fix_zindex(&mut self, force_zindex_ids: HashSet<Id>) {
@@ -365,13 +488,86 @@ impl Timeline {
     }
 }
 
+/// Whether the bounds of precomp `layer` cut into `container`, the size of the
+/// composition holding it. Layers with a parent or an animated transform are
+/// assumed to cut into it
+fn clips_container(layer: &Layer, container: Vector2D) -> bool {
+    let size = match &layer.content {
+        LayerContent::PreCompositionRef(r) => Vector2D::new(r.width as f32, r.height as f32),
+        _ => return false,
+    };
+    let transform = layer.transform.clone().unwrap_or_default();
+    let animated = transform.anchor.as_ref().is_some_and(|a| a.is_animated())
+        || transform.position.as_ref().is_some_and(|p| p.is_animated())
+        || transform.scale.is_animated()
+        || transform.rotation.is_animated()
+        || transform.skew.as_ref().is_some_and(|s| s.is_animated())
+        || transform
+            .skew_axis
+            .as_ref()
+            .is_some_and(|s| s.is_animated());
+    if layer.parent_index.is_some() || animated {
+        return true;
+    }
+    // The clip is a parallelogram in the container, which covers the container
+    // if it holds all of its corners
+    let inverse = transform.initial_value().inverse();
+    [
+        Vector2D::zero(),
+        Vector2D::new(container.x, 0.0),
+        Vector2D::new(0.0, container.y),
+        container,
+    ]
+    .into_iter()
+    .any(|corner| {
+        let p = inverse.transform_point3(Vec3::new(corner.x, corner.y, 0.0));
+        const EPSILON: f32 = 1e-3;
+        p.x < -EPSILON || p.y < -EPSILON || p.x > size.x + EPSILON || p.y > size.y + EPSILON
+    })
+}
+
 struct LayerInfo {
     layer: Layer,
     zindex: f32,
     child_index_window: f32,
     target_ref: TargetRef,
     parent: Option<Id>,
+    precomp: Option<Id>,
+    usage: LayerUsage,
     parent_map: Rc<RefCell<HashMap<u32, Id>>>,
     standby_map: Rc<RefCell<HashMap<u32, Vec<Id>>>>,
     time_remapping: Option<Animated<f32>>,
 }
+
+/// How a layer is referenced by its siblings in the same composition
+#[derive(Clone, Copy, Default)]
+struct LayerUsage {
+    /// The next layer uses this layer as its track matte
+    matte_source: bool,
+    /// Some layer uses this layer as its parent
+    parent: bool,
+}
+
+impl LayerUsage {
+    fn of_composition(layers: &[Layer]) -> Vec<LayerUsage> {
+        let parents = layers
+            .iter()
+            .filter_map(|layer| layer.parent_index)
+            .collect::<HashSet<_>>();
+        layers
+            .iter()
+            .enumerate()
+            .map(|(index, layer)| LayerUsage {
+                matte_source: layers
+                    .get(index + 1)
+                    .and_then(|next| next.matte_mode)
+                    .map(|mode| mode != MatteMode::Normal)
+                    .unwrap_or(false),
+                parent: layer
+                    .index
+                    .map(|ind| parents.contains(&ind))
+                    .unwrap_or(false),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::model::*;
+use crate::visit::{walk, Visit};
+
+/// An invariant of the lottie format the staging code relies on
+#[derive(Debug, Clone, PartialEq)]
+pub enum ViolationKind {
+    /// Another layer of the composition has the same `ind`
+    DuplicateIndex(u32),
+    /// No layer of the composition has the `ind` of this `parent`
+    MissingParent(u32),
+    /// The layer with this `ind` is its own ancestor
+    ParentCycle(u32),
+    /// No asset has this `refId`
+    MissingAsset(String),
+    /// The asset with this `refId` is not of the kind the layer needs
+    AssetKind {
+        ref_id: String,
+        expected: &'static str,
+    },
+    /// No font of `fonts.list` has this name
+    MissingFont(String),
+    /// Keyframes are not sorted by time
+    UnorderedKeyframes,
+    /// Vertices and tangents of a path differ in count
+    BezierLengths {
+        vertices: usize,
+        in_tangents: usize,
+        out_tangents: usize,
+    },
+    /// The in point is not before the out point
+    FrameRange { start: f32, end: f32 },
+}
+
+impl fmt::Display for ViolationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ViolationKind::DuplicateIndex(index) => write!(f, "duplicate layer index {}", index),
+            ViolationKind::MissingParent(index) => write!(f, "no parent layer {}", index),
+            ViolationKind::ParentCycle(index) => write!(f, "layer {} is its own ancestor", index),
+            ViolationKind::MissingAsset(id) => write!(f, "no asset {}", id),
+            ViolationKind::AssetKind { ref_id, expected } => {
+                write!(f, "asset {} is not a {}", ref_id, expected)
+            }
+            ViolationKind::MissingFont(name) => write!(f, "no font {} in the font list", name),
+            ViolationKind::UnorderedKeyframes => f.write_str("keyframes are not in time order"),
+            ViolationKind::BezierLengths {
+                vertices,
+                in_tangents,
+                out_tangents,
+            } => write!(
+                f,
+                "{} vertices for {} in tangents and {} out tangents",
+                vertices, in_tangents, out_tangents
+            ),
+            ViolationKind::FrameRange { start, end } => {
+                write!(f, "in point {} is not before out point {}", start, end)
+            }
+        }
+    }
+}
+
+/// A broken invariant, at the JSON path of the value breaking it, e.g.
+/// `layers[3].parent`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub path: String,
+    pub kind: ViolationKind,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}`: {}", self.path, self.kind)
+    }
+}
+
+/// Check the structural invariants of `model`, returning all of the
+/// violations found, e.g. to check animations in CI before shipping them
+pub fn validate(model: &Model) -> Vec<Violation> {
+    let mut validator = Validator {
+        model,
+        violations: vec![],
+    };
+    if let Some(kind) = frame_range(model.start_frame, model.end_frame) {
+        validator.push("op".to_string(), kind);
+    }
+    walk(model, &mut validator);
+    validator.violations
+}
+
+fn frame_range(start: f32, end: f32) -> Option<ViolationKind> {
+    if start < end {
+        None
+    } else {
+        Some(ViolationKind::FrameRange { start, end })
+    }
+}
+
+struct Validator<'a> {
+    model: &'a Model,
+    violations: Vec<Violation>,
+}
+
+impl Validator<'_> {
+    fn push(&mut self, path: String, kind: ViolationKind) {
+        self.violations.push(Violation { path, kind });
+    }
+
+    fn asset(&mut self, path: &str, ref_id: &str, precomp: bool) {
+        let asset = self
+            .model
+            .assets
+            .iter()
+            .find(|asset| asset.id() == Some(ref_id));
+        let expected = match asset {
+            None => {
+                let kind = ViolationKind::MissingAsset(ref_id.to_string());
+                return self.push(format!("{}.refId", path), kind);
+            }
+            Some(Asset::Precomposition(_)) if !precomp => "media",
+            Some(Asset::Media(_)) if precomp => "precomposition",
+            Some(_) => return,
+        };
+        let kind = ViolationKind::AssetKind {
+            ref_id: ref_id.to_string(),
+            expected,
+        };
+        self.push(format!("{}.refId", path), kind);
+    }
+}
+
+impl Visit for Validator<'_> {
+    fn composition(&mut self, path: &str, layers: &[Layer]) {
+        let mut indices = HashMap::new();
+        for (position, layer) in layers.iter().enumerate() {
+            if let Some(index) = layer.index {
+                if indices.contains_key(&index) {
+                    let kind = ViolationKind::DuplicateIndex(index);
+                    self.push(format!("{}[{}].ind", path, position), kind);
+                } else {
+                    indices.insert(index, position);
+                }
+            }
+        }
+        for (position, layer) in layers.iter().enumerate() {
+            let Some(parent) = layer.parent_index else {
+                continue;
+            };
+            if !indices.contains_key(&parent) {
+                let kind = ViolationKind::MissingParent(parent);
+                self.push(format!("{}[{}].parent", path, position), kind);
+                continue;
+            }
+            // a layer is in a cycle when walking up its parents leads back to
+            // it, layers leading into a cycle are left to its members
+            let mut ancestor = Some(parent);
+            for _ in 0..layers.len() {
+                let Some(index) = ancestor else {
+                    break;
+                };
+                if Some(index) == layer.index {
+                    let kind = ViolationKind::ParentCycle(index);
+                    self.push(format!("{}[{}].parent", path, position), kind);
+                    break;
+                }
+                ancestor = indices
+                    .get(&index)
+                    .and_then(|position| layers[*position].parent_index);
+            }
+        }
+    }
+
+    fn layer(&mut self, path: &str, layer: &Layer) {
+        if let Some(kind) = frame_range(layer.start_frame, layer.end_frame) {
+            self.push(format!("{}.op", path), kind);
+        }
+        match &layer.content {
+            LayerContent::PreCompositionRef(r) => self.asset(path, &r.ref_id, true),
+            LayerContent::MediaRef(r) => self.asset(path, &r.ref_id, false),
+            LayerContent::Text(text) => {
+                for (index, keyframe) in text.document.keyframes.iter().enumerate() {
+                    let name = &keyframe.start_value.font_name;
+                    if self.model.font(name).is_none() {
+                        let kind = ViolationKind::MissingFont(name.clone());
+                        self.push(format!("{}.t.d.k[{}].s.f", path, index), kind);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn animated<T>(&mut self, path: &str, key: &str, animated: &Animated<T>) {
+        let ordered = animated
+            .keyframes
+            .windows(2)
+            .all(|pair| pair[0].start_frame <= pair[1].start_frame);
+        if !ordered {
+            let kind = ViolationKind::UnorderedKeyframes;
+            self.push(format!("{}.{}.k", path, key), kind);
+        }
+    }
+
+    fn beziers(&mut self, path: &str, key: &str, animated: &Animated<Vec<Bezier>>) {
+        let mismatch = animated
+            .keyframes
+            .iter()
+            .flat_map(|keyframe| &keyframe.start_value)
+            .find(|b| {
+                b.in_tangent.len() != b.verticies.len() || b.out_tangent.len() != b.verticies.len()
+            });
+        if let Some(b) = mismatch {
+            let kind = ViolationKind::BezierLengths {
+                vertices: b.verticies.len(),
+                in_tangents: b.in_tangent.len(),
+                out_tangents: b.out_tangent.len(),
+            };
+            self.push(format!("{}.{}.k", path, key), kind);
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use crate::model::*;
+
+/// Callbacks of [walk], each given the JSON path of the value it visits,
+/// e.g. `layers[3].shapes[1].it[2]`
+pub(crate) trait Visit {
+    /// Layers of the root composition or of a precomposition
+    fn composition(&mut self, _path: &str, _layers: &[Layer]) {}
+
+    fn layer(&mut self, _path: &str, _layer: &Layer) {}
+
+    fn mask(&mut self, _path: &str, _mask: &Mask) {}
+
+    fn transform(&mut self, _path: &str, _transform: &Transform) {}
+
+    fn shape(&mut self, _path: &str, _shape: &Shape) {}
+
+    /// Animated property `key` of the value at `path`
+    fn animated<T>(&mut self, _path: &str, _key: &str, _animated: &Animated<T>) {}
+
+    /// Shape keyframes of a path or a mask, also visited by
+    /// [Visit::animated]
+    fn beziers(&mut self, _path: &str, _key: &str, _animated: &Animated<Vec<Bezier>>) {}
+}
+
+/// Visit the compositions of `model`, their layers, shapes and animated
+/// properties in document order
+pub(crate) fn walk(model: &Model, visitor: &mut impl Visit) {
+    layers("layers", &model.layers, visitor);
+    for (index, asset) in model.assets.iter().enumerate() {
+        if let Asset::Precomposition(precomp) = asset {
+            layers(
+                &format!("assets[{}].layers", index),
+                &precomp.layers,
+                visitor,
+            );
+        }
+    }
+}
+
+fn layers(path: &str, layers: &[Layer], visitor: &mut impl Visit) {
+    visitor.composition(path, layers);
+    for (index, layer) in layers.iter().enumerate() {
+        let path = format!("{}[{}]", path, index);
+        visitor.layer(&path, layer);
+        if let Some(transform) = &layer.transform {
+            self::transform(&format!("{}.ks", path), transform, visitor);
+        }
+        if layer.has_mask {
+            for (index, mask) in layer.masks_properties.iter().enumerate() {
+                let path = format!("{}.masksProperties[{}]", path, index);
+                visitor.mask(&path, mask);
+                visitor.animated(&path, "pt", &mask.points);
+                visitor.beziers(&path, "pt", &mask.points);
+                visitor.animated(&path, "o", &mask.opacity);
+                if let Some(expand) = &mask.expand {
+                    visitor.animated(&path, "e", expand);
+                }
+            }
+        }
+        match &layer.content {
+            LayerContent::Shape(group) => {
+                shapes(&format!("{}.shapes", path), &group.shapes, visitor)
+            }
+            LayerContent::PreCompositionRef(r) => {
+                if let Some(time_remapping) = &r.time_remapping {
+                    visitor.animated(&path, "tm", time_remapping);
+                }
+            }
+            LayerContent::Text(data) => text(&format!("{}.t", path), data, visitor),
+            _ => {}
+        }
+    }
+}
+
+fn transform(path: &str, transform: &Transform, visitor: &mut impl Visit) {
+    visitor.transform(path, transform);
+    transform_properties(path, transform, visitor);
+}
+
+/// Animated properties of a transform, also used for the transforms of text
+/// animators, which are not visited by [Visit::transform]
+fn transform_properties(path: &str, transform: &Transform, visitor: &mut impl Visit) {
+    if let Some(anchor) = &transform.anchor {
+        visitor.animated(path, "a", anchor);
+    }
+    if let Some(position) = &transform.position {
+        visitor.animated(path, "p", position);
+    }
+    visitor.animated(path, "s", &transform.scale);
+    visitor.animated(path, "r", &transform.rotation);
+    visitor.animated(path, "o", &transform.opacity);
+    if let Some(skew) = &transform.skew {
+        visitor.animated(path, "sk", skew);
+    }
+    if let Some(skew_axis) = &transform.skew_axis {
+        visitor.animated(path, "sa", skew_axis);
+    }
+}
+
+/// Animated properties of the animators, alignment and path of a text layer,
+/// the document keyframes are left to [Visit::layer]
+fn text(path: &str, text: &TextAnimationData, visitor: &mut impl Visit) {
+    for (index, range) in text.ranges.iter().enumerate() {
+        let path = format!("{}.a[{}]", path, index);
+        let selector = &range.selector;
+        let selector_path = format!("{}.s", path);
+        visitor.animated(&selector_path, "xe", &selector.max_ease);
+        visitor.animated(&selector_path, "ne", &selector.min_ease);
+        visitor.animated(&selector_path, "a", &selector.max_amount);
+        let optional = [
+            ("o", &selector.offset),
+            ("sm", &selector.selector_smoothness),
+            ("s", &selector.start),
+            ("e", &selector.end),
+        ];
+        for (key, animated) in optional {
+            if let Some(animated) = animated {
+                visitor.animated(&selector_path, key, animated);
+            }
+        }
+        if let Some(style) = &range.style {
+            let path = format!("{}.a", path);
+            if let Some(transform) = &style.transform {
+                transform_properties(&path, transform, visitor);
+            }
+            for (key, color) in [("sc", &style.stroke_color), ("fc", &style.fill_color)] {
+                if let Some(color) = color {
+                    visitor.animated(&path, key, color);
+                }
+            }
+            let optional = [
+                ("sw", &style.stroke_width),
+                ("sh", &style.stroke_hue),
+                ("ss", &style.stroke_saturation),
+                ("sb", &style.stroke_brightness),
+                ("so", &style.stroke_opacity),
+                ("fh", &style.fill_hue),
+                ("fs", &style.fill_saturation),
+                ("fb", &style.fill_brightness),
+                ("t", &style.letter_spacing),
+                ("bl", &style.blur),
+                ("ls", &style.line_spacing),
+            ];
+            for (key, animated) in optional {
+                if let Some(animated) = animated {
+                    visitor.animated(&path, key, animated);
+                }
+            }
+        }
+    }
+    if let Some(alignment) = &text.options.alignment {
+        visitor.animated(&format!("{}.m", path), "a", alignment);
+    }
+    let follow_path = &text.follow_path;
+    let optional = [
+        ("f", &follow_path.first_margin),
+        ("l", &follow_path.last_margin),
+        ("r", &follow_path.reversed),
+        ("a", &follow_path.force_alignment),
+        ("p", &follow_path.perpendicular),
+    ];
+    for (key, animated) in optional {
+        if let Some(animated) = animated {
+            visitor.animated(&format!("{}.p", path), key, animated);
+        }
+    }
+}
+
+fn shapes(path: &str, shapes: &[ShapeLayer], visitor: &mut impl Visit) {
+    for (index, shape) in shapes.iter().enumerate() {
+        if shape.hidden {
+            continue;
+        }
+        let path = format!("{}[{}]", path, index);
+        visitor.shape(&path, &shape.shape);
+        match &shape.shape {
+            Shape::Rectangle(rect) => {
+                visitor.animated(&path, "p", &rect.position);
+                visitor.animated(&path, "s", &rect.size);
+                visitor.animated(&path, "r", &rect.radius);
+            }
+            Shape::Ellipse(ellipse) => {
+                visitor.animated(&path, "p", &ellipse.position);
+                visitor.animated(&path, "s", &ellipse.size);
+            }
+            Shape::PolyStar(star) => {
+                visitor.animated(&path, "p", &star.position);
+                visitor.animated(&path, "or", &star.outer_radius);
+                visitor.animated(&path, "os", &star.outer_roundness);
+                if let Some(inner_radius) = &star.inner_radius {
+                    visitor.animated(&path, "ir", inner_radius);
+                }
+                if let Some(inner_roundness) = &star.inner_roundness {
+                    visitor.animated(&path, "is", inner_roundness);
+                }
+                visitor.animated(&path, "r", &star.rotation);
+                visitor.animated(&path, "pt", &star.points);
+            }
+            Shape::Path { d, .. } => {
+                visitor.animated(&path, "ks", d);
+                visitor.beziers(&path, "ks", d);
+            }
+            Shape::Fill(fill) => {
+                visitor.animated(&path, "o", &fill.opacity);
+                visitor.animated(&path, "c", &fill.color);
+            }
+            Shape::Stroke(stroke) => {
+                visitor.animated(&path, "o", &stroke.opacity);
+                visitor.animated(&path, "w", &stroke.width);
+                visitor.animated(&path, "c", &stroke.color);
+            }
+            Shape::GradientFill(fill) => {
+                visitor.animated(&path, "o", &fill.opacity);
+                visitor.animated(&path, "s", &fill.gradient.start);
+                visitor.animated(&path, "e", &fill.gradient.end);
+                visitor.animated(&format!("{}.g", path), "k", &fill.gradient.colors.colors);
+            }
+            Shape::GradientStroke(stroke) => {
+                visitor.animated(&path, "o", &stroke.opacity);
+                visitor.animated(&path, "w", &stroke.width);
+                visitor.animated(&path, "s", &stroke.gradient.start);
+                visitor.animated(&path, "e", &stroke.gradient.end);
+                visitor.animated(&format!("{}.g", path), "k", &stroke.gradient.colors.colors);
+            }
+            Shape::Group { shapes: group } => self::shapes(&format!("{}.it", path), group, visitor),
+            Shape::Transform(t) => transform(&path, t, visitor),
+            Shape::Trim(trim) => {
+                visitor.animated(&path, "s", &trim.start);
+                visitor.animated(&path, "e", &trim.end);
+                visitor.animated(&path, "o", &trim.offset);
+            }
+            _ => {}
+        }
+    }
+}
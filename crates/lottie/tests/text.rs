@@ -0,0 +1,733 @@
+use std::fs;
+
+use fontkit::FontKit;
+use lottie::prelude::*;
+use lottie::{DenyResolver, Error, Feature, FontDB, Lottie};
+
+/// A glyph of the staged text layers of an animation
+struct Glyph {
+    /// Char the glyph is named after, the first of its cluster
+    c: String,
+    line: usize,
+    /// Baseline of the line of the glyph
+    line_y: f32,
+    /// Left edge of the glyph in its line
+    x: f32,
+    /// Point animators transform the glyph around, relative to its line
+    pivot: Vector2D,
+    shapes: Vec<ShapeLayer>,
+}
+
+fn find_transform(shapes: &[ShapeLayer]) -> Option<Transform> {
+    shapes.iter().find_map(|shape| match &shape.shape {
+        Shape::Transform(transform) => Some(transform.clone()),
+        Shape::Group { shapes } => find_transform(shapes),
+        _ => None,
+    })
+}
+
+fn glyphs(lottie: &Lottie) -> Vec<Glyph> {
+    let mut result = vec![];
+    for layer in lottie.timeline().items() {
+        let RenderableContent::Shape(group) = &layer.content else {
+            continue;
+        };
+        let Some(Shape::Group { shapes: lines }) = group.shapes.first().map(|s| &s.shape) else {
+            continue;
+        };
+        let lines = lines.iter().filter_map(|line| match &line.shape {
+            Shape::Group { shapes } => Some(shapes),
+            _ => None,
+        });
+        for (line, shapes) in lines.enumerate() {
+            let line_y = shapes
+                .iter()
+                .find_map(|shape| match &shape.shape {
+                    Shape::Transform(transform) => transform.position.clone(),
+                    _ => None,
+                })
+                .map_or(0.0, |position| position.initial_value().y);
+            for glyph in shapes {
+                let Shape::Group { shapes } = &glyph.shape else {
+                    continue;
+                };
+                let transform = find_transform(shapes).unwrap();
+                let value = |v: &Option<Animated<Vector2D>>| {
+                    v.as_ref().map(|v| v.initial_value()).unwrap_or_default()
+                };
+                let pivot = value(&transform.position);
+                result.push(Glyph {
+                    c: glyph.name.clone().unwrap_or_default(),
+                    line,
+                    line_y,
+                    x: pivot.x - value(&transform.anchor).x,
+                    pivot,
+                    shapes: shapes.clone(),
+                });
+            }
+        }
+    }
+    result
+}
+
+fn chars(glyphs: &[Glyph]) -> String {
+    glyphs.iter().map(|glyph| glyph.c.as_str()).collect()
+}
+
+/// Names of the styles of the groups a glyph is painted with, top first
+fn paint_order(glyph: &Glyph) -> Vec<&'static str> {
+    glyph
+        .shapes
+        .iter()
+        .filter_map(|shape| match &shape.shape {
+            Shape::Group { shapes } => shapes.iter().find_map(|shape| match &shape.shape {
+                Shape::Fill(_) => Some("fill"),
+                Shape::Stroke(_) => Some("stroke"),
+                _ => None,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_text_stroke_order() -> Result<(), Error> {
+    let json = fs::read_to_string("../../fixtures/ui/text/text_range_letter_spacing.json")?;
+    for stroke_over_fill in [true, false] {
+        let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        for keyframe in value["layers"][0]["t"]["d"]["k"].as_array_mut().unwrap() {
+            keyframe["s"]["of"] = stroke_over_fill.into();
+        }
+        let model = Model::from_reader(value.to_string().as_bytes())?;
+        let lottie = Lottie::new(model, FontKit::new(), &DenyResolver)?;
+        let glyphs = glyphs(&lottie);
+        assert_eq!(&chars(&glyphs)[..11], "Hello World");
+        let expected = if stroke_over_fill {
+            ["stroke", "fill"]
+        } else {
+            ["fill", "stroke"]
+        };
+        for glyph in glyphs.iter().filter(|glyph| glyph.c != " ") {
+            assert_eq!(paint_order(glyph), expected, "{}", glyph.c);
+        }
+    }
+    Ok(())
+}
+
+/// A 1×1 PNG image
+const PNG: &[u8] = &[
+    0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1f, 0x15, 0xc4,
+    0x89, 0x00, 0x00, 0x00, 0x0a, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0x00, 0x01, 0x00, 0x00,
+    0x05, 0x00, 0x01, 0x0d, 0x0a, 0x2d, 0xb4, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae,
+    0x42, 0x60, 0x82,
+];
+
+/// A minimal TrueType font of 1000 units per em, its glyphs rectangles as
+/// wide as their advance and `height` units tall. Glyph `0` is an empty
+/// `.notdef`, the glyphs of `chars` follow in order
+struct TestFont {
+    family: &'static str,
+    height: i16,
+    /// Chars and their advance width
+    chars: Vec<(char, u16)>,
+    /// Pair of chars the `liga` feature substitutes with a glyph of that
+    /// advance
+    ligature: Option<(char, char, u16)>,
+    /// Char drawn as `COLR` layers, the outlines of the first two chars in
+    /// red then blue
+    color: Option<char>,
+    /// Char drawn as an `sbix` PNG of 100 pixels per em
+    bitmap: Option<char>,
+}
+
+impl TestFont {
+    fn new(family: &'static str, height: i16, chars: &str, advance: u16) -> Self {
+        TestFont {
+            family,
+            height,
+            chars: chars
+                .chars()
+                .map(|c| (c, if c == ' ' { advance / 2 } else { advance }))
+                .collect(),
+            ligature: None,
+            color: None,
+            bitmap: None,
+        }
+    }
+
+    fn glyph_id(&self, c: char) -> u16 {
+        self.chars.iter().position(|(ch, _)| *ch == c).unwrap() as u16 + 1
+    }
+
+    fn build(&self) -> Vec<u8> {
+        let mut advances = vec![500];
+        advances.extend(self.chars.iter().map(|(_, advance)| *advance));
+        if let Some((_, _, advance)) = self.ligature {
+            advances.push(advance);
+        }
+        let count = advances.len() as u16;
+
+        let mut glyf = vec![];
+        let mut loca = vec![];
+        for (index, advance) in advances.iter().enumerate() {
+            loca.extend((glyf.len() as u32).to_be_bytes());
+            let blank = index == 0 || self.chars.get(index - 1).map(|(c, _)| *c) == Some(' ');
+            if !blank {
+                let (w, h) = (*advance as i16, self.height);
+                for value in [1, 0, 0, w, h, 3, 0] {
+                    glyf.extend(i16::to_be_bytes(value));
+                }
+                glyf.extend([1, 1, 1, 1]);
+                for delta in [0, w, 0, -w, 0, 0, h, 0] {
+                    glyf.extend(i16::to_be_bytes(delta));
+                }
+            }
+        }
+        loca.extend((glyf.len() as u32).to_be_bytes());
+
+        let mut tables: Vec<(&[u8; 4], Vec<u8>)> = vec![];
+        let mut head = vec![
+            0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0x5f, 0x0f, 0x3c, 0xf5, 0, 0,
+        ];
+        head.extend(1000u16.to_be_bytes());
+        head.extend([0; 16]);
+        for value in [0, 0, 1000, self.height, 0, 8, 2, 1, 0] {
+            head.extend(i16::to_be_bytes(value));
+        }
+        tables.push((b"head", head));
+        let mut hhea = vec![0, 1, 0, 0];
+        for value in [800, -200, 0, 1000, 0, 0, 1000, 1, 0, 0, 0, 0, 0, 0, 0] {
+            hhea.extend(i16::to_be_bytes(value));
+        }
+        hhea.extend(count.to_be_bytes());
+        tables.push((b"hhea", hhea));
+        let mut maxp = vec![0, 0, 0x50, 0];
+        maxp.extend(count.to_be_bytes());
+        tables.push((b"maxp", maxp));
+        let hmtx = advances
+            .iter()
+            .flat_map(|advance| [advance.to_be_bytes(), [0, 0]].concat())
+            .collect();
+        tables.push((b"hmtx", hmtx));
+        tables.push((b"loca", loca));
+        tables.push((b"glyf", glyf));
+
+        // format 12 subtable for the Windows Unicode full repertoire
+        let mut mapping = self
+            .chars
+            .iter()
+            .map(|(c, _)| (*c as u32, self.glyph_id(*c) as u32))
+            .collect::<Vec<_>>();
+        mapping.sort();
+        let mut cmap = vec![0, 0, 0, 1, 0, 3, 0, 10, 0, 0, 0, 12, 0, 12, 0, 0];
+        cmap.extend((16 + 12 * mapping.len() as u32).to_be_bytes());
+        cmap.extend([0, 0, 0, 0]);
+        cmap.extend((mapping.len() as u32).to_be_bytes());
+        for (c, id) in mapping {
+            for value in [c, c, id] {
+                cmap.extend(value.to_be_bytes());
+            }
+        }
+        tables.push((b"cmap", cmap));
+
+        let full_name = format!("{} Regular", self.family);
+        let postscript_name = format!("{}-Regular", self.family.replace(' ', ""));
+        let names = [
+            (1, self.family),
+            (2, "Regular"),
+            (4, full_name.as_str()),
+            (6, postscript_name.as_str()),
+            (16, self.family),
+        ];
+        let mut name = vec![0, 0];
+        name.extend((names.len() as u16).to_be_bytes());
+        name.extend((6 + 12 * names.len() as u16).to_be_bytes());
+        let mut strings = vec![];
+        for (id, value) in names {
+            let value = value
+                .encode_utf16()
+                .flat_map(u16::to_be_bytes)
+                .collect::<Vec<_>>();
+            for field in [3, 1, 0x409, id, value.len() as u16, strings.len() as u16] {
+                name.extend(u16::to_be_bytes(field));
+            }
+            strings.extend(value);
+        }
+        name.extend(strings);
+        tables.push((b"name", name));
+
+        if let Some((first, second, _)) = self.ligature {
+            // DFLT script and liga feature, with a single ligature lookup
+            let mut gsub = vec![];
+            for value in [1, 0, 10, 30, 44] {
+                gsub.extend(u16::to_be_bytes(value));
+            }
+            gsub.extend([0, 1]);
+            gsub.extend(b"DFLT");
+            for value in [8, 4, 0, 0, 0xffff, 1, 0] {
+                gsub.extend(u16::to_be_bytes(value));
+            }
+            gsub.extend([0, 1]);
+            gsub.extend(b"liga");
+            for value in [8, 0, 1, 0] {
+                gsub.extend(u16::to_be_bytes(value));
+            }
+            for value in [1, 4, 4, 0, 1, 8, 1, 8, 1, 14, 1, 1] {
+                gsub.extend(u16::to_be_bytes(value));
+            }
+            gsub.extend(self.glyph_id(first).to_be_bytes());
+            for value in [1, 4, count - 1, 2, self.glyph_id(second)] {
+                gsub.extend(u16::to_be_bytes(value));
+            }
+            tables.push((b"GSUB", gsub));
+        }
+
+        if let Some(c) = self.color {
+            let mut colr = vec![0, 0, 0, 1, 0, 0, 0, 14, 0, 0, 0, 20, 0, 2];
+            for value in [self.glyph_id(c), 0, 2, 1, 0, 2, 1] {
+                colr.extend(u16::to_be_bytes(value));
+            }
+            tables.push((b"COLR", colr));
+            // palette of red and blue, in BGRA
+            let mut cpal = vec![0, 0, 0, 2, 0, 1, 0, 2, 0, 0, 0, 14, 0, 0];
+            cpal.extend([0, 0, 255, 255, 255, 0, 0, 255]);
+            tables.push((b"CPAL", cpal));
+        }
+
+        if let Some(c) = self.bitmap {
+            let mut sbix = vec![0, 1, 0, 1, 0, 0, 0, 1, 0, 0, 0, 12, 0, 100, 0, 72];
+            let data = [&[0, 0, 0, 0][..], b"png ", PNG].concat();
+            let start = 4 + 4 * (count as u32 + 1);
+            for index in 0..=count {
+                let offset = if index > self.glyph_id(c) {
+                    data.len() as u32
+                } else {
+                    0
+                };
+                sbix.extend((start + offset).to_be_bytes());
+            }
+            sbix.extend(data);
+            tables.push((b"sbix", sbix));
+        }
+
+        tables.sort_by_key(|(tag, _)| **tag);
+        let mut font = vec![0, 1, 0, 0];
+        font.extend((tables.len() as u16).to_be_bytes());
+        font.extend([0; 6]);
+        let mut offset = 12 + 16 * tables.len();
+        let mut data = vec![];
+        for (tag, table) in &tables {
+            font.extend(*tag);
+            font.extend([0; 4]);
+            font.extend((offset as u32).to_be_bytes());
+            font.extend((table.len() as u32).to_be_bytes());
+            data.extend(table);
+            data.resize(data.len().next_multiple_of(4), 0);
+            offset = 12 + 16 * tables.len() + data.len();
+        }
+        font.extend(data);
+        font
+    }
+}
+
+/// An animation of a single text layer, with `doc` holding the properties
+/// of its document beside the text, font and size, and `m` its more options
+fn text_animation(fontdb: FontDB, text: &str, doc: serde_json::Value) -> Result<Lottie, Error> {
+    let mut document = serde_json::json!({
+        "t": text,
+        "f": "TestSans-Regular",
+        "s": 100,
+        "j": 0,
+        "tr": 0,
+        "fc": [0, 0, 0],
+    });
+    for (key, value) in doc
+        .as_object()
+        .unwrap()
+        .iter()
+        .filter(|(key, _)| *key != "m")
+    {
+        document[key] = value.clone();
+    }
+    let json = serde_json::json!({
+        "v": "5.7.0",
+        "fr": 30,
+        "ip": 0,
+        "op": 30,
+        "w": 1000,
+        "h": 1000,
+        "fonts": {"list": [
+            {"fName": "TestSans-Regular", "fFamily": "Test Sans", "fStyle": "Regular", "ascent": 80}
+        ]},
+        "layers": [{
+            "ty": 5,
+            "nm": "Text",
+            "ind": 1,
+            "ip": 0,
+            "op": 30,
+            "st": 0,
+            "ks": {},
+            "t": {
+                "d": {"k": [{"s": document, "t": 0}]},
+                "p": {},
+                "m": doc.get("m").cloned().unwrap_or(serde_json::json!({"g": 1})),
+                "a": []
+            }
+        }]
+    });
+    let model = Model::from_reader(json.to_string().as_bytes())?;
+    Lottie::with_fontdb(model, fontdb, &DenyResolver)
+}
+
+/// A font database holding `font` as `Test Sans`, without fallbacks
+fn test_fontdb(font: &TestFont) -> FontDB {
+    let mut fontdb = FontDB::default();
+    fontdb.register_font("Test Sans", font.build()).unwrap();
+    fontdb.set_fallbacks(vec![]);
+    fontdb
+}
+
+fn latin() -> TestFont {
+    TestFont::new(
+        "Test Sans",
+        700,
+        " abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ",
+        500,
+    )
+}
+
+/// Left edge of the first glyph and right edge of the last glyph of a line
+fn line_extent(glyphs: &[Glyph], line: usize) -> (f32, f32) {
+    let mut line = glyphs
+        .iter()
+        .filter(|glyph| glyph.line == line && glyph.c != " ");
+    let first = line.next().unwrap();
+    let last = line.next_back().unwrap_or(first);
+    // Glyphs are 50 wide at size 100
+    (first.x, last.x + 50.0)
+}
+
+#[test]
+fn test_text_box_wrapping() -> Result<(), Error> {
+    let font = latin();
+    let text = "aaa bbb ccc";
+    let layout_doc = |doc| -> Result<Vec<Glyph>, Error> {
+        Ok(glyphs(&text_animation(test_fontdb(&font), text, doc)?))
+    };
+    let layout = |justify: u8| {
+        layout_doc(serde_json::json!({"j": justify, "sz": [400, 500], "ps": [10, 20]}))
+    };
+
+    let glyphs = layout(0)?;
+    assert_eq!(chars(&glyphs), "aaa bbb ccc");
+    assert_eq!(glyphs.iter().map(|glyph| glyph.line).max(), Some(1));
+    // The first baseline is one ascent below the box top
+    assert_eq!(glyphs[0].line_y, 100.0);
+    assert_eq!(glyphs[8].line_y, 200.0);
+    assert_eq!(line_extent(&glyphs, 0), (10.0, 335.0));
+    assert_eq!(line_extent(&glyphs, 1), (10.0, 160.0));
+
+    // Justified lines are stretched to the box width, except for the last
+    // line which is aligned on its own
+    for (justify, last_line) in [(3, (10.0, 160.0)), (4, (260.0, 410.0)), (5, (135.0, 285.0))] {
+        let glyphs = layout(justify)?;
+        assert_eq!(line_extent(&glyphs, 0), (10.0, 410.0), "{}", justify);
+        assert_eq!(glyphs[4].x, 260.0, "{}", justify);
+        assert_eq!(line_extent(&glyphs, 1), last_line, "{}", justify);
+    }
+
+    // Words wider than the box are broken between chars
+    let doc = serde_json::json!({"sz": [400, 500]});
+    let lottie = text_animation(test_fontdb(&font), "aaaaaaaaaa b", doc)?;
+    let lines = self::glyphs(&lottie)
+        .iter()
+        .map(|glyph| glyph.line)
+        .collect::<Vec<_>>();
+    assert_eq!(lines, [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1]);
+
+    // Lines below the box are left out
+    let doc = serde_json::json!({"sz": [400, 150]});
+    let glyphs = layout_doc(doc)?;
+    assert_eq!(chars(&glyphs), "aaa bbb ");
+    Ok(())
+}
+
+#[test]
+fn test_text_line_height_tracking_caps() -> Result<(), Error> {
+    let font = latin();
+    let layout = |text: &str, doc| -> Result<Vec<Glyph>, Error> {
+        Ok(glyphs(&text_animation(test_fontdb(&font), text, doc)?))
+    };
+
+    // Lines are one font height apart unless `lh` is set
+    let glyphs = layout("ab\rcd", serde_json::json!({}))?;
+    assert_eq!(chars(&glyphs), "abcd");
+    assert_eq!(glyphs[2].line_y - glyphs[0].line_y, 100.0);
+    let glyphs = layout("ab\rcd", serde_json::json!({"lh": 150}))?;
+    assert_eq!(glyphs[2].line_y - glyphs[0].line_y, 150.0);
+
+    // Tracking is in thousandths of an em
+    let glyphs = layout("abc", serde_json::json!({"tr": 100}))?;
+    let x = glyphs.iter().map(|glyph| glyph.x).collect::<Vec<_>>();
+    assert_eq!(x, [0.0, 60.0, 120.0]);
+
+    let glyphs = layout("aBc", serde_json::json!({"ca": 1}))?;
+    assert_eq!(chars(&glyphs), "ABC");
+    let x = glyphs.iter().map(|glyph| glyph.x).collect::<Vec<_>>();
+    assert_eq!(x, [0.0, 50.0, 100.0]);
+
+    // Small caps draw lowercase chars as smaller capitals
+    let glyphs = layout("aBc", serde_json::json!({"ca": 2}))?;
+    assert_eq!(chars(&glyphs), "ABC");
+    let x = glyphs.iter().map(|glyph| glyph.x).collect::<Vec<_>>();
+    assert_eq!(x, [0.0, 35.0, 85.0]);
+    Ok(())
+}
+
+#[test]
+fn test_text_grouping_anchors() -> Result<(), Error> {
+    let font = latin();
+    let layout = |options| -> Result<Vec<Glyph>, Error> {
+        let doc = serde_json::json!({"m": options});
+        Ok(glyphs(&text_animation(
+            test_fontdb(&font),
+            "ab cd\ref",
+            doc,
+        )?))
+    };
+    let pivots = |glyphs: &[Glyph]| {
+        glyphs
+            .iter()
+            .filter(|glyph| glyph.c != " ")
+            .map(|glyph| (glyph.pivot.x, glyph.line_y + glyph.pivot.y))
+            .collect::<Vec<_>>()
+    };
+
+    let glyphs = layout(serde_json::json!({"g": 1}))?;
+    assert_eq!(chars(&glyphs), "ab cdef");
+    assert_eq!(
+        pivots(&glyphs),
+        [
+            (25.0, 0.0),
+            (75.0, 0.0),
+            (150.0, 0.0),
+            (200.0, 0.0),
+            (25.0, 100.0),
+            (75.0, 100.0)
+        ]
+    );
+
+    // Glyphs of a word or line turn around its center
+    let glyphs = layout(serde_json::json!({"g": 2}))?;
+    assert_eq!(
+        pivots(&glyphs),
+        [
+            (50.0, 0.0),
+            (50.0, 0.0),
+            (175.0, 0.0),
+            (175.0, 0.0),
+            (50.0, 100.0),
+            (50.0, 100.0)
+        ]
+    );
+    let glyphs = layout(serde_json::json!({"g": 3}))?;
+    assert_eq!(
+        pivots(&glyphs),
+        [
+            (112.5, 0.0),
+            (112.5, 0.0),
+            (112.5, 0.0),
+            (112.5, 0.0),
+            (50.0, 100.0),
+            (50.0, 100.0)
+        ]
+    );
+    // and all glyphs around the center of the text
+    let glyphs = layout(serde_json::json!({"g": 4}))?;
+    assert_eq!(pivots(&glyphs), [(112.5, 50.0); 6]);
+
+    // Anchor alignment is in percents of the group half size
+    let glyphs = layout(serde_json::json!({"g": 2, "a": {"a": 0, "k": [100, 0]}}))?;
+    assert_eq!(
+        pivots(&glyphs),
+        [
+            (100.0, 0.0),
+            (100.0, 0.0),
+            (225.0, 0.0),
+            (225.0, 0.0),
+            (100.0, 100.0),
+            (100.0, 100.0)
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_text_ligature_rtl() -> Result<(), Error> {
+    let mut font = latin();
+    font.chars.extend([('א', 500), ('ב', 400)]);
+    font.ligature = Some(('f', 'i', 800));
+    let layout = |text: &str| -> Result<Vec<Glyph>, Error> {
+        Ok(glyphs(&text_animation(
+            test_fontdb(&font),
+            text,
+            serde_json::json!({}),
+        )?))
+    };
+    let x = |glyphs: &[Glyph]| glyphs.iter().map(|glyph| glyph.x).collect::<Vec<_>>();
+
+    // A ligature is drawn once, named after the first char of its cluster
+    let glyphs = layout("fia")?;
+    assert_eq!(chars(&glyphs), "fa");
+    assert_eq!(x(&glyphs), [0.0, 80.0]);
+
+    // Right to left runs are laid out in visual order, whatever the
+    // direction of the paragraph
+    for text in ["ab אב", "אב ab"] {
+        let glyphs = layout(text)?;
+        assert_eq!(chars(&glyphs), "ab בא", "{}", text);
+        assert_eq!(x(&glyphs), [0.0, 50.0, 100.0, 125.0, 165.0], "{}", text);
+    }
+    Ok(())
+}
+
+/// Width and height of the outline of a glyph or glyph layer
+fn outline_size(shapes: &[ShapeLayer]) -> (f32, f32) {
+    let beziers = shapes
+        .iter()
+        .find_map(|shape| match &shape.shape {
+            Shape::Path { d, .. } => Some(d.initial_value()),
+            _ => None,
+        })
+        .unwrap();
+    let points = beziers.iter().flat_map(|bezier| bezier.verticies.iter());
+    let (min, max) = points.fold(
+        (Vector2D::splat(f32::MAX), Vector2D::splat(f32::MIN)),
+        |(min, max), point| (min.min(*point), max.max(*point)),
+    );
+    ((max.x - min.x).round(), (max.y - min.y).round())
+}
+
+#[test]
+fn test_text_fallback_notdef() -> Result<(), Error> {
+    let mut fontdb = test_fontdb(&latin());
+    let hebrew = TestFont::new("Test Hebrew", 300, "אב", 400);
+    fontdb.register_font("Test Hebrew", hebrew.build())?;
+    fontdb.set_fallbacks(vec!["Test Hebrew".to_string()]);
+    let lottie = text_animation(fontdb, "aא中b", serde_json::json!({}))?;
+    let glyphs = glyphs(&lottie);
+    assert_eq!(chars(&glyphs), "aא中b");
+    // Chars missing from the font are drawn with the first fallback having
+    // them, with its own advances
+    assert_eq!(outline_size(&glyphs[0].shapes), (50.0, 70.0));
+    assert_eq!(outline_size(&glyphs[1].shapes), (40.0, 30.0));
+    // and chars missing from all fonts with a `.notdef` box as tall as the
+    // ascent, inset in its advance
+    assert_eq!(glyphs[2].x, 90.0);
+    assert_eq!(outline_size(&glyphs[2].shapes), (40.0, 80.0));
+    assert_eq!(glyphs[3].x, 140.0);
+
+    let diagnostics = lottie.diagnostics();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].path, "layers[0].t.d");
+    assert_eq!(
+        diagnostics[0].feature,
+        Feature::MissingGlyphs("中".to_string())
+    );
+    Ok(())
+}
+
+#[test]
+fn test_text_color_glyphs() -> Result<(), Error> {
+    let mut font = TestFont::new("Test Sans", 700, "ab★☺", 500);
+    font.color = Some('★');
+    font.bitmap = Some('☺');
+    let lottie = text_animation(test_fontdb(&font), "★☺", serde_json::json!({}))?;
+    let glyphs = glyphs(&lottie);
+    assert_eq!(chars(&glyphs), "★☺");
+
+    // COLR glyphs are groups of layers, each filled with its palette color
+    let layers = glyphs[0]
+        .shapes
+        .iter()
+        .map(|layer| {
+            let Shape::Group { shapes } = &layer.shape else {
+                panic!("{:?}", layer.shape);
+            };
+            let Some(Shape::Fill(fill)) = shapes.get(1).map(|shape| &shape.shape) else {
+                panic!("{:?}", shapes);
+            };
+            let color = fill.color.initial_value();
+            ((color.r, color.g, color.b), outline_size(shapes))
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        layers,
+        [((255, 0, 0), (50.0, 70.0)), ((0, 0, 255), (50.0, 70.0))]
+    );
+
+    // Bitmap glyphs are images scaled from their strike
+    let Shape::Image { image, .. } = &glyphs[1].shapes[0].shape else {
+        panic!("{:?}", glyphs[1].shapes[0].shape);
+    };
+    assert_eq!(image.content, PNG);
+    assert_eq!(image.size, Vector2D::new(1.0, 1.0));
+    assert!(matches!(glyphs[1].shapes[1].shape, Shape::Fill(_)));
+    Ok(())
+}
+
+#[test]
+fn test_text_shrink_to_fit() -> Result<(), Error> {
+    let font = latin();
+    let doc = serde_json::json!({"sz": [400, 100]});
+    let mut lottie = text_animation(test_fontdb(&font), "a", doc)?;
+    let fit = TextFit::Shrink {
+        min_size: 10.0,
+        max_width: None,
+    };
+    // 8 letters and a space, 4.25 em wide, fit on a line of 400 up to size
+    // 94.1
+    lottie.set_text(&"Text".into(), "aaaa bbbb", fit)?;
+    let glyphs = glyphs(&lottie);
+    assert_eq!(glyphs.iter().filter(|glyph| glyph.line > 0).count(), 0);
+    let size = glyphs[5].x / 2.25;
+    assert!(size > 93.0 && size <= 94.2, "{}", size);
+    assert_eq!(outline_size(&glyphs[0].shapes).0, (size / 2.0).round());
+
+    // the minimum size is kept when the text does not fit at all
+    lottie.set_text(&"Text".into(), &"a".repeat(200), fit)?;
+    assert_eq!(self::glyphs(&lottie)[1].x, 5.0);
+    Ok(())
+}
+
+#[test]
+fn test_font_caches() -> Result<(), Error> {
+    let latin = latin().build();
+    let fira = fs::read("../../assets/FiraMono-Regular.ttf")?;
+    let bold: Font = serde_json::from_str(
+        r#"{"fFamily": "Test Sans", "fName": "TestSans-Bold", "fStyle": "Bold", "fWeight": "700"}"#,
+    )
+    .unwrap();
+    let missing: Font = serde_json::from_str(
+        r#"{"fFamily": "Missing Family", "fName": "MissingFamily-Regular", "fStyle": "Regular"}"#,
+    )
+    .unwrap();
+
+    // fonts of the family are matched even when fontkit has no exact match
+    let fontkit = FontKit::new();
+    fontkit.add_font_from_buffer(latin.clone())?;
+    let fontdb = FontDB::new(fontkit);
+    assert_eq!(fontdb.font(&bold).unwrap().key().family, "Test Sans");
+
+    // changing the default font or registering fonts drops cached files
+    let mut fontdb = FontDB::new(FontKit::new());
+    fontdb.register_font("Fira Mono", fira.clone())?;
+    assert_eq!(fontdb.font_data(&missing).as_deref(), Some(&fira));
+    fontdb.register_font("Test Sans", latin.clone())?;
+    fontdb.set_default_font(Some("Test Sans".to_string()));
+    assert_eq!(fontdb.font_data(&missing).as_deref(), Some(&latin));
+    fontdb.register_font("Missing Family", fira.clone())?;
+    assert_eq!(fontdb.font_data(&missing).as_deref(), Some(&fira));
+    Ok(())
+}
@@ -0,0 +1,385 @@
+use std::fs;
+use std::sync::Arc;
+
+use fontkit::FontKit;
+use lottie::prelude::*;
+use lottie::{
+    AssetKind, AssetResolver, DenyResolver, Error, FileResolver, FontDB, Limit, LoadLimits, Lottie,
+    MemoryResolver,
+};
+
+fn load(path: &str) -> Result<Lottie, Error> {
+    let file = fs::File::open(path)?;
+    let model = Model::from_reader(file)?;
+    Lottie::new(model, FontKit::new(), &FileResolver::new("../../fixtures"))
+}
+
+#[test]
+fn test_precomp_clip() -> Result<(), Error> {
+    let lottie = load("../../fixtures/ui/lottie-ios-samples/timeremap.json")?;
+    let timeline = lottie.timeline();
+    let precomps = timeline
+        .items()
+        .filter(|layer| layer.precomp_size.is_some())
+        .collect::<Vec<_>>();
+    assert_eq!(precomps.len(), 4);
+    for layer in precomps {
+        assert_eq!(layer.precomp_size, Some(Vector2D::new(300.0, 300.0)));
+        let clip = layer
+            .mask_hierarchy
+            .masks()
+            .first()
+            .expect("missing clip mask");
+        assert!(clip.clip);
+        assert!(timeline.item(clip.id).unwrap().is_mask);
+    }
+
+    // A precomp covering the whole composition needs no clip
+    let lottie = load("../../fixtures/ui/delete.json")?;
+    let precomp = lottie
+        .timeline()
+        .items()
+        .find(|layer| layer.precomp_size.is_some())
+        .unwrap();
+    assert_eq!(precomp.precomp_size, Some(Vector2D::new(500.0, 500.0)));
+    assert!(precomp.mask_hierarchy.masks().iter().all(|mask| !mask.clip));
+    Ok(())
+}
+
+#[test]
+fn test_layer_visibility() -> Result<(), Error> {
+    let mut lottie = load("../../fixtures/ui/lottie-ios-samples/timeremap.json")?;
+    assert_eq!(lottie.timeline().find(&"Shape Layer 1".into()).len(), 0);
+    let ids = lottie.set_layer_visibility(&"Reverse.Shape Layer 1".into(), false);
+    assert_eq!(ids.len(), 1);
+    assert!(lottie.timeline().item(ids[0]).unwrap().hidden);
+    let ids = lottie.set_layer_visibility(&"2".into(), false);
+    assert_eq!(ids.len(), 1);
+    assert_eq!(
+        lottie.timeline().item(ids[0]).unwrap().name.as_deref(),
+        Some("Reverse")
+    );
+    Ok(())
+}
+
+#[test]
+fn test_set_text() -> Result<(), Error> {
+    let mut lottie = load("../../fixtures/ui/text/text_range_letter_spacing.json")?;
+    let ids = lottie.set_text(&"Hello World".into(), "Hello\nWorld", TextFit::None)?;
+    assert_eq!(ids.len(), 2);
+    for id in ids {
+        let lines = match &lottie.timeline().item(id).unwrap().content {
+            RenderableContent::Shape(group) => match &group.shapes[0].shape {
+                Shape::Group { shapes } => shapes
+                    .iter()
+                    .map(|line| line.name.clone().unwrap_or_default())
+                    .collect::<Vec<_>>(),
+                _ => vec![],
+            },
+            _ => vec![],
+        };
+        assert_eq!(lines, ["Hello", "World"]);
+    }
+    let fit = TextFit::Shrink {
+        min_size: 10.0,
+        max_width: Some(200.0),
+    };
+    assert_eq!(
+        lottie.set_text(&"Hello World".into(), "Hello", fit)?.len(),
+        2
+    );
+    assert!(lottie
+        .set_text(&"Shape Layer 1".into(), "Hello", fit)?
+        .is_empty());
+    Ok(())
+}
+
+/// Transforms of the glyph groups below `shapes`, by glyph
+fn glyph_transforms(shapes: &[ShapeLayer], out: &mut Vec<(String, Transform)>) {
+    for layer in shapes {
+        if let Shape::Group { shapes } = &layer.shape {
+            let name = layer.name.clone().unwrap_or_default();
+            let transform = shapes.iter().find_map(|shape| match &shape.shape {
+                Shape::Group { shapes } => shapes.iter().find_map(|shape| match &shape.shape {
+                    Shape::Transform(transform) => Some(transform.clone()),
+                    _ => None,
+                }),
+                Shape::Transform(transform) => Some(transform.clone()),
+                _ => None,
+            });
+            match transform {
+                Some(transform) if name.chars().count() == 1 => out.push((name, transform)),
+                _ => glyph_transforms(shapes, out),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_text_follow_path() -> Result<(), Error> {
+    let lottie = load("../../fixtures/ui/text/text_follow_path.json")?;
+    let mut glyphs = vec![];
+    for layer in lottie.timeline().items() {
+        if let RenderableContent::Shape(group) = &layer.content {
+            glyph_transforms(&group.shapes, &mut glyphs);
+        }
+    }
+    let names = glyphs.iter().map(|(c, _)| c.as_str()).collect::<String>();
+    assert_eq!(names, "Hello");
+
+    // The mask is a single arc from (0, 0) to (400, 0)
+    let arc = |t: f32| {
+        let mt = 1.0 - t;
+        Vector2D::new(
+            400.0 * (3.0 * mt * t * t + t * t * t),
+            -220.0 * (3.0 * mt * mt * t + 3.0 * mt * t * t),
+        )
+    };
+    let distance = |p: Vector2D| {
+        (0..=1000)
+            .map(|i| (arc(i as f32 / 1000.0) - p).length())
+            .fold(f32::MAX, f32::min)
+    };
+    for (c, transform) in &glyphs {
+        let position = transform.position.as_ref().unwrap();
+        // Between the margin keyframes at 0 and 60, eased and on the curve
+        for frame in [0.0, 15.0, 30.0, 45.0, 60.0] {
+            let p = position.value(frame);
+            assert!(
+                distance(p) < 1.0,
+                "{} off the path at {}: {:?}",
+                c,
+                frame,
+                p
+            );
+        }
+        assert_ne!(position.value(0.0), position.value(30.0));
+        let rotation = transform.rotation.value(30.0);
+        assert!(rotation > -180.0 && rotation < 180.0);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_asset_resolver() -> Result<(), Error> {
+    let file = fs::File::open("../../fixtures/ui/lottie-ios-samples/Nonanimating/_dog.json")?;
+    let model = Model::from_reader(file)?;
+    let denied = Lottie::new(model.clone(), FontKit::new(), &DenyResolver);
+    assert!(matches!(denied, Err(Error::AssetDenied(path)) if path == "dog.png"));
+    let mut resolver = MemoryResolver::new();
+    resolver.insert("dog.png", vec![1, 2, 3]);
+    let lottie = Lottie::new(model, FontKit::new(), &resolver)?;
+    let content = lottie
+        .timeline()
+        .items()
+        .find_map(|layer| match &layer.content {
+            RenderableContent::Media(media) => Some(media.content.clone()),
+            _ => None,
+        });
+    assert_eq!(content, Some(vec![1, 2, 3]));
+
+    let files = FileResolver::new("../../fixtures/ui/lottie-ios-samples");
+    assert!(files
+        .resolve("Nonanimating/dog.png", AssetKind::Media)
+        .is_ok());
+    for path in ["../../../Cargo.toml", "/etc/passwd", "file:///etc/passwd"] {
+        let outside = files.resolve(path, AssetKind::Media);
+        assert!(matches!(outside, Err(Error::AssetDenied(p)) if p == path));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_load_async() -> Result<(), Error> {
+    let file = fs::File::open("../../fixtures/ui/lottie-ios-samples/Nonanimating/_dog.json")?;
+    let model = Model::from_reader(file)?;
+    let mut resolver = MemoryResolver::new();
+    resolver.insert("dog.png", vec![1, 2, 3]);
+    let resolver: Arc<dyn AssetResolver> = Arc::new(resolver);
+    let lottie = smol::block_on(Lottie::load_async(
+        model.clone(),
+        FontDB::default(),
+        resolver.clone(),
+        &LoadLimits::default(),
+    ))?;
+    let content = lottie
+        .timeline()
+        .items()
+        .find_map(|layer| match &layer.content {
+            RenderableContent::Media(media) => Some(media.content.clone()),
+            _ => None,
+        });
+    assert_eq!(content, Some(vec![1, 2, 3]));
+
+    let limits = LoadLimits {
+        max_asset_bytes: 2,
+        ..LoadLimits::default()
+    };
+    let result = smol::block_on(Lottie::load_async(
+        model,
+        FontDB::default(),
+        resolver,
+        &limits,
+    ));
+    assert!(matches!(
+        result,
+        Err(Error::LimitExceeded {
+            limit: Limit::AssetBytes,
+            ..
+        })
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_load_limits() -> Result<(), Error> {
+    let cycle = r#"{"ip":0,"op":60,"fr":30,"w":100,"h":100,"layers":[
+        {"ty":3,"ind":1,"parent":2,"ip":0,"op":60,"st":0},
+        {"ty":3,"ind":2,"parent":1,"ip":0,"op":60,"st":0}
+    ]}"#;
+    let model = Model::from_reader(cycle.as_bytes())?;
+    let result = Lottie::new(model, FontKit::new(), &DenyResolver);
+    assert!(matches!(result, Err(Error::ParentCycle(_))));
+
+    // the cycle 1 -> 2 -> 1 hides behind the unparented duplicate of 2
+    let duplicate = r#"{"ip":0,"op":60,"fr":30,"w":100,"h":100,"layers":[
+        {"ty":3,"ind":1,"parent":2,"ip":0,"op":60,"st":0},
+        {"ty":3,"ind":2,"ip":0,"op":60,"st":0},
+        {"ty":3,"ind":2,"parent":1,"ip":0,"op":60,"st":0}
+    ]}"#;
+    let model = Model::from_reader(duplicate.as_bytes())?;
+    let result = Lottie::new(model, FontKit::new(), &DenyResolver);
+    assert!(matches!(result, Err(Error::AmbiguousParent(2))));
+
+    let untyped = r#"{"ip":0,"op":60,"fr":30,"w":100,"h":100,"layers":[
+        {"ind":1,"ip":0,"op":60,"st":0}
+    ]}"#;
+    assert!(Model::from_reader(untyped.as_bytes()).is_err());
+
+    let file = fs::File::open("../../fixtures/ui/lottie-ios-samples/timeremap.json")?;
+    let limits = LoadLimits {
+        max_json_bytes: 1_024,
+        ..LoadLimits::default()
+    };
+    let result = limits.read_model(file);
+    assert!(matches!(
+        result,
+        Err(Error::LimitExceeded {
+            limit: Limit::JsonBytes,
+            ..
+        })
+    ));
+
+    let file = fs::File::open("../../fixtures/ui/lottie-ios-samples/Nonanimating/_dog.json")?;
+    let model = Model::from_reader(file)?;
+    let mut resolver = MemoryResolver::new();
+    resolver.insert("dog.png", vec![0; 16]);
+    let limits = LoadLimits {
+        max_asset_bytes: 8,
+        ..LoadLimits::default()
+    };
+    let result = Lottie::with_limits(model, FontDB::default(), &resolver, &limits);
+    assert!(matches!(
+        result,
+        Err(Error::LimitExceeded {
+            limit: Limit::AssetBytes,
+            ..
+        })
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_malformed_input() {
+    let layers = [
+        r#"{"ind":1,"ip":0,"op":60,"st":0}"#,
+        r#"{"ty":1,"sc":"zz","sw":10,"sh":10,"ip":0,"op":60,"st":0}"#,
+        r#"{"ty":3,"ks":{"o":{"a":0,"k":[]}},"ip":0,"op":60,"st":0}"#,
+        r#"{"ty":3,"ks":{"r":{"a":1,"k":[{"t":0}]}},"ip":0,"op":60,"st":0}"#,
+        r#"{"ty":3,"ks":{"p":{"a":0,"k":{"i":[],"o":[],"v":[]}}},"ip":0,"op":60,"st":0}"#,
+    ];
+    for layer in layers {
+        let json = format!(
+            r#"{{"ip":0,"op":60,"fr":30,"w":100,"h":100,"layers":[{}]}}"#,
+            layer
+        );
+        let result =
+            Lottie::from_reader_with_fontdb(json.as_bytes(), FontDB::default(), &DenyResolver);
+        assert!(result.is_err(), "{} should be rejected", layer);
+    }
+}
+
+#[cfg(feature = "dotlottie")]
+#[test]
+fn test_dotlottie() -> Result<(), Error> {
+    use lottie::{DotLottie, DotLottieWriter};
+
+    let file = fs::File::open("../../fixtures/ui/lottie-ios-samples/Nonanimating/_dog.json")?;
+    let mut model = Model::from_reader(file)?;
+    if let Some(transform) = model.layers[0].transform.as_mut() {
+        transform.opacity.slot = Some("opacity".to_string());
+    }
+    let mut resolver = MemoryResolver::new();
+    resolver.insert("dog.png", vec![1, 2, 3]);
+    let file = fs::File::open("../../fixtures/ui/lottie-ios-samples/Watermelon.json")?;
+    let watermelon = Model::from_reader(file)?;
+    let mut writer = DotLottieWriter::new();
+    writer.add_animation("dog", &model, &resolver)?;
+    writer.add_animation("watermelon", &watermelon, &resolver)?;
+    let theme = serde_json::json!({
+        "rules": [{"id": "opacity", "type": "Scalar", "value": 50}]
+    });
+    writer.add_theme("dim", &theme)?;
+    let mut archive = std::io::Cursor::new(vec![]);
+    writer.write(&mut archive)?;
+
+    let dotlottie = DotLottie::from_reader(archive.clone())?;
+    let ids = dotlottie
+        .animations()
+        .iter()
+        .map(|animation| animation.id.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(ids, ["dog", "watermelon"]);
+    let solid = dotlottie
+        .model("watermelon", None)?
+        .layers
+        .into_iter()
+        .find_map(|layer| match layer.content {
+            LayerContent::SolidColor { color, width, .. } => Some((color.to_string(), width)),
+            _ => None,
+        });
+    assert_eq!(solid, Some(("#ffffff".to_string(), 120.0)));
+    assert!(matches!(
+        dotlottie.model("cat", None),
+        Err(Error::AnimationNotFound(id)) if id == "cat"
+    ));
+    let lottie = dotlottie.load("dog", Some("dim"), FontDB::default())?;
+    let content = lottie
+        .timeline()
+        .items()
+        .find_map(|layer| match &layer.content {
+            RenderableContent::Media(media) => Some(media.content.clone()),
+            _ => None,
+        });
+    assert_eq!(content, Some(vec![1, 2, 3]));
+    let opacity = lottie.model.layers[0]
+        .transform
+        .as_ref()
+        .map(|transform| transform.opacity.initial_value());
+    assert_eq!(opacity, Some(50.0));
+
+    // animations are parsed within the limits of the archive
+    let limits = LoadLimits {
+        max_layers: 0,
+        ..LoadLimits::default()
+    };
+    let dotlottie = DotLottie::from_reader_with_limits(archive, &limits)?;
+    assert!(matches!(
+        dotlottie.model("dog", Some("dim")),
+        Err(Error::LimitExceeded {
+            limit: Limit::Layers,
+            ..
+        })
+    ));
+    Ok(())
+}
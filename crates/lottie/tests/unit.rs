@@ -1,7 +1,15 @@
 use std::fs;
-use std::io::Error;
+use std::io::{Error, Write};
+use std::sync::Arc;
 
-use lottie::prelude::{Animated, GradientFill, Stroke, TextRange, Transform, Vector2D};
+use lottie::prelude::{
+    Animated, Font, GlyphStyle, GradientFill, LayerContent, Model, Shape, Stroke, TextFollowPath,
+    TextGrouping, TextRange, TextRangeInfo, Transform, Vector2D, Version,
+};
+use lottie::{
+    analyze, validate, Diagnostic, Feature, FontDB, FontRequest, LoadLimits, Severity, TgsProfile,
+    TgsViolationKind, Violation, ViolationKind,
+};
 
 #[test]
 fn test_transform_complex() -> Result<(), Error> {
@@ -48,6 +56,75 @@ fn test_text_range() -> Result<(), Error> {
     Ok(())
 }
 
+#[test]
+fn test_text_follow_path() -> Result<(), Error> {
+    let file = fs::File::open("../../fixtures/ui/text/text_follow_path.json")?;
+    let model = Model::from_reader(file).unwrap();
+    let follow = match &model.layers[0].content {
+        LayerContent::Text(text) => &text.follow_path,
+        _ => panic!("expected a text layer"),
+    };
+    let value = |flag: &Option<Animated<f32>>| flag.as_ref().map(|f| f.initial_value());
+    assert_eq!(follow.mask, Some(0));
+    assert_eq!(value(&follow.reversed), Some(0.0));
+    assert_eq!(value(&follow.perpendicular), Some(1.0));
+    assert!(follow.first_margin.as_ref().unwrap().is_animated());
+
+    // Older exporters wrote the flags as plain numbers
+    let follow: TextFollowPath = serde_json::from_str(r#"{"m": 0, "r": 1, "a": 0}"#)?;
+    assert_eq!(value(&follow.reversed), Some(1.0));
+    assert_eq!(value(&follow.force_alignment), Some(0.0));
+    assert!(follow.perpendicular.is_none());
+    Ok(())
+}
+
+#[test]
+fn test_text_grouping() -> Result<(), Error> {
+    let file =
+        fs::File::open("../../fixtures/ui/lottie-ios-samples/Nonanimating/blend_mode_test.json")?;
+    assert!(Model::from_reader(file).is_ok());
+    let grouping = |json| serde_json::from_str::<TextGrouping>(json).unwrap();
+    assert!(matches!(grouping("1.0"), TextGrouping::Characters));
+    assert!(matches!(grouping("3"), TextGrouping::Lines));
+    assert!(matches!(grouping("2.0"), TextGrouping::Words));
+    assert!(matches!(grouping("9"), TextGrouping::Characters));
+    Ok(())
+}
+
+#[test]
+fn test_text_range_selector_amount() -> Result<(), Error> {
+    let file = fs::File::open("../../fixtures/segments/text_range.json")?;
+    let d = &mut serde_json::Deserializer::from_reader(file);
+    let mut d: TextRange = serde_path_to_error::deserialize(d).unwrap();
+    d.style.as_mut().unwrap().letter_spacing = Some(Animated::from_value(10.0));
+    let value = vec!["The quick brown fox".chars().collect::<Vec<_>>(); 2];
+    let units = Arc::new(vec![d.selector.units(&value, 0)]);
+    let tracking = Arc::default();
+    let animate = |line: usize, c: usize| {
+        let info = TextRangeInfo {
+            units: units.clone(),
+            index: (line, c),
+            ranges: vec![d.clone()],
+            color_glyph: false,
+            tracking: Arc::clone(&tracking),
+        };
+        info.animate(0.0, GlyphStyle::default())
+    };
+    // start 23, end 6 and offset -3 in index units select chars 3..20, whose
+    // fill color is replaced
+    let selected = |line: usize, c: usize| animate(line, c).style.fill_color.b != 0;
+    assert!(!selected(0, 2));
+    assert!((3..value[0].len()).all(|c| selected(0, c)));
+    assert!(selected(1, 0));
+    assert!(!selected(1, 1));
+    // tracking of the selected chars pushes the following chars of their line
+    let offsets = (0..6).map(|c| animate(0, c).tracking).collect::<Vec<_>>();
+    assert_eq!(offsets, [0.0, 0.0, 0.0, 0.0, 10.0, 20.0]);
+    assert_eq!(animate(0, 18).tracking, 150.0);
+    assert_eq!(animate(1, 5).tracking, 10.0);
+    Ok(())
+}
+
 #[test]
 fn test_legacy_animated_position() -> Result<(), Error> {
     let file = fs::File::open("../../fixtures/segments/animated_position_legacy.json")?;
@@ -56,3 +133,322 @@ fn test_legacy_animated_position() -> Result<(), Error> {
     println!("{:?}", d);
     Ok(())
 }
+
+#[test]
+fn test_font_request() -> Result<(), Error> {
+    let font: Font = serde_json::from_str(
+        r#"{"fFamily": "Roboto", "fName": "Roboto-SemiBoldItalic", "fStyle": "SemiBold Condensed Italic", "ascent": 75}"#,
+    )?;
+    let request = FontRequest::from_font(&font);
+    assert_eq!(request.family, "Roboto");
+    assert_eq!(request.weight, 600);
+    assert!(request.italic);
+    assert_eq!(request.stretch, 3);
+    Ok(())
+}
+
+#[test]
+fn test_embedded_chars() -> Result<(), Error> {
+    let file = fs::File::open("../../fixtures/ui/text/text_range_letter_spacing.json")?;
+    let model = Model::from_reader(file)?;
+    let font = model.font("GillSans-UltraBold").unwrap();
+    let h = model.character(font, 'H').unwrap();
+    assert_eq!(h.width, 96.92);
+    assert!(!h.data.shapes.is_empty());
+    assert!(model.character(font, ' ').unwrap().data.shapes.is_empty());
+    assert!(model.character(font, 'z').is_none());
+    Ok(())
+}
+
+#[test]
+fn test_registered_font() -> Result<(), Error> {
+    let data = fs::read("../../assets/FiraMono-Regular.ttf")?;
+    let mut fontdb = FontDB::default();
+    assert!(!fontdb.system_fonts());
+    fontdb.register_font("Fira Mono", data.clone()).unwrap();
+    let font: Font = serde_json::from_str(
+        r#"{"fFamily": "Missing Family", "fName": "MissingFamily-Regular", "fStyle": "Regular", "ascent": 75}"#,
+    )?;
+    assert_eq!(fontdb.font_data(&font).as_deref(), Some(&data));
+    let mut fontdb = FontDB::default();
+    fontdb.register_font("Fira Mono", data).unwrap();
+    fontdb.set_default_font(None);
+    assert!(fontdb.font_data(&font).is_none());
+    Ok(())
+}
+
+#[test]
+fn test_parse_error_location() {
+    let json = r##"{"ip":0,"op":60,"fr":30,"w":100,"h":100,"layers":[
+        {"ty":1,"sc":"#ff0000","sw":"wide","sh":10,"ip":0,"op":60,"st":0},
+        {"ty":3,"ks":{"o":{"a":0,"k":"opaque"}},"ip":0,"op":60,"st":0}
+    ]}"##;
+    let e = Model::from_reader(json.as_bytes()).unwrap_err();
+    assert_eq!(e.path(), "layers[0].sw");
+    assert_eq!(e.line(), 2);
+    assert!(e.to_string().contains("at `layers[0].sw`, line 2"));
+
+    let json = json.replace(r#""sw":"wide""#, r#""sw":10"#);
+    let e = Model::from_reader(json.as_bytes()).unwrap_err();
+    assert_eq!(e.path(), "layers[1].ks.o.k");
+    assert!(e.message().contains("Plain:"));
+    assert!(e.message().contains("AnimatedHelper:"));
+
+    let json = r#"{"ip":0,"op":60,"fr":30,"w":100,"h":100,"layers":[],"assets":[{"id":"a"}]}"#;
+    let e = Model::from_reader(json.as_bytes()).unwrap_err();
+    assert_eq!(e.path(), "assets[0]");
+    assert!(e.message().contains("Media: missing field `p`"));
+    assert!(e
+        .message()
+        .contains("Precomposition: missing field `layers`"));
+
+    // recent exports are parsed in place, while failures in old ones
+    // rewritten by migrations lose their position
+    let json = r##"{"v":"VERSION","ip":0,"op":60,"fr":30,"w":100,"h":100,"layers":[
+        {"ty":1,"sc":"#ff0000","sw":"wide","sh":10,"ip":0,"op":60,"st":0},
+        {"ty":4,"ip":0,"op":60,"st":0,"shapes":[
+            {"ty":"fl","o":{"a":0,"k":100},"c":{"a":0,"k":[255,0,0,255]}}
+        ]}
+    ]}"##;
+    for (version, line) in [("5.7.0", 2), ("4.0.0", 0)] {
+        let json = json.replace("VERSION", version);
+        let e = Model::from_reader(json.as_bytes()).unwrap_err();
+        assert_eq!(e.path(), "layers[0].sw", "{}", version);
+        assert_eq!(e.line(), line, "{}", version);
+    }
+}
+
+#[test]
+fn test_analyze() -> Result<(), Error> {
+    let file = fs::File::open("../../fixtures/ui/lottie-ios-samples/Issues/issue_1735.json")?;
+    let model = Model::from_reader(file)?;
+    let diagnostics = analyze(&model);
+    assert!(diagnostics.contains(&Diagnostic {
+        path: "layers[0].ef[0]".to_string(),
+        feature: Feature::Effect(Some("Excite - Skew - Transform".to_string())),
+        severity: Severity::Error,
+    }));
+    assert!(diagnostics.contains(&Diagnostic {
+        path: "layers[0].shapes[0].it[2].p.x".to_string(),
+        feature: Feature::Expression,
+        severity: Severity::Warning,
+    }));
+
+    let json = r#"{"ip":0,"op":60,"fr":30,"w":100,"h":100,"layers":[
+        {"ty":13,"ip":0,"op":60,"st":0}
+    ]}"#;
+    let model = Model::from_reader(json.as_bytes())?;
+    let diagnostics = analyze(&model);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].path, "layers[0].ty");
+    assert_eq!(diagnostics[0].feature, Feature::LayerType(13));
+
+    let mask = r#"{"mode":"a","pt":{"a":0,"k":{"c":true,"v":[[0,0]],"i":[[0,0]],"o":[[0,0]]}},"o":{"a":0,"k":100}}"#;
+    let json = r#"{"ip":0,"op":60,"fr":30,"w":100,"h":100,
+        "fonts":{"list":[{"fName":"Sans","fFamily":"Sans","fStyle":"Regular"}]},
+        "assets":[{"id":"comp","layers":[]}],"layers":[
+        {"ty":0,"refId":"comp","w":10,"h":10,"ip":0,"op":60,"st":0,"hasMask":true,"masksProperties":[MASK]},
+        {"ty":5,"ip":0,"op":60,"st":0,"hasMask":true,"masksProperties":[MASK,MASK],"t":{
+            "d":{"k":[{"s":{"t":"text","f":"Sans","s":10,"fc":[0,0,0]},"t":0}]},
+            "m":{"a":{"a":0,"k":[0,0],"x":"wiggle(1, 2)"}},
+            "p":{"m":1,"f":{"a":0,"k":0,"x":"time"}},
+            "a":[{"s":{"t":0,"xe":{"a":0,"k":0},"ne":{"a":0,"k":0},"a":{"a":0,"k":100,"x":"time"},
+                "b":1,"rn":0,"sh":1,"r":1},"a":{"p":{"a":0,"k":[0,0],"x":"time"}}}]
+        }}
+    ]}"#
+    .replace("MASK", mask);
+    let model = Model::from_reader(json.as_bytes())?;
+    let diagnostics = analyze(&model)
+        .into_iter()
+        .map(|diagnostic| (diagnostic.path, diagnostic.feature))
+        .collect::<Vec<_>>();
+    let expected = [
+        (
+            "layers[0].masksProperties[0]",
+            Feature::LayerMask("precomposition"),
+        ),
+        ("layers[1].masksProperties[0]", Feature::LayerMask("text")),
+        ("layers[1].t.a[0].s.a.x", Feature::Expression),
+        ("layers[1].t.a[0].a.p.x", Feature::Expression),
+        ("layers[1].t.m.a.x", Feature::Expression),
+        ("layers[1].t.p.f.x", Feature::Expression),
+    ];
+    assert_eq!(
+        diagnostics,
+        expected.map(|(path, feature)| (path.to_string(), feature))
+    );
+    Ok(())
+}
+
+#[test]
+fn test_validate() -> Result<(), Error> {
+    let json = r#"{"ip":0,"op":60,"fr":30,"w":100,"h":100,"assets":[
+        {"id":"image","w":10,"h":10,"u":"","p":"image.png"}
+    ],"layers":[
+        {"ty":3,"ind":1,"parent":2,"ip":0,"op":60,"st":0},
+        {"ty":3,"ind":2,"parent":1,"ip":0,"op":60,"st":0},
+        {"ty":3,"ind":2,"parent":7,"ip":0,"op":60,"st":0},
+        {"ty":0,"refId":"image","w":10,"h":10,"ip":30,"op":10,"st":0},
+        {"ty":4,"ip":0,"op":60,"st":0,"ks":{"o":{"a":1,"k":[
+            {"t":20,"s":[0]},{"t":10,"s":[100]}
+        ]}},"shapes":[
+            {"ty":"sh","ks":{"a":0,"k":{"c":false,"v":[[0,0],[1,1]],"i":[[0,0]],"o":[[0,0],[0,0]]}}}
+        ]}
+    ]}"#;
+    let model = Model::from_reader(json.as_bytes())?;
+    let violations = validate(&model);
+    let expected = [
+        ("layers[2].ind", ViolationKind::DuplicateIndex(2)),
+        ("layers[0].parent", ViolationKind::ParentCycle(1)),
+        ("layers[1].parent", ViolationKind::ParentCycle(2)),
+        ("layers[2].parent", ViolationKind::MissingParent(7)),
+        (
+            "layers[3].op",
+            ViolationKind::FrameRange {
+                start: 30.0,
+                end: 10.0,
+            },
+        ),
+        (
+            "layers[3].refId",
+            ViolationKind::AssetKind {
+                ref_id: "image".to_string(),
+                expected: "precomposition",
+            },
+        ),
+        ("layers[4].ks.o.k", ViolationKind::UnorderedKeyframes),
+        (
+            "layers[4].shapes[0].ks.k",
+            ViolationKind::BezierLengths {
+                vertices: 2,
+                in_tangents: 1,
+                out_tangents: 2,
+            },
+        ),
+    ];
+    for (path, kind) in expected {
+        let violation = Violation {
+            path: path.to_string(),
+            kind,
+        };
+        assert!(violations.contains(&violation), "missing {}", violation);
+    }
+    assert_eq!(violations.len(), 8);
+    Ok(())
+}
+
+#[test]
+fn test_tgs_profile() -> Result<(), lottie::Error> {
+    let json = r#"{"ip":0,"op":240,"fr":30,"w":100,"h":100,"assets":[
+        {"id":"image","w":10,"h":10,"u":"","p":"image.png"}
+    ],"layers":[
+        {"ty":3,"ip":0,"op":240,"st":0,"ks":{"o":{"a":0,"k":100,"x":"value"}}}
+    ]}"#;
+    let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+    encoder.write_all(json.as_bytes())?;
+    let tgs = encoder.finish()?;
+    let model = LoadLimits::default().read_model(tgs.as_slice())?;
+    assert_eq!(model.frame_rate, 30.0);
+
+    let profile = TgsProfile {
+        max_bytes: 16,
+        ..TgsProfile::default()
+    };
+    let violations = profile.check_file(&tgs)?;
+    let kinds = violations
+        .iter()
+        .map(|violation| (violation.path.as_str(), violation.kind.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [
+            (
+                "",
+                TgsViolationKind::FileSize {
+                    bytes: tgs.len(),
+                    max: 16
+                }
+            ),
+            (
+                "w",
+                TgsViolationKind::Dimensions {
+                    width: 100,
+                    height: 100
+                }
+            ),
+            ("fr", TgsViolationKind::FrameRate(30.0)),
+            ("op", TgsViolationKind::Duration(8.0)),
+            ("assets[0]", TgsViolationKind::Media),
+            ("layers[0].ks.o.x", TgsViolationKind::Expression),
+        ]
+    );
+    let violations = profile.check_file(json.as_bytes())?;
+    assert_eq!(violations[0].kind, TgsViolationKind::Uncompressed);
+    Ok(())
+}
+
+#[test]
+fn test_migrate() -> Result<(), Error> {
+    let json = r#"{"v":"4.0.0","ip":0,"op":60,"fr":30,"w":100,"h":100,"layers":[
+        {"ty":4,"ip":0,"op":60,"st":0,"ks":{"o":{"a":1,"k":[
+            {"t":0,"s":[0],"e":[100],"i":{"x":[1],"y":[1]},"o":{"x":[0],"y":[0]}},
+            {"t":30}
+        ]}},"shapes":[
+            {"ty":"sh","closed":true,"ks":{"a":0,"k":{"v":[[0,0],[1,1]],"i":[[0,0],[0,0]],"o":[[0,0],[0,0]]}}},
+            {"ty":"fl","o":{"a":0,"k":100},"c":{"a":0,"k":[1,0,0,255]}}
+        ]},
+        {"ty":5,"ip":0,"op":60,"st":0,"t":{"a":[],"d":{"t":"Hi","f":"Arial","s":12}}}
+    ]}"#;
+    let model = Model::from_reader(json.as_bytes())?;
+    assert_eq!(model.version(), Some(Version::new(4, 0, 0)));
+    let Some(transform) = &model.layers[0].transform else {
+        panic!("missing transform");
+    };
+    let keyframes = &transform.opacity.keyframes;
+    assert_eq!(keyframes[0].end_value, 100.0);
+    assert_eq!(keyframes[0].end_frame, 30.0);
+    let LayerContent::Shape(group) = &model.layers[0].content else {
+        panic!("not a shape layer");
+    };
+    match &group.shapes[0].shape {
+        Shape::Path { d, .. } => assert!(d.initial_value()[0].closed),
+        _ => panic!("not a path"),
+    }
+    match &group.shapes[1].shape {
+        // 0-255 channels, not a red in 0-1 channels
+        Shape::Fill(fill) => assert!(fill.color.initial_value().r < 2),
+        _ => panic!("not a fill"),
+    }
+    let LayerContent::Text(text) = &model.layers[1].content else {
+        panic!("not a text layer");
+    };
+    assert_eq!(text.document.keyframes[0].start_value.value, "Hi");
+    Ok(())
+}
+
+#[test]
+fn test_keyframes_round_trip() -> Result<(), Error> {
+    let json = r#"{"a":1,"k":[
+        {"t":0,"s":[0],"h":1},
+        {"t":10,"s":[50],"i":{"x":[1],"y":[1]},"o":{"x":[0],"y":[0]}},
+        {"t":20,"s":[100],"e":[80]},
+        {"t":30}
+    ]}"#;
+    let animated: Animated<f32> = serde_json::from_str(json)?;
+    let written = serde_json::to_string(&animated)?;
+    let read: Animated<f32> = serde_json::from_str(&written)?;
+    assert_eq!(read.keyframes.len(), animated.keyframes.len());
+    for (read, keyframe) in read.keyframes.iter().zip(&animated.keyframes) {
+        assert_eq!(read.start_value, keyframe.start_value);
+        assert_eq!(read.end_value, keyframe.end_value);
+        assert_eq!(read.start_frame, keyframe.start_frame);
+        assert_eq!(read.end_frame, keyframe.end_frame);
+    }
+    // still values are written without keyframes
+    let still = Animated::from_value(Vector2D::new(1.0, 2.0));
+    assert_eq!(
+        serde_json::to_value(&still)?["k"],
+        serde_json::json!([1.0, 2.0])
+    );
+    Ok(())
+}
@@ -6,12 +6,17 @@ use std::path::Path;
 // use bevy_prototype_debug_lines::{DebugLines, DebugLinesPlugin};
 use anyhow::Error;
 use clap::Parser;
-use lottie::{Config, HeadlessConfig, Lottie, Renderer, Target, WindowConfig};
+use lottie::{
+    default_resolver, Config, FontDB, HeadlessConfig, Lottie, Renderer, Target, WindowConfig,
+};
 use lottie_renderer_bevy::BevyRenderer;
 use smol::pin;
 use smol::stream::StreamExt;
 use webp_animation::Encoder;
 
+/// Default font of text layers whose font is missing
+const FIRA_MONO: &[u8] = include_bytes!("../../../assets/FiraMono-Regular.ttf");
+
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
 struct Args {
@@ -57,7 +62,11 @@ fn main() -> Result<(), Error> {
     }
     let root_path = &*root_path.to_string_lossy();
     let f = fs::File::open(path).unwrap();
-    let mut lottie = Lottie::from_reader(f, root_path).unwrap();
+    let mut fontdb = FontDB::default();
+    fontdb.set_system_fonts(true);
+    fontdb.register_font("Fira Mono", FIRA_MONO.to_vec())?;
+    let mut lottie =
+        Lottie::from_reader_with_fontdb(f, fontdb, &*default_resolver(root_path)).unwrap();
     lottie.scale = args.scale.unwrap_or(1.0);
     let final_timestamp = (lottie.model.end_frame / lottie.model.frame_rate * 1000.0) as i32;
     let (mut renderer, frame_stream) = BevyRenderer::new();
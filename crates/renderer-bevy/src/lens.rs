@@ -1,7 +1,7 @@
-use bevy::prelude::{Transform, Vec2};
+use bevy::prelude::{Color, Transform, Vec2};
 use bevy_tweening_captured::{Lens, Targetable};
 use lottie::prelude::{
-    Animated, Bezier, OpacityHierarchy, PathFactory, TextBased, TextRangeInfo, TextRangeSelector,
+    Animated, Bezier, GlyphStyle, OpacityHierarchy, PathFactory, Rgb, TextRangeInfo,
     Transform as LottieTransform, TransformHierarchy, TrimInfo,
 };
 use lyon::algorithms::measure::PathMeasurements;
@@ -9,7 +9,7 @@ use lyon::algorithms::measure::SampleType::Normalized;
 use lyon::geom::euclid::approxeq::ApproxEq;
 use lyon::path::Path as LyonPath;
 
-use crate::shape::{DrawMode, Path};
+use crate::shape::{DrawMode, Path, SolidOrGradient};
 
 pub struct PathLens {
     pub(crate) start: Vec<Bezier>,
@@ -104,36 +104,15 @@ pub struct TransformLens {
 impl Lens<Transform> for TransformLens {
     fn lerp(&mut self, target: &mut dyn Targetable<Transform>, ratio: f32) {
         let frame = self.frames * ratio;
-        let value = self.data.value(frame);
+        let value = match self.text_range.as_ref() {
+            Some(info) => info
+                .animate(frame, GlyphStyle::default())
+                .glyph_matrix(&self.data, frame),
+            None => self.data.value(frame),
+        };
         *target.target_mut() = Transform::from_matrix(value);
         target.translation.z = self.zindex;
         target.translation.x += self.mask_offset.x / self.transform_hierarchy.scale_x(frame);
-
-        if let Some(info) = self.text_range.as_ref() {
-            for range in &info.ranges {
-                let appliable = lerp_index_in_text_range(
-                    &range.selector,
-                    frame,
-                    &info.value,
-                    info.index.0,
-                    info.index.1,
-                );
-                if !appliable {
-                    continue;
-                }
-                // TODO: support more selector attributes
-                let styles = match range.style.as_ref() {
-                    Some(s) => s,
-                    None => continue,
-                };
-                let letter_spacing = styles
-                    .letter_spacing
-                    .as_ref()
-                    .map(|l| l.value(frame))
-                    .unwrap_or(0.0);
-                target.translation.x += info.index.1 as f32 * letter_spacing;
-            }
-        }
     }
 }
 
@@ -163,31 +142,46 @@ impl Lens<DrawMode> for OpacityLens {
     }
 }
 
-fn lerp_index_in_text_range(
-    selector: &TextRangeSelector,
-    frame: f32,
-    value: &Vec<Vec<char>>,
-    line: usize,
-    c: usize,
-) -> bool {
-    let start = selector
-        .start
-        .as_ref()
-        .map(|start| start.value(frame))
-        .unwrap_or(0.0)
-        .round() as usize;
-    let end = selector
-        .end
-        .as_ref()
-        .map(|end| end.value(frame))
-        .unwrap_or(std::f32::MAX)
-        .round() as usize;
-    match selector.range_units {
-        TextBased::Characters => {
-            let index = (0..line).map(|i| value[i].len()).sum::<usize>() + c;
-            return index >= start && index < end;
+/// Lerp fill/stroke styles of a glyph affected by text animators
+pub struct TextStyleLens {
+    pub(crate) opacity: OpacityHierarchy,
+    pub(crate) frames: f32,
+    pub(crate) text_range: TextRangeInfo,
+    pub(crate) fill: Option<(Animated<Rgb>, Animated<f32>)>,
+    pub(crate) stroke: Option<(Animated<Rgb>, Animated<f32>, Animated<f32>)>,
+}
+
+impl Lens<DrawMode> for TextStyleLens {
+    fn lerp(&mut self, target: &mut dyn Targetable<DrawMode>, ratio: f32) {
+        let frame = self.frames * ratio;
+        let mut style = GlyphStyle::default();
+        if let Some((color, _)) = self.fill.as_ref() {
+            style.fill_color = color.value(frame);
+        }
+        if let Some((color, _, width)) = self.stroke.as_ref() {
+            style.stroke_color = color.value(frame);
+            style.stroke_width = width.value(frame);
+        }
+        let animation = self.text_range.animate(frame, style);
+        let opacity = self.opacity.value(frame) * animation.opacity;
+
+        if let (Some(fill), Some((_, fill_opacity))) = (target.fill.as_mut(), self.fill.as_ref()) {
+            // layers of color glyphs keep their own colors
+            if !self.text_range.color_glyph {
+                let color = animation.style.fill_color;
+                fill.color = SolidOrGradient::Solid(Color::rgb_u8(color.r, color.g, color.b));
+            }
+            fill.opacity = opacity * fill_opacity.value(frame) / 100.0;
+        }
+        if let (Some(stroke), Some((_, stroke_opacity, _))) =
+            (target.stroke.as_mut(), self.stroke.as_ref())
+        {
+            let color = animation.style.stroke_color;
+            stroke.color = SolidOrGradient::Solid(Color::rgb_u8(color.r, color.g, color.b));
+            stroke.opacity =
+                opacity * animation.stroke_opacity * stroke_opacity.value(frame) / 100.0;
+            stroke.options.line_width = animation.style.stroke_width.max(0.0);
         }
-        _ => unimplemented!(),
     }
 }
 
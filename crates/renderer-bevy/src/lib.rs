@@ -23,7 +23,7 @@ pub use bevy;
 use bevy::prelude::Transform;
 use bevy::render::texture::{BevyDefault, Image};
 use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
-use lottie::prelude::{Id as TimelineItemId, StyledShape};
+use lottie::prelude::{Id as TimelineItemId, LayerPath, StyledShape};
 use lottie::*;
 use shape::{DrawMode, Path};
 use system::component_animator_system;
@@ -57,6 +57,17 @@ struct LottieShapeComp(StyledShape);
 #[derive(Component)]
 struct LayerId(TimelineItemId);
 
+/// Marks a layer hidden at runtime, see [SetLayerVisibility]
+#[derive(Component)]
+pub struct LayerHidden;
+
+/// Send this event to show or hide layers addressed by `path` at runtime
+#[derive(Event)]
+pub struct SetLayerVisibility {
+    pub path: LayerPath,
+    pub visible: bool,
+}
+
 #[derive(Resource)]
 struct LottieGlobals {
     lottie: Option<Lottie>,
@@ -189,9 +200,11 @@ impl Renderer for BevyRenderer {
             // .add_plugin(LogDiagnosticsPlugin::default())
             .add_plugins(LottiePlugin)
             .add_event::<TweenCompleted>()
+            .add_event::<SetLayerVisibility>()
             .add_systems(Update, component_animator_system::<Transform>)
             .add_systems(Update, component_animator_system::<Path>)
             .add_systems(Update, component_animator_system::<DrawMode>)
+            .add_systems(Update, layer_visibility_system.before(animate_system))
             .add_systems(Update, animate_system)
             .add_systems(Startup, setup_system);
 
@@ -501,6 +514,33 @@ fn setup_system(
     commands.entity(root_entity).insert(comp);
 }
 
+fn layer_visibility_system(
+    mut events: EventReader<SetLayerVisibility>,
+    mut lottie_query: Query<&mut LottieComp>,
+    info: Res<LottieAnimationInfo>,
+    mut commands: Commands,
+) {
+    for event in events.read() {
+        for mut comp in lottie_query.iter_mut() {
+            let ids = comp.lottie.set_layer_visibility(&event.path, event.visible);
+            if ids.is_empty() {
+                log::warn!("no layer found at {}", event.path);
+            }
+            for id in ids {
+                let entity = match info.entities.get(&id) {
+                    Some(entity) => *entity,
+                    None => continue,
+                };
+                if event.visible {
+                    commands.entity(entity).remove::<LayerHidden>();
+                } else {
+                    commands.entity(entity).insert(LayerHidden);
+                }
+            }
+        }
+    }
+}
+
 fn animate_system(
     mut visibility_query: Query<(
         Entity,
@@ -508,6 +548,7 @@ fn animate_system(
         &InheritedVisibility,
         Option<&AudioSink>,
         Has<LottieAudio>,
+        Has<LayerHidden>,
         &FrameTracker,
     )>,
     mut transform_animation: Query<(&mut Animator<Transform>, &FrameTracker)>,
@@ -584,10 +625,10 @@ fn animate_system(
         }
     }
 
-    for (_, mut visibility, inherited_visibility, audio_sink, _, tracker) in
+    for (_, mut visibility, inherited_visibility, audio_sink, _, hidden, tracker) in
         visibility_query.iter_mut()
     {
-        let visible = tracker.value(current_frame).is_some();
+        let visible = !hidden && tracker.value(current_frame).is_some();
         if let Some(sink) = audio_sink {
             if !inherited_visibility.get() && visible {
                 sink.play();
@@ -109,6 +109,10 @@ pub struct GradientDataUniform {
     pub stops: [GradientDataStop; 2],
 }
 
+/// Max count of masks a single shape could be affected by, limited by the size
+/// of [MaskDataUniform::masks]
+pub const MAX_MASK_COUNT: usize = 4;
+
 #[derive(Clone, Default, ShaderType)]
 pub struct MaskDataUniform {
     // #[size(runtime)]
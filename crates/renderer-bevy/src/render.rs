@@ -8,7 +8,9 @@ use bevy::render::view::RenderLayers;
 use bevy_tweening_captured::{Animator, EaseMethod, Sequence, Tracks, Tween};
 use lottie::prelude::{Transform as LottieTransform, *};
 
-use crate::lens::{OpacityLens, PathFactoryLens, PathLens, StrokeWidthLens, TransformLens};
+use crate::lens::{
+    OpacityLens, PathFactoryLens, PathLens, StrokeWidthLens, TextStyleLens, TransformLens,
+};
 use crate::material::*;
 use crate::plugin::MaskMarker;
 use crate::shape::ShapeBundle;
@@ -128,6 +130,10 @@ impl<'a> BevyStagedLayer<'a> {
             *self.mask_index += 1;
         }
 
+        if self.layer.hidden {
+            c.insert(LayerHidden);
+        }
+
         let id = c.id();
         c.insert(FrameTracker(self.layer.frame_transform_hierarchy.clone()));
         c.insert(VisibilityBundle::default());
@@ -203,6 +209,15 @@ impl<'a> BevyStagedLayer<'a> {
             }
         }
 
+        // Precomp bounds clips only take the mask slots the masks of the
+        // animation itself leave
+        let masks = self.layer.mask_hierarchy.masks();
+        let masks = masks
+            .iter()
+            .filter(|mask| !mask.clip)
+            .chain(masks.iter().filter(|mask| mask.clip))
+            .take(MAX_MASK_COUNT)
+            .collect::<Vec<_>>();
         let mut material = LottieMaterial {
             size: Vec4::new(self.model_size.x, self.model_size.y, self.scale, 0.0),
             mask_info: MaskDataUniform {
@@ -212,7 +227,7 @@ impl<'a> BevyStagedLayer<'a> {
                     UVec4::default(),
                     UVec4::default(),
                 ],
-                mask_count: self.layer.mask_hierarchy.len() as u32,
+                mask_count: masks.len() as u32,
                 mask_total_count: self.mask_count,
             },
             mask: if !self.layer.is_mask {
@@ -224,7 +239,22 @@ impl<'a> BevyStagedLayer<'a> {
         };
 
         if !self.layer.is_mask {
-            for (index, item) in self.layer.mask_hierarchy.masks().iter().enumerate() {
+            let mask_count = self
+                .layer
+                .mask_hierarchy
+                .masks()
+                .iter()
+                .filter(|mask| !mask.clip)
+                .count();
+            if mask_count > MAX_MASK_COUNT {
+                log::warn!(
+                    "layer {:?} has {} masks, only the innermost {} are applied",
+                    self.layer.name,
+                    mask_count,
+                    MAX_MASK_COUNT
+                );
+            }
+            for (index, item) in masks.into_iter().enumerate() {
                 let mask_index = *self.mask_registry.get(&item.id).unwrap();
                 let mode = item.mode as u32;
                 material.mask_info.masks[index] = UVec4::new(mask_index, mode, 0, 0);
@@ -317,6 +347,23 @@ impl<'a> BevyStagedLayer<'a> {
                     c.insert(animator);
                 }
             }
+            Shape::Image { image, text_range } => {
+                c.insert(TransformBundle::from_transform(transform));
+                c.insert(VisibilityBundle::default());
+                if let Some(animator) =
+                    self.transform_animator(&shape.transform, zindex, text_range.clone())
+                {
+                    c.insert(animator);
+                }
+                let opacity = draw_mode.fill.as_ref().map(|f| f.opacity).unwrap_or(1.0);
+                if let Some(sprite) = self.glyph_sprite(image, opacity) {
+                    c.with_children(|parent| {
+                        parent.spawn(sprite);
+                    });
+                }
+                c.insert(FrameTracker(self.layer.frame_transform_hierarchy.clone()));
+                return Some(c.id());
+            }
             Shape::Group { .. } => {
                 unreachable!()
             }
@@ -352,6 +399,45 @@ impl<'a> BevyStagedLayer<'a> {
         Some(c.id())
     }
 
+    /// Sprite of a bitmap glyph, placed in the coordinate space of its glyph.
+    /// Text animators only transform it, colors and opacity are not animated.
+    /// SVG glyphs are not rasterized, `None` is returned for them
+    fn glyph_sprite(&mut self, image: &GlyphImage, opacity: f32) -> Option<SpriteBundle> {
+        let mime = match infer::get(&image.content) {
+            Some(mime) if mime.mime_type().starts_with("image") => mime.mime_type(),
+            _ => {
+                log::warn!("SVG glyphs are not supported, only bitmap glyphs are rendered");
+                return None;
+            }
+        };
+        let texture = Image::from_buffer(
+            &image.content,
+            ImageType::MimeType(mime),
+            CompressedImageFormats::NONE,
+            true,
+            bevy::render::texture::ImageSampler::Default,
+            RenderAssetUsages::RENDER_WORLD,
+        )
+        .map_err(|e| log::warn!("cannot load glyph image: {:?}", e))
+        .ok()?;
+        let size = texture.size();
+        let center = image.origin + image.size / 2.0;
+        // the camera flips y, so images are flipped back
+        let transform =
+            Transform::from_translation(Vec3::new(center.x, center.y, 0.0)).with_scale(Vec3::new(
+                image.size.x / size.x as f32,
+                -image.size.y / size.y as f32,
+                1.0,
+            ));
+        let mut bundle = SpriteBundle {
+            texture: self.image_assets.add(texture),
+            transform,
+            ..Default::default()
+        };
+        bundle.sprite.color = Color::rgba(1.0, 1.0, 1.0, opacity);
+        Some(bundle)
+    }
+
     fn transform_animator(
         &self,
         transform: &LottieTransform,
@@ -395,6 +481,13 @@ impl<'a> BevyStagedLayer<'a> {
     }
 
     fn draw_mode_animator(&self, shape: &StyledShape) -> Option<Animator<DrawMode>> {
+        if let Shape::Path {
+            text_range: Some(text_range),
+            ..
+        } = &shape.shape.shape
+        {
+            return Some(self.text_style_animator(shape, text_range.clone()));
+        }
         let mut tweens = vec![];
         let frame_rate = self.layer.frame_rate;
         if let Some(stroke) = shape.stroke.as_ref() {
@@ -433,6 +526,41 @@ impl<'a> BevyStagedLayer<'a> {
         }
     }
 
+    /// Glyphs with text animators evaluate opacity, colors and stroke width
+    /// as a whole
+    fn text_style_animator(
+        &self,
+        shape: &StyledShape,
+        text_range: TextRangeInfo,
+    ) -> Animator<DrawMode> {
+        let fill = match &shape.fill {
+            AnyFill::Solid(fill) => Some((fill.color.clone(), fill.opacity.clone())),
+            // only gradient layers of color glyphs, whose colors are kept
+            AnyFill::Gradient(fill) if text_range.color_glyph => Some((
+                Animated::from_value(Rgb::new_u8(0, 0, 0)),
+                fill.opacity.clone(),
+            )),
+            AnyFill::Gradient(_) => None,
+        };
+        let lens = TextStyleLens {
+            opacity: OpacityHierarchy::from(&self.layer.transform_hierarchy),
+            frames: self.layer.end_frame,
+            text_range,
+            fill,
+            stroke: match shape.stroke.as_ref() {
+                Some(AnyStroke::Solid(stroke)) => Some((
+                    stroke.color.clone(),
+                    stroke.opacity.clone(),
+                    stroke.width.clone(),
+                )),
+                _ => None,
+            },
+        };
+        let secs = (lens.frames / self.layer.frame_rate).max(f32::EPSILON);
+        let tween = Tween::new(EaseMethod::Linear, Duration::from_secs_f32(secs), lens);
+        Animator::new(tween).with_state(AnimatorState::Paused)
+    }
+
     fn path_animator(
         &self,
         factory: impl PathFactory + Send + Sync + 'static,
@@ -1,7 +1,7 @@
 use std::fs::File;
 use std::path::PathBuf;
 
-use lottie::{Config, Error, HeadlessConfig, Lottie, Renderer};
+use lottie::{Config, Error, FileResolver, FontDB, HeadlessConfig, Lottie, Renderer};
 use lottie_renderer_bevy::BevyRenderer;
 use rstest::rstest;
 use smol::stream::StreamExt;
@@ -11,7 +11,13 @@ fn check_fixture(
     #[files("../../fixtures/ui/checked/**/*.json")] path: PathBuf,
 ) -> Result<(), Error> {
     let f = File::open(&path)?;
-    let lottie = Lottie::from_reader(f, "../../")?;
+    // Only the bundled font, so that renders do not depend on the machine
+    let mut fontdb = FontDB::default();
+    fontdb.register_font(
+        "Fira Mono",
+        std::fs::read("../../assets/FiraMono-Regular.ttf")?,
+    )?;
+    let lottie = Lottie::from_reader_with_fontdb(f, fontdb, &FileResolver::new("../../"))?;
     let (mut renderer, frame_stream) = BevyRenderer::new();
     renderer.load_lottie(
         lottie,